@@ -1,3 +1,4 @@
+use crate::content_filter::ContentFilter;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -20,6 +21,12 @@ pub struct MangaDexResponse {
     pub total: u32,
 }
 
+/// Single-entity response, e.g. GET /manga/{id}
+#[derive(Debug, Deserialize)]
+pub struct MangaDexEntityResponse {
+    pub data: MangaData,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct FilteredMangaResponse {
     pub english_manga: Vec<MangaData>,
@@ -42,6 +49,8 @@ pub struct MangaData {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MangaAttributes {
     pub title: HashMap<String, String>,
+    #[serde(rename = "altTitles", default)]
+    pub alt_titles: Vec<HashMap<String, String>>,
     pub description: HashMap<String, String>,
     pub status: String,
     #[serde(rename = "lastVolume")]
@@ -88,6 +97,32 @@ pub enum MangaDexClientError {
     RequestFailed(String),
 }
 
+/// User agent sent to MangaDex and its at-home image servers
+pub const UPSTREAM_USER_AGENT: &str = "mangadownloader/0.1 (ravin.bhakta@gmail.com)";
+
+fn upstream_builder() -> reqwest::ClientBuilder {
+    Client::builder()
+        .user_agent(UPSTREAM_USER_AGENT)
+        .connect_timeout(std::time::Duration::from_secs(10))
+        .timeout(std::time::Duration::from_secs(60))
+}
+
+/// Build the HTTP client shared by everything that fetches directly from MangaDex
+pub fn upstream_client() -> Client {
+    upstream_builder()
+        .build()
+        .expect("Failed to build upstream reqwest client")
+}
+
+/// Like `upstream_client`, but redirects come back as responses instead of
+/// being followed, for callers that vet every URL before fetching it
+pub fn upstream_client_without_redirects() -> Client {
+    upstream_builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build upstream reqwest client")
+}
+
 // --- MangaDexClient ---
 #[derive(Clone)]
 pub struct MangaDexClient {
@@ -133,14 +168,10 @@ impl MangaDexClient {
             }
         }
 
-        let mangadex_response: MangaDexResponse = serde_json::from_str(&body_text)
+        let mangadex_response: MangaDexEntityResponse = serde_json::from_str(&body_text)
             .map_err(|e| MangaDexClientError::RequestFailed(format!("Failed to parse success JSON: {}", e)))?;
 
-        mangadex_response
-            .data
-            .into_iter()
-            .next()
-            .ok_or_else(|| MangaDexClientError::NotFound(format!("No data found for ID '{}'", id)))
+        Ok(mangadex_response.data)
     }
 
     pub async fn search_manga(
@@ -148,8 +179,10 @@ impl MangaDexClient {
         title: &str,
         limit: Option<u32>,
         offset: Option<u32>,
+        content: &ContentFilter,
     ) -> Result<MangaDexResponse, MangaDexClientError> {
         let mut url = format!("{}/manga?title={}", self.base_url, urlencoding::encode(title));
+        url.push_str(&content.mangadex_query());
 
         if let Some(l) = limit {
             url.push_str(&format!("&limit={}", l));
@@ -185,9 +218,10 @@ impl MangaDexClient {
         limit: Option<u32>,
         offset: Option<u32>,
         include_non_english: bool,
+        content: &ContentFilter,
     ) -> Result<FilteredMangaResponse, MangaDexClientError> {
         // First get all results
-        let response = self.search_manga(title, limit, offset).await?;
+        let response = self.search_manga(title, limit, offset, content).await?;
 
         // Filter manga based on English content availability
         let (english_manga, non_english_manga) = Self::filter_manga_by_language(&response.data);
@@ -317,6 +351,7 @@ mod tests {
             item_type: "manga".to_string(),
             attributes: MangaAttributes {
                 title,
+                alt_titles: Vec::new(),
                 description,
                 status: "ongoing".to_string(),
                 last_volume: None,
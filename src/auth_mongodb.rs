@@ -10,6 +10,7 @@ use mongodb::{Client, Collection, Database};
 use serde::{Deserialize, Serialize};
 use std::env;
 use uuid::Uuid;
+use crate::pagination::{keyset_sort, next_page_cursor, parse_cursor, InvalidCursor, PaginationParams};
 
 // JWT Claims structure
 #[derive(Debug, Serialize, Deserialize)]
@@ -139,6 +140,8 @@ pub struct UserListResponse {
     pub users: Option<Vec<UserPublic>>,
     pub total_count: Option<i64>,
     pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -625,8 +628,7 @@ impl AuthService {
     pub async fn list_users(
         &self,
         admin_user_id: &str,
-        page: Option<i64>,
-        limit: Option<i64>,
+        pagination: &PaginationParams,
     ) -> Result<UserListResponse, Box<dyn std::error::Error>> {
         // Verify admin permissions
         if let Some(admin_user) = self.get_user_by_id(admin_user_id).await? {
@@ -636,6 +638,7 @@ impl AuthService {
                     users: None,
                     total_count: None,
                     message: Some("Insufficient permissions".to_string()),
+                    next_cursor: None,
                 });
             }
         } else {
@@ -644,6 +647,7 @@ impl AuthService {
                 users: None,
                 total_count: None,
                 message: Some("Admin user not found".to_string()),
+                next_cursor: None,
             });
         }
 
@@ -652,21 +656,29 @@ impl AuthService {
         // Get total count
         let total_count = users.count_documents(doc! {}).await?;
 
-        // Apply pagination
-        let page = page.unwrap_or(1).max(1);
-        let limit = limit.unwrap_or(10).min(100); // Max 100 users per page
-        let skip = (page - 1) * limit;
+        // Apply pagination: a cursor continues after the last user it saw,
+        // otherwise fall back to page/limit
+        let limit = pagination.limit();
+        let (filter, skip) = match parse_cursor(pagination.cursor.as_deref())? {
+            Some(cursor) => (cursor.keyset_filter("created_at", -1)?, 0),
+            None => (doc! {}, pagination.skip()),
+        };
 
         let mut cursor = users
-            .find(doc! {})
-            .sort(doc! {"created_at": -1})
-            .skip(skip as u64)
-            .limit(limit)
+            .find(filter)
+            .sort(keyset_sort("created_at", -1))
+            .skip(skip)
+            .limit(limit + 1)
             .await?;
 
-        let mut user_list = Vec::new();
+        let mut page_users: Vec<User> = Vec::new();
         while cursor.advance().await? {
-            let user = cursor.deserialize_current()?;
+            page_users.push(cursor.deserialize_current()?);
+        }
+        let next_cursor = next_page_cursor(&mut page_users, limit as usize, "created_at");
+
+        let mut user_list = Vec::new();
+        for user in page_users {
             user_list.push(UserPublic {
                 id: user.user_id,
                 username: user.username,
@@ -684,6 +696,7 @@ impl AuthService {
             users: Some(user_list.clone()),
             total_count: Some(total_count as i64),
             message: Some(format!("Retrieved {} users", user_list.len())),
+            next_cursor,
         })
     }
 
@@ -915,7 +928,7 @@ pub async fn logout_handler() -> impl IntoResponse {
 }
 
 // Extract JWT token from Authorization header
-fn extract_token_from_header(headers: &HeaderMap) -> Option<String> {
+pub fn extract_token_from_header(headers: &HeaderMap) -> Option<String> {
     if let Some(auth_header) = headers.get("Authorization") {
         if let Ok(auth_str) = auth_header.to_str() {
             if auth_str.starts_with("Bearer ") {
@@ -1055,17 +1068,12 @@ pub async fn update_profile_handler(
     }
 }
 
-#[derive(serde::Deserialize)]
-pub struct PaginationQuery {
-    page: Option<i64>,
-    limit: Option<i64>,
-}
-
 pub async fn list_users_handler(
     State(auth_service): State<AuthService>,
     headers: HeaderMap,
-    Query(pagination): Query<PaginationQuery>,
+    Query(mut pagination): Query<PaginationParams>,
 ) -> impl IntoResponse {
+    pagination.validate();
     let token = match extract_token_from_header(&headers) {
         Some(token) => token,
         None => {
@@ -1076,36 +1084,48 @@ pub async fn list_users_handler(
                     users: None,
                     total_count: None,
                     message: Some("No authorization token provided".to_string()),
+                    next_cursor: None,
                 }),
             )
         }
     };
 
-    match auth_service.verify_token(&token).await {
-        Ok(claims) => {
-            match auth_service
-                .list_users(&claims.sub, pagination.page, pagination.limit)
-                .await
-            {
-                Ok(response) => (StatusCode::OK, Json(response)),
-                Err(_) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(UserListResponse {
-                        success: false,
-                        users: None,
-                        total_count: None,
-                        message: Some("Internal server error".to_string()),
-                    }),
-                ),
-            }
+    let claims = match auth_service.verify_token(&token).await {
+        Ok(claims) => claims,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(UserListResponse {
+                    success: false,
+                    users: None,
+                    total_count: None,
+                    message: Some("Invalid token".to_string()),
+                    next_cursor: None,
+                }),
+            )
         }
+    };
+
+    match auth_service.list_users(&claims.sub, &pagination).await {
+        Ok(response) => (StatusCode::OK, Json(response)),
+        Err(e) if e.is::<InvalidCursor>() => (
+            StatusCode::BAD_REQUEST,
+            Json(UserListResponse {
+                success: false,
+                users: None,
+                total_count: None,
+                message: Some(e.to_string()),
+                next_cursor: None,
+            }),
+        ),
         Err(_) => (
-            StatusCode::UNAUTHORIZED,
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(UserListResponse {
                 success: false,
                 users: None,
                 total_count: None,
-                message: Some("Invalid token".to_string()),
+                message: Some("Internal server error".to_string()),
+                next_cursor: None,
             }),
         ),
     }
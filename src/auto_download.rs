@@ -0,0 +1,687 @@
+use crate::downloader::{sanitize_path_component, ChapterDownloadRequest, Downloader, Quality};
+use crate::progress::{LibraryEntry, ProgressService};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Failed chapters are retried on later runs until this many attempts
+const MAX_ATTEMPTS: u32 = 3;
+
+/// How an auto-downloaded chapter is stored on disk
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Images,
+    Cbz,
+}
+
+/// Per-library-entry rule for downloading new chapters automatically
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AutoDownloadRule {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_languages")]
+    pub languages: Vec<String>,
+    // Scanlation group IDs in order of preference; empty accepts any group
+    #[serde(default)]
+    pub scanlation_groups: Vec<String>,
+    #[serde(default)]
+    pub quality: Quality,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_languages() -> Vec<String> {
+    vec!["en".to_string()]
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Paused,
+    Completed,
+    Failed,
+}
+
+/// A chapter queued for download on behalf of a user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AutoDownloadJob {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub manga_id: String,
+    pub manga_title: String,
+    pub chapter_id: String,
+    pub chapter_number: Option<String>,
+    pub language: String,
+    pub quality: Quality,
+    pub output_format: OutputFormat,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub bytes: u64,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// --- MangaDex feed structures (only the fields the scheduler needs) ---
+
+#[derive(Debug, Deserialize)]
+struct FeedResponse {
+    data: Vec<FeedChapter>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedChapter {
+    pub id: String,
+    pub attributes: FeedChapterAttributes,
+    #[serde(default)]
+    pub relationships: Vec<FeedRelationship>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedChapterAttributes {
+    pub chapter: Option<String>,
+    #[serde(rename = "translatedLanguage")]
+    pub translated_language: String,
+    #[serde(default)]
+    pub pages: u32,
+    #[serde(rename = "externalUrl")]
+    pub external_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedRelationship {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub rel_type: String,
+}
+
+impl FeedChapter {
+    pub(crate) fn group_ids(&self) -> impl Iterator<Item = &str> {
+        self.relationships
+            .iter()
+            .filter(|r| r.rel_type == "scanlation_group")
+            .map(|r| r.id.as_str())
+    }
+}
+
+/// Feed page size; MangaDex caps offset + limit at 10000
+const FEED_PAGE_SIZE: usize = 500;
+const FEED_MAX_OFFSET: usize = 10_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FeedError {
+    #[error("MangaDex feed request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("MangaDex feed returned HTTP {0}")]
+    Status(reqwest::StatusCode),
+}
+
+/// Walk a manga's feed in chapter order, a page at a time, until `visit`
+/// breaks or the feed runs out. Returns what `visit` broke with.
+pub(crate) async fn walk_feed<T>(
+    client: &reqwest::Client,
+    manga_id: &str,
+    languages: &[String],
+    mut visit: impl FnMut(Vec<FeedChapter>) -> ControlFlow<T>,
+) -> Result<Option<T>, FeedError> {
+    let mut offset = 0;
+    while offset + FEED_PAGE_SIZE <= FEED_MAX_OFFSET {
+        let mut url = format!(
+            "https://api.mangadex.org/manga/{}/feed?limit={}&offset={}&order[chapter]=asc&includes[]=scanlation_group",
+            manga_id, FEED_PAGE_SIZE, offset
+        );
+        for language in languages {
+            url.push_str(&format!("&translatedLanguage[]={}", urlencoding::encode(language)));
+        }
+
+        let response = client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(FeedError::Status(response.status()));
+        }
+        let page = response.json::<FeedResponse>().await?.data;
+        let last_page = page.len() < FEED_PAGE_SIZE;
+        if let ControlFlow::Break(found) = visit(page) {
+            return Ok(Some(found));
+        }
+        if last_page {
+            break;
+        }
+        offset += FEED_PAGE_SIZE;
+    }
+    Ok(None)
+}
+
+/// Pick the chapters a rule wants: one upload per chapter number and language,
+/// preferring groups earlier in the rule's list. External chapters are skipped.
+pub fn select_chapters<'a>(rule: &AutoDownloadRule, chapters: &'a [FeedChapter]) -> Vec<&'a FeedChapter> {
+    let group_rank = |chapter: &FeedChapter| -> Option<usize> {
+        if rule.scanlation_groups.is_empty() {
+            return Some(0);
+        }
+        chapter
+            .group_ids()
+            .filter_map(|id| rule.scanlation_groups.iter().position(|g| g == id))
+            .min()
+    };
+
+    let mut best: HashMap<(String, String), (usize, &FeedChapter)> = HashMap::new();
+    let mut order = Vec::new();
+
+    for chapter in chapters {
+        let attrs = &chapter.attributes;
+        if attrs.external_url.is_some() || attrs.pages == 0 {
+            continue;
+        }
+        if !rule.languages.is_empty() && !rule.languages.contains(&attrs.translated_language) {
+            continue;
+        }
+        let Some(rank) = group_rank(chapter) else {
+            continue;
+        };
+
+        // Oneshots have no chapter number; key them by ID so they are never merged
+        let number = attrs.chapter.clone().unwrap_or_else(|| chapter.id.clone());
+        let key = (number, attrs.translated_language.clone());
+        match best.get(&key) {
+            Some((existing, _)) if *existing <= rank => {}
+            Some(_) => {
+                best.insert(key, (rank, chapter));
+            }
+            None => {
+                order.push(key.clone());
+                best.insert(key, (rank, chapter));
+            }
+        }
+    }
+
+    order.iter().map(|key| best[key].1).collect()
+}
+
+/// Total size of all files below a directory
+pub async fn dir_size(root: &Path) -> u64 {
+    let mut total = 0;
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.metadata().await {
+                Ok(meta) if meta.is_dir() => stack.push(entry.path()),
+                Ok(meta) => total += meta.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    total
+}
+
+/// Pack a downloaded chapter directory into `<dir>.cbz` and remove the loose pages
+pub async fn package_cbz(chapter_dir: PathBuf) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    tokio::task::spawn_blocking(move || {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut pages: Vec<PathBuf> = std::fs::read_dir(&chapter_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .map(|n| !n.starts_with('.') && !n.ends_with(".part"))
+                        .unwrap_or(false)
+            })
+            .collect();
+        pages.sort();
+
+        let mut cbz_name = chapter_dir.as_os_str().to_owned();
+        cbz_name.push(".cbz");
+        let cbz_path = PathBuf::from(cbz_name);
+
+        let mut tmp_name = cbz_path.as_os_str().to_owned();
+        tmp_name.push(".part");
+        let tmp_path = PathBuf::from(tmp_name);
+
+        // Images are already compressed, so store them as-is
+        let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&tmp_path)?);
+        for page in &pages {
+            let name = page.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            writer.start_file(name, options)?;
+            writer.write_all(&std::fs::read(page)?)?;
+        }
+        writer.finish()?.sync_all()?;
+
+        std::fs::rename(&tmp_path, &cbz_path)?;
+        std::fs::remove_dir_all(&chapter_dir)?;
+        Ok(cbz_path)
+    })
+    .await?
+}
+
+#[derive(Debug, Serialize)]
+pub struct AutoDownloadUsage {
+    pub used_bytes: u64,
+    pub quota_bytes: u64,
+    pub paused: bool,
+}
+
+/// Scheduler that keeps followed series downloaded
+#[derive(Clone)]
+pub struct AutoDownloadService {
+    progress_service: ProgressService,
+    downloader: Downloader,
+    client: reqwest::Client,
+    download_root: PathBuf,
+    interval: Duration,
+    quota_bytes: u64,
+}
+
+impl AutoDownloadService {
+    pub fn new(progress_service: ProgressService, downloader: Downloader, client: reqwest::Client) -> Self {
+        let download_root =
+            env::var("AUTO_DOWNLOAD_ROOT").unwrap_or_else(|_| "manga_storage".to_string());
+        let interval_secs = env::var("AUTO_DOWNLOAD_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+        let quota_mb: u64 = env::var("AUTO_DOWNLOAD_QUOTA_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2048);
+
+        Self {
+            progress_service,
+            downloader,
+            client,
+            download_root: PathBuf::from(download_root),
+            interval: Duration::from_secs(interval_secs.max(60)),
+            quota_bytes: quota_mb * 1024 * 1024,
+        }
+    }
+
+    fn jobs_collection(&self) -> Collection<AutoDownloadJob> {
+        self.progress_service.db().collection("auto_downloads")
+    }
+
+    fn user_root(&self, user_id: &str) -> PathBuf {
+        self.download_root.join(sanitize_path_component(user_id))
+    }
+
+    pub async fn create_indexes(&self) -> Result<(), Box<dyn std::error::Error>> {
+        use mongodb::{options::IndexOptions, IndexModel};
+
+        let jobs = self.jobs_collection();
+        jobs.create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1, "chapter_id": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .name("user_chapter_unique".to_string())
+                        .build(),
+                )
+                .build(),
+        )
+        .await?;
+
+        jobs.create_index(
+            IndexModel::builder()
+                .keys(doc! { "status": 1, "created_at": 1 })
+                .build(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Start the background loop checking feeds on the configured interval
+    pub fn spawn_scheduler(self) {
+        tokio::spawn(async move {
+            if let Err(e) = self.create_indexes().await {
+                tracing::warn!("⚠️  Failed to create auto-download indexes: {}", e);
+            }
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.run_once().await {
+                    tracing::error!("Auto-download run failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Check feeds for every followed series, queue new chapters and work the queue
+    pub async fn run_once(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = self.progress_service.get_auto_download_entries().await?;
+        tracing::info!("⏬ Auto-download check for {} library entries", entries.len());
+
+        for entry in &entries {
+            match self.enqueue_new_chapters(entry).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!(
+                    "⏬ Queued {} new chapters of {} for user {}",
+                    count,
+                    entry.manga_title,
+                    entry.user_id
+                ),
+                Err(e) => tracing::warn!(
+                    "Failed to check feed of {} for user {}: {}",
+                    entry.manga_id,
+                    entry.user_id,
+                    e
+                ),
+            }
+        }
+
+        self.process_queue().await
+    }
+
+    /// The whole feed in the rule's languages, page by page, so the newest
+    /// chapters of long series are included
+    async fn fetch_feed(&self, manga_id: &str, languages: &[String]) -> Result<Vec<FeedChapter>, FeedError> {
+        let mut chapters = Vec::new();
+        walk_feed(&self.client, manga_id, languages, |page| {
+            chapters.extend(page);
+            ControlFlow::<()>::Continue(())
+        })
+        .await?;
+        Ok(chapters)
+    }
+
+    async fn enqueue_new_chapters(&self, entry: &LibraryEntry) -> Result<usize, Box<dyn std::error::Error>> {
+        let Some(rule) = entry.auto_download.as_ref().filter(|r| r.enabled) else {
+            return Ok(0);
+        };
+
+        let feed = self.fetch_feed(&entry.manga_id, &rule.languages).await?;
+        let wanted = select_chapters(rule, &feed);
+
+        let jobs = self.jobs_collection();
+        let mut known = HashSet::new();
+        let mut cursor = jobs
+            .find(doc! { "user_id": &entry.user_id, "manga_id": &entry.manga_id })
+            .await?;
+        while cursor.advance().await? {
+            known.insert(cursor.deserialize_current()?.chapter_id);
+        }
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut queued = 0;
+        for chapter in wanted.into_iter().filter(|c| !known.contains(&c.id)) {
+            let job = AutoDownloadJob {
+                id: None,
+                user_id: entry.user_id.clone(),
+                manga_id: entry.manga_id.clone(),
+                manga_title: entry.manga_title.clone(),
+                chapter_id: chapter.id.clone(),
+                chapter_number: chapter.attributes.chapter.clone(),
+                language: chapter.attributes.translated_language.clone(),
+                quality: rule.quality,
+                output_format: rule.output_format,
+                status: JobStatus::Queued,
+                attempts: 0,
+                bytes: 0,
+                error: None,
+                created_at: now.clone(),
+                updated_at: now.clone(),
+            };
+            jobs.insert_one(&job).await?;
+            queued += 1;
+        }
+
+        Ok(queued)
+    }
+
+    /// Work queued jobs user by user, pausing users over their storage quota
+    async fn process_queue(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let jobs = self.jobs_collection();
+        let mut cursor = jobs
+            .find(doc! {
+                "$or": [
+                    { "status": { "$in": ["queued", "paused"] } },
+                    { "status": "failed", "attempts": { "$lt": MAX_ATTEMPTS } }
+                ]
+            })
+            .sort(doc! { "created_at": 1 })
+            .await?;
+
+        let mut by_user: HashMap<String, Vec<AutoDownloadJob>> = HashMap::new();
+        while cursor.advance().await? {
+            let job = cursor.deserialize_current()?;
+            by_user.entry(job.user_id.clone()).or_default().push(job);
+        }
+
+        for (user_id, user_jobs) in by_user {
+            let mut used = dir_size(&self.user_root(&user_id)).await;
+
+            for job in user_jobs {
+                if used >= self.quota_bytes {
+                    if job.status != JobStatus::Paused
+                        && let Err(e) = self
+                            .set_status(&job, JobStatus::Paused, Some("Storage quota exceeded".to_string()))
+                            .await
+                    {
+                        tracing::warn!("Failed to pause auto-download job {}: {}", job.chapter_id, e);
+                    }
+                    continue;
+                }
+
+                // One job's database error should not hold up everyone else's
+                match self.run_job(&job).await {
+                    Ok(bytes) => used += bytes,
+                    Err(e) => tracing::warn!("Auto-download job {} failed: {}", job.chapter_id, e),
+                }
+            }
+
+            if used >= self.quota_bytes {
+                tracing::warn!(
+                    "⏸️  Auto-downloads paused for user {}: {} of {} bytes used",
+                    user_id,
+                    used,
+                    self.quota_bytes
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_job(&self, job: &AutoDownloadJob) -> Result<u64, Box<dyn std::error::Error>> {
+        let chapter_title = match &job.chapter_number {
+            Some(number) => format!("Chapter {} [{}]", number, job.language),
+            None => format!("Oneshot {} [{}]", job.chapter_id, job.language),
+        };
+        let request = ChapterDownloadRequest {
+            chapter_id: job.chapter_id.clone(),
+            save_root: self.user_root(&job.user_id),
+            quality: job.quality,
+            manga_title: Some(job.manga_title.clone()),
+            chapter_title: Some(chapter_title),
+        };
+
+        let (status, bytes, error) = match self.downloader.download_chapter(&request).await {
+            Ok(report) if report.complete => {
+                let packaged = match job.output_format {
+                    OutputFormat::Cbz => package_cbz(PathBuf::from(&report.save_path)).await.map(|_| ()),
+                    OutputFormat::Images => Ok(()),
+                };
+                match packaged {
+                    Ok(()) => (JobStatus::Completed, report.bytes_downloaded, None),
+                    Err(e) => {
+                        tracing::warn!("Failed to package {} as CBZ: {}", job.chapter_id, e);
+                        (
+                            JobStatus::Failed,
+                            report.bytes_downloaded,
+                            Some(format!("CBZ packaging failed: {}", e)),
+                        )
+                    }
+                }
+            }
+            Ok(report) => (
+                JobStatus::Failed,
+                report.bytes_downloaded,
+                Some(format!("{} pages failed", report.failed_downloads.len())),
+            ),
+            Err(e) => (JobStatus::Failed, 0, Some(e.to_string())),
+        };
+
+        self.jobs_collection()
+            .update_one(
+                doc! { "_id": &job.id },
+                doc! {
+                    "$set": {
+                        "status": mongodb::bson::to_bson(&status)?,
+                        "bytes": (job.bytes + bytes) as i64,
+                        "error": error,
+                        "updated_at": chrono::Utc::now().to_rfc3339()
+                    },
+                    "$inc": { "attempts": 1 }
+                },
+            )
+            .await?;
+
+        Ok(bytes)
+    }
+
+    async fn set_status(
+        &self,
+        job: &AutoDownloadJob,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.jobs_collection()
+            .update_one(
+                doc! { "_id": &job.id },
+                doc! {
+                    "$set": {
+                        "status": mongodb::bson::to_bson(&status)?,
+                        "error": error,
+                        "updated_at": chrono::Utc::now().to_rfc3339()
+                    }
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Recent auto-download jobs for a user
+    pub async fn list_jobs(
+        &self,
+        user_id: &str,
+        limit: u32,
+    ) -> Result<Vec<AutoDownloadJob>, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .jobs_collection()
+            .find(doc! { "user_id": user_id })
+            .sort(doc! { "updated_at": -1 })
+            .limit(limit as i64)
+            .await?;
+
+        let mut jobs = Vec::new();
+        while cursor.advance().await? {
+            jobs.push(cursor.deserialize_current()?);
+        }
+        Ok(jobs)
+    }
+
+    pub async fn usage(&self, user_id: &str) -> AutoDownloadUsage {
+        let used_bytes = dir_size(&self.user_root(user_id)).await;
+        AutoDownloadUsage {
+            used_bytes,
+            quota_bytes: self.quota_bytes,
+            paused: used_bytes >= self.quota_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(id: &str, number: Option<&str>, lang: &str, group: &str) -> FeedChapter {
+        FeedChapter {
+            id: id.to_string(),
+            attributes: FeedChapterAttributes {
+                chapter: number.map(|n| n.to_string()),
+                translated_language: lang.to_string(),
+                pages: 20,
+                external_url: None,
+            },
+            relationships: vec![FeedRelationship {
+                id: group.to_string(),
+                rel_type: "scanlation_group".to_string(),
+            }],
+        }
+    }
+
+    fn rule(languages: &[&str], groups: &[&str]) -> AutoDownloadRule {
+        AutoDownloadRule {
+            enabled: true,
+            languages: languages.iter().map(|s| s.to_string()).collect(),
+            scanlation_groups: groups.iter().map(|s| s.to_string()).collect(),
+            quality: Quality::Data,
+            output_format: OutputFormat::Images,
+        }
+    }
+
+    #[test]
+    fn test_select_filters_language_and_dedupes() {
+        let feed = vec![
+            chapter("a", Some("1"), "en", "g1"),
+            chapter("b", Some("1"), "en", "g2"),
+            chapter("c", Some("1"), "fr", "g1"),
+            chapter("d", Some("2"), "en", "g2"),
+        ];
+        let ids: Vec<_> = select_chapters(&rule(&["en"], &[]), &feed)
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["a", "d"]);
+    }
+
+    #[test]
+    fn test_select_prefers_listed_groups() {
+        let feed = vec![
+            chapter("a", Some("1"), "en", "g1"),
+            chapter("b", Some("1"), "en", "g2"),
+            chapter("c", Some("2"), "en", "g3"),
+        ];
+        let ids: Vec<_> = select_chapters(&rule(&["en"], &["g2", "g1"]), &feed)
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["b"]);
+    }
+
+    #[test]
+    fn test_select_skips_external_chapters() {
+        let mut external = chapter("a", Some("1"), "en", "g1");
+        external.attributes.external_url = Some("https://example.com".to_string());
+        external.attributes.pages = 0;
+        assert!(select_chapters(&rule(&["en"], &[]), &[external]).is_empty());
+    }
+
+    #[test]
+    fn test_rule_defaults() {
+        let rule: AutoDownloadRule = serde_json::from_str("{}").unwrap();
+        assert!(rule.enabled);
+        assert_eq!(rule.languages, vec!["en"]);
+        assert_eq!(rule.quality, Quality::Data);
+        assert_eq!(rule.output_format, OutputFormat::Images);
+    }
+}
@@ -206,14 +206,22 @@ pub struct CacheStats {
 
 /// Cache key builders for consistency
 pub mod cache_keys {
-    pub fn manga_search(query: &str, limit: u32, offset: u32) -> String {
-        format!("manga:search:{}:{}:{}", query, limit, offset)
+    pub fn manga_search(query: &str, limit: u32, offset: u32, filter: &str) -> String {
+        format!("manga:search:{}:{}:{}:{}", query, limit, offset, filter)
     }
 
     pub fn manga_details(manga_id: &str) -> String {
         format!("manga:details:{}", manga_id)
     }
 
+    pub fn manga_similar(manga_id: &str) -> String {
+        format!("manga:similar:{}", manga_id)
+    }
+
+    pub fn llm_response(prompt_hash: &str) -> String {
+        format!("llm:response:{}", prompt_hash)
+    }
+
     pub fn manga_chapters(manga_id: &str, lang: &str) -> String {
         format!("manga:chapters:{}:{}", manga_id, lang)
     }
@@ -238,8 +246,8 @@ mod tests {
     #[test]
     fn test_cache_key_generation() {
         assert_eq!(
-            cache_keys::manga_search("naruto", 20, 0),
-            "manga:search:naruto:20:0"
+            cache_keys::manga_search("naruto", 20, 0, "safe"),
+            "manga:search:naruto:20:0:safe"
         );
         assert_eq!(
             cache_keys::manga_details("123"),
@@ -1,5 +1,6 @@
 use crate::api::{FilteredMangaResponse, MangaData, MangaDexClient, MangaDexClientError, MangaDexResponse};
 use crate::cache::{cache_keys, CacheService};
+use crate::content_filter::ContentFilter;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -97,10 +98,11 @@ impl CachedMangaDexClient {
         title: &str,
         limit: Option<u32>,
         offset: Option<u32>,
+        content: &ContentFilter,
     ) -> Result<MangaDexResponse, MangaDexClientError> {
         let limit = limit.unwrap_or(20);
         let offset = offset.unwrap_or(0);
-        let cache_key = cache_keys::manga_search(title, limit, offset);
+        let cache_key = cache_keys::manga_search(title, limit, offset, &content.cache_key());
 
         // Try cache first
         if let Some(cache) = &self.cache {
@@ -136,7 +138,7 @@ impl CachedMangaDexClient {
 
         // Fetch from API
         tracing::info!("❌ Cache MISS: searching for '{}' from API", title);
-        let result = self.client.search_manga(title, Some(limit), Some(offset)).await;
+        let result = self.client.search_manga(title, Some(limit), Some(offset), content).await;
 
         // Cache successful result
         if let Ok(ref data) = result {
@@ -164,14 +166,15 @@ impl CachedMangaDexClient {
         limit: Option<u32>,
         offset: Option<u32>,
         include_non_english: bool,
+        content: &ContentFilter,
     ) -> Result<FilteredMangaResponse, MangaDexClientError> {
         let limit = limit.unwrap_or(20);
         let offset = offset.unwrap_or(0);
         
         // Create cache key with filter flag
         let cache_key = format!(
-            "manga:search:filtered:{}:{}:{}:{}",
-            title, limit, offset, include_non_english
+            "manga:search:filtered:{}:{}:{}:{}:{}",
+            title, limit, offset, include_non_english, content.cache_key()
         );
 
         // Try cache first
@@ -209,7 +212,7 @@ impl CachedMangaDexClient {
         // Fetch from API
         tracing::info!("❌ Cache MISS: filtered search for '{}' from API", title);
         let result = self.client
-            .search_manga_english_filtered(title, Some(limit), Some(offset), include_non_english)
+            .search_manga_english_filtered(title, Some(limit), Some(offset), include_non_english, content)
             .await;
 
         // Cache successful result
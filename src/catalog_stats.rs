@@ -0,0 +1,210 @@
+use crate::manga_service::MangaService;
+use crate::progress::ProgressService;
+use mongodb::bson::{doc, Document};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+/// Reads within this window count towards popularity
+const RECENT_READ_DAYS: i64 = 30;
+
+/// Popularity weight of a library add
+const LIBRARY_WEIGHT: f64 = 1.0;
+
+/// Popularity weight of a reader active in the recent window
+const RECENT_READER_WEIGHT: f64 = 2.0;
+
+/// Engagement numbers for one manga, aggregated from user data
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MangaStats {
+    pub library_count: i64,
+    pub rating_sum: i64,
+    pub rating_count: i64,
+    pub recent_readers: i64,
+}
+
+impl MangaStats {
+    pub fn average_rating(&self) -> Option<f64> {
+        (self.rating_count > 0).then(|| self.rating_sum as f64 / self.rating_count as f64)
+    }
+
+    pub fn popularity(&self) -> f64 {
+        self.library_count as f64 * LIBRARY_WEIGHT + self.recent_readers as f64 * RECENT_READER_WEIGHT
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LibraryGroup {
+    #[serde(rename = "_id")]
+    manga_id: String,
+    library_count: i64,
+    rating_sum: i64,
+    rating_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReaderGroup {
+    #[serde(rename = "_id")]
+    manga_id: String,
+    readers: i64,
+}
+
+/// Keeps `rating`, `rating_count` and `popularity` on catalog entries in
+/// sync with library entries and reading history
+#[derive(Clone)]
+pub struct CatalogStatsService {
+    progress_service: ProgressService,
+    manga_service: MangaService,
+    interval: Duration,
+}
+
+impl CatalogStatsService {
+    pub fn new(progress_service: ProgressService, manga_service: MangaService) -> Self {
+        let interval_secs = env::var("CATALOG_STATS_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(900);
+
+        Self {
+            progress_service,
+            manga_service,
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+
+    /// Recompute stats now and then on every interval
+    pub fn spawn_scheduler(self) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                match self.run_once().await {
+                    Ok(count) => tracing::info!("📊 Updated catalog stats for {} manga", count),
+                    Err(e) => tracing::error!("Catalog stats run failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Aggregate user data and write it to the catalog; returns how many
+    /// manga have engagement
+    pub async fn run_once(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let stats = self.collect().await?;
+        let run_at = chrono::Utc::now().to_rfc3339();
+        let collection = self.manga_service.manga_collection();
+
+        for (manga_id, entry) in &stats {
+            collection
+                .update_many(
+                    doc! { "manga_id": manga_id },
+                    doc! {
+                        "$set": {
+                            "rating": entry.average_rating(),
+                            "rating_count": entry.rating_count,
+                            "popularity": entry.popularity(),
+                            "stats_updated_at": &run_at
+                        }
+                    },
+                )
+                .await?;
+        }
+
+        // Manga that lost all their engagement since the last run
+        collection
+            .update_many(
+                doc! {
+                    "stats_updated_at": { "$ne": &run_at },
+                    "$or": [{ "popularity": { "$gt": 0 } }, { "rating": { "$ne": null } }]
+                },
+                doc! {
+                    "$set": {
+                        "rating": null,
+                        "rating_count": 0,
+                        "popularity": 0.0,
+                        "stats_updated_at": &run_at
+                    }
+                },
+            )
+            .await?;
+
+        Ok(stats.len())
+    }
+
+    async fn collect(&self) -> Result<HashMap<String, MangaStats>, Box<dyn std::error::Error>> {
+        let db = self.progress_service.db();
+        let mut stats: HashMap<String, MangaStats> = HashMap::new();
+
+        let mut cursor = db
+            .collection::<Document>("library")
+            .aggregate(library_pipeline())
+            .await?;
+        while cursor.advance().await? {
+            let group: LibraryGroup = mongodb::bson::from_document(cursor.deserialize_current()?)?;
+            let entry = stats.entry(group.manga_id).or_default();
+            entry.library_count = group.library_count;
+            entry.rating_sum = group.rating_sum;
+            entry.rating_count = group.rating_count;
+        }
+
+        let since = (chrono::Utc::now() - chrono::Duration::days(RECENT_READ_DAYS)).to_rfc3339();
+        let mut cursor = db
+            .collection::<Document>("reading_history")
+            .aggregate(recent_readers_pipeline(&since))
+            .await?;
+        while cursor.advance().await? {
+            let group: ReaderGroup = mongodb::bson::from_document(cursor.deserialize_current()?)?;
+            stats.entry(group.manga_id).or_default().recent_readers = group.readers;
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Library adds and ratings per manga
+fn library_pipeline() -> Vec<Document> {
+    vec![doc! {
+        "$group": {
+            "_id": "$manga_id",
+            "library_count": { "$sum": 1_i64 },
+            "rating_sum": { "$sum": { "$toLong": { "$ifNull": ["$rating", 0] } } },
+            "rating_count": { "$sum": { "$cond": [{ "$gt": ["$rating", 0] }, 1_i64, 0_i64] } }
+        }
+    }]
+}
+
+/// Distinct users who read each manga since `since` (RFC 3339, as stored)
+fn recent_readers_pipeline(since: &str) -> Vec<Document> {
+    vec![
+        doc! { "$match": { "timestamp": { "$gte": since } } },
+        doc! { "$group": { "_id": { "manga_id": "$manga_id", "user_id": "$user_id" } } },
+        doc! { "$group": { "_id": "$_id.manga_id", "readers": { "$sum": 1_i64 } } },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_rating_ignores_unrated() {
+        let stats = MangaStats {
+            library_count: 4,
+            rating_sum: 9,
+            rating_count: 2,
+            recent_readers: 0,
+        };
+        assert_eq!(stats.average_rating(), Some(4.5));
+        assert_eq!(MangaStats::default().average_rating(), None);
+    }
+
+    #[test]
+    fn test_popularity_weights_recent_readers() {
+        let stats = MangaStats {
+            library_count: 3,
+            recent_readers: 2,
+            ..Default::default()
+        };
+        assert_eq!(stats.popularity(), 7.0);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::auth_mongodb::{extract_token_from_header, AuthService, ReadingPreferences};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap},
+};
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Policy changes made on another instance show up after this long
+const POLICY_REFRESH: Duration = Duration::from_secs(60);
+
+const POLICY_ID: &str = "content_policy";
+
+/// MangaDex content ratings, mildest first
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRating {
+    Safe,
+    Suggestive,
+    Erotica,
+    Pornographic,
+}
+
+impl ContentRating {
+    const ALL: [ContentRating; 4] = [
+        ContentRating::Safe,
+        ContentRating::Suggestive,
+        ContentRating::Erotica,
+        ContentRating::Pornographic,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentRating::Safe => "safe",
+            ContentRating::Suggestive => "suggestive",
+            ContentRating::Erotica => "erotica",
+            ContentRating::Pornographic => "pornographic",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|rating| rating.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+
+    /// Every rating up to and including `max`
+    pub fn up_to(max: ContentRating) -> Vec<ContentRating> {
+        Self::ALL.into_iter().filter(|rating| *rating <= max).collect()
+    }
+}
+
+/// Instance-wide limits set by admins; they apply on top of every user's
+/// own preferences
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContentPolicy {
+    /// Highest content rating served to anyone
+    pub max_content_rating: ContentRating,
+    /// Chapter languages served; empty allows every language
+    #[serde(default)]
+    pub allowed_languages: Vec<String>,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+impl Default for ContentPolicy {
+    fn default() -> Self {
+        Self {
+            max_content_rating: ContentRating::Pornographic,
+            allowed_languages: Vec::new(),
+            updated_by: None,
+            updated_at: None,
+        }
+    }
+}
+
+/// What one request may see: the caller's preferences capped by the policy.
+///
+/// Manga saved before content ratings were recorded have none; they are
+/// treated as `safe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentFilter {
+    pub ratings: Vec<ContentRating>,
+    pub preferred_language: Option<String>,
+    pub allowed_languages: Vec<String>,
+}
+
+impl Default for ContentFilter {
+    /// Anonymous callers under the default policy
+    fn default() -> Self {
+        Self::resolve(&ContentPolicy::default(), None)
+    }
+}
+
+impl ContentFilter {
+    pub fn resolve(policy: &ContentPolicy, preferences: Option<&ReadingPreferences>) -> Self {
+        let user_max = match preferences {
+            Some(preferences) if preferences.mature_content => ContentRating::Pornographic,
+            _ => ContentRating::Suggestive,
+        };
+        let allowed_languages: Vec<String> = policy
+            .allowed_languages
+            .iter()
+            .map(|language| language.to_lowercase())
+            .collect();
+        let preferred_language = preferences
+            .map(|p| p.preferred_language.to_lowercase())
+            .filter(|language| !language.is_empty());
+
+        Self {
+            ratings: ContentRating::up_to(user_max.min(policy.max_content_rating)),
+            preferred_language,
+            allowed_languages,
+        }
+    }
+
+    pub fn allows(&self, content_rating: Option<&str>) -> bool {
+        let rating = content_rating
+            .and_then(ContentRating::parse)
+            .unwrap_or(ContentRating::Safe);
+        self.ratings.contains(&rating)
+    }
+
+    pub fn allows_language(&self, language: &str) -> bool {
+        self.allowed_languages.is_empty()
+            || self
+                .allowed_languages
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(language))
+    }
+
+    /// Chapter language for a feed: the requested one if allowed, then the
+    /// reader's preferred language, then the first allowed language
+    pub fn chapter_language(&self, requested: Option<String>) -> String {
+        requested
+            .into_iter()
+            .chain(self.preferred_language.clone())
+            .chain(std::iter::once("en".to_string()))
+            .find(|language| self.allows_language(language))
+            .or_else(|| self.allowed_languages.first().cloned())
+            .unwrap_or_else(|| "en".to_string())
+    }
+
+    /// Requested ratings (or all of them) that this filter allows
+    pub fn narrow(&self, requested: Option<Vec<String>>) -> Vec<String> {
+        let requested: Option<Vec<ContentRating>> =
+            requested.map(|values| values.iter().filter_map(|v| ContentRating::parse(v)).collect());
+        self.ratings
+            .iter()
+            .filter(|rating| requested.as_ref().is_none_or(|r| r.contains(rating)))
+            .map(|rating| rating.as_str().to_string())
+            .collect()
+    }
+
+    /// Restrict a catalog query to allowed ratings
+    pub fn apply(&self, filter: &mut Document) {
+        let ratings: Vec<String> = self.ratings.iter().map(|r| r.as_str().to_string()).collect();
+        filter.insert("content_rating", rating_condition(&ratings));
+    }
+
+    /// Query string forwarded to MangaDex list endpoints, starting with `&`
+    pub fn mangadex_query(&self) -> String {
+        let mut query = String::new();
+        for rating in &self.ratings {
+            query.push_str(&format!("&contentRating[]={}", rating.as_str()));
+        }
+        for language in &self.allowed_languages {
+            query.push_str(&format!(
+                "&availableTranslatedLanguage[]={}",
+                urlencoding::encode(language)
+            ));
+        }
+        query
+    }
+
+    /// Distinguishes cached upstream responses fetched under different filters
+    pub fn cache_key(&self) -> String {
+        let ratings: Vec<&str> = self.ratings.iter().map(|r| r.as_str()).collect();
+        format!("{}|{}", ratings.join(","), self.allowed_languages.join(","))
+    }
+}
+
+/// `content_rating` condition for a list of rating names; unrated manga
+/// count as `safe`
+pub fn rating_condition(ratings: &[String]) -> Document {
+    let mut values: Vec<Bson> = ratings.iter().map(|r| Bson::String(r.clone())).collect();
+    if ratings.iter().any(|r| r == ContentRating::Safe.as_str()) {
+        values.push(Bson::Null);
+    }
+    doc! { "$in": values }
+}
+
+/// Loads the instance content policy and resolves per-request filters
+#[derive(Clone)]
+pub struct ContentPolicyService {
+    db: Database,
+    auth_service: AuthService,
+    cached: Arc<RwLock<Option<(Instant, ContentPolicy)>>>,
+}
+
+impl ContentPolicyService {
+    pub fn new(db: Database, auth_service: AuthService) -> Self {
+        Self {
+            db,
+            auth_service,
+            cached: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    fn settings_collection(&self) -> Collection<Document> {
+        self.db.collection("settings")
+    }
+
+    pub async fn policy(&self) -> ContentPolicy {
+        let fresh = self
+            .cached
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|(loaded_at, _)| loaded_at.elapsed() < POLICY_REFRESH)
+            .map(|(_, policy)| policy.clone());
+        if let Some(policy) = fresh {
+            return policy;
+        }
+
+        let policy = match self.load().await {
+            Ok(policy) => policy.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to load content policy: {}", e);
+                // Keep serving the last known policy rather than loosening it
+                match self.cached.read().unwrap().as_ref() {
+                    Some((_, policy)) => return policy.clone(),
+                    None => ContentPolicy::default(),
+                }
+            }
+        };
+        *self.cached.write().unwrap() = Some((Instant::now(), policy.clone()));
+        policy
+    }
+
+    async fn load(&self) -> Result<Option<ContentPolicy>, mongodb::error::Error> {
+        let document = self
+            .settings_collection()
+            .find_one(doc! { "_id": POLICY_ID })
+            .await?;
+        Ok(document.and_then(|d| mongodb::bson::from_document(d).ok()))
+    }
+
+    pub async fn set_policy(
+        &self,
+        policy: ContentPolicy,
+        admin_user_id: &str,
+    ) -> Result<ContentPolicy, Box<dyn std::error::Error>> {
+        let policy = ContentPolicy {
+            updated_by: Some(admin_user_id.to_string()),
+            updated_at: Some(chrono::Utc::now().to_rfc3339()),
+            ..policy
+        };
+        let mut document = mongodb::bson::to_document(&policy)?;
+        document.insert("_id", POLICY_ID);
+        self.settings_collection()
+            .replace_one(doc! { "_id": POLICY_ID }, document)
+            .upsert(true)
+            .await?;
+        *self.cached.write().unwrap() = Some((Instant::now(), policy.clone()));
+        Ok(policy)
+    }
+
+    /// Filter for the caller behind the request's bearer token, or for an
+    /// anonymous caller
+    pub async fn filter_for(&self, headers: &HeaderMap) -> ContentFilter {
+        let policy = self.policy().await;
+        let preferences = self.reading_preferences(headers).await;
+        ContentFilter::resolve(&policy, preferences.as_ref())
+    }
+
+    async fn reading_preferences(&self, headers: &HeaderMap) -> Option<ReadingPreferences> {
+        let token = extract_token_from_header(headers)?;
+        let claims = self.auth_service.verify_token(&token).await.ok()?;
+        let user = self.auth_service.get_user_by_id(&claims.sub).await.ok()??;
+        Some(user.profile.reading_preferences)
+    }
+}
+
+/// Handlers take a `ContentFilter` argument to get the caller's filter. The
+/// service comes from an `Extension` layer; without it the anonymous
+/// default applies.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ContentFilter {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match parts.extensions.get::<ContentPolicyService>().cloned() {
+            Some(service) => Ok(service.filter_for(&parts.headers).await),
+            None => Ok(ContentFilter::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preferences(language: &str, mature_content: bool) -> ReadingPreferences {
+        ReadingPreferences {
+            preferred_language: language.to_string(),
+            mature_content,
+            notifications_enabled: false,
+        }
+    }
+
+    #[test]
+    fn test_policy_caps_user_preferences() {
+        let policy = ContentPolicy {
+            max_content_rating: ContentRating::Erotica,
+            ..Default::default()
+        };
+        let mature = ContentFilter::resolve(&policy, Some(&preferences("en", true)));
+        assert_eq!(mature.ratings, ContentRating::up_to(ContentRating::Erotica));
+        assert!(!mature.allows(Some("pornographic")));
+
+        let anonymous = ContentFilter::resolve(&policy, None);
+        assert_eq!(anonymous.ratings, ContentRating::up_to(ContentRating::Suggestive));
+        assert!(anonymous.allows(None));
+        assert!(!anonymous.allows(Some("erotica")));
+    }
+
+    #[test]
+    fn test_chapter_language_respects_allowed_languages() {
+        let policy = ContentPolicy {
+            allowed_languages: vec!["es".to_string(), "pt-br".to_string()],
+            ..Default::default()
+        };
+        let filter = ContentFilter::resolve(&policy, Some(&preferences("pt-br", false)));
+        assert_eq!(filter.chapter_language(Some("es".to_string())), "es");
+        assert_eq!(filter.chapter_language(Some("fr".to_string())), "pt-br");
+        assert_eq!(ContentFilter::resolve(&policy, None).chapter_language(None), "es");
+        assert_eq!(ContentFilter::default().chapter_language(None), "en");
+    }
+
+    #[test]
+    fn test_narrow_and_upstream_query() {
+        let filter = ContentFilter::default();
+        assert_eq!(
+            filter.narrow(Some(vec!["suggestive".to_string(), "erotica".to_string()])),
+            vec!["suggestive"]
+        );
+        assert_eq!(filter.narrow(None), vec!["safe", "suggestive"]);
+        assert_eq!(
+            filter.mangadex_query(),
+            "&contentRating[]=safe&contentRating[]=suggestive"
+        );
+        assert_eq!(
+            rating_condition(&["safe".to_string()]),
+            doc! { "$in": ["safe", Bson::Null] }
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use crate::cache::{cache_keys, CacheService};
+use crate::embedding::{cosine, EmbeddingProvider};
+use crate::manga_service::{Manga, MangaService};
+use crate::progress::{MangaReassignment, ProgressService};
+use crate::search_index::{normalize, words};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Weights of the three signals in the duplicate score
+const TITLE_WEIGHT: f32 = 0.45;
+const EMBEDDING_WEIGHT: f32 = 0.35;
+const AUTHOR_WEIGHT: f32 = 0.2;
+
+/// Embedding neighbours checked for each manga
+const NEIGHBOURS: usize = 10;
+
+/// Titles shared by more manga than this ("Oneshot", "Untitled") are too
+/// generic to pair on
+const MAX_TITLE_GROUP: usize = 20;
+
+#[derive(Debug, thiserror::Error)]
+#[error("the surviving manga must be one of the pair")]
+pub struct InvalidSurvivor;
+
+/// Key under which the same title from different sources lines up:
+/// "Berserk!" and "berserk" share one
+pub fn title_key(title: &str) -> String {
+    words(title).join(" ")
+}
+
+/// Key for a creator name that ignores word order, so "Miura Kentaro" and
+/// "Kentaro Miura" match
+pub fn author_key(name: &str) -> String {
+    let mut words = words(name);
+    words.sort();
+    words.join(" ")
+}
+
+fn bigrams(key: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = key.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Dice coefficient over character bigrams of two title keys, 0-1
+pub fn title_similarity(a: &str, b: &str) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let (a, mut b) = (bigrams(a), bigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let total = (a.len() + b.len()) as f32;
+    let mut shared = 0;
+    for gram in &a {
+        if let Some(i) = b.iter().position(|g| g == gram) {
+            b.swap_remove(i);
+            shared += 1;
+        }
+    }
+    2.0 * shared as f32 / total
+}
+
+fn title_keys(manga: &Manga) -> HashSet<String> {
+    manga
+        .all_titles()
+        .into_iter()
+        .map(|(_, title)| title_key(title))
+        .filter(|key| !key.is_empty())
+        .collect()
+}
+
+fn creator_keys(manga: &Manga) -> HashSet<String> {
+    [manga.author.as_deref(), manga.artist.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(author_key)
+        .filter(|key| !key.is_empty())
+        .collect()
+}
+
+/// Why two catalog entries look like the same series
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DuplicateScore {
+    pub score: f32,
+    /// Best match between any title of one and any title of the other
+    pub title_similarity: f32,
+    pub embedding_similarity: Option<f32>,
+    /// `None` when either has no author or artist
+    pub author_match: Option<bool>,
+}
+
+/// Score a pair from title similarity, embedding similarity (when both were
+/// embedded by the same model) and matching creators. Without embeddings
+/// the other two signals are scaled up to the same 0-1 range.
+pub fn score_duplicate(a: &Manga, b: &Manga) -> DuplicateScore {
+    let (titles_a, titles_b) = (title_keys(a), title_keys(b));
+    let title_similarity = titles_a
+        .iter()
+        .flat_map(|x| titles_b.iter().map(move |y| title_similarity(x, y)))
+        .fold(0.0, f32::max);
+    let embedding_similarity = match (&a.embedding, &b.embedding) {
+        (Some(x), Some(y)) if a.embedding_model.is_some() && a.embedding_model == b.embedding_model => cosine(x, y),
+        _ => None,
+    };
+    let (creators_a, creators_b) = (creator_keys(a), creator_keys(b));
+    let author_match =
+        (!creators_a.is_empty() && !creators_b.is_empty()).then(|| !creators_a.is_disjoint(&creators_b));
+
+    let author = match author_match {
+        Some(true) => 1.0,
+        Some(false) => 0.0,
+        None => 0.5,
+    };
+    let score = match embedding_similarity {
+        Some(embedding) => {
+            TITLE_WEIGHT * title_similarity + EMBEDDING_WEIGHT * embedding.max(0.0) + AUTHOR_WEIGHT * author
+        }
+        None => (TITLE_WEIGHT * title_similarity + AUTHOR_WEIGHT * author) / (TITLE_WEIGHT + AUTHOR_WEIGHT),
+    };
+    DuplicateScore {
+        score,
+        title_similarity,
+        embedding_similarity,
+        author_match,
+    }
+}
+
+/// Fold `other` into `keep`: titles, alternative titles and tags are
+/// combined, and `keep` takes any field it is missing from `other`
+pub fn merge_manga(mut keep: Manga, other: Manga) -> Manga {
+    let mut other_titles = other.alt_titles.unwrap_or_default();
+    match (&keep.title, other.title) {
+        (None, title) => keep.title = title,
+        (Some(_), Some(title)) => other_titles.insert(0, title),
+        (Some(_), None) => {}
+    }
+    let mut known: HashSet<String> = keep.all_titles().into_iter().map(|(_, t)| title_key(t)).collect();
+
+    let mut titles = keep.titles.take().unwrap_or_default();
+    for title in other.titles.into_iter().flatten() {
+        if !titles.contains(&title) {
+            known.insert(title_key(&title.title));
+            titles.push(title);
+        }
+    }
+    keep.titles = (!titles.is_empty()).then_some(titles);
+
+    let mut alt_titles = keep.alt_titles.take().unwrap_or_default();
+    for title in other_titles {
+        if known.insert(title_key(&title)) {
+            alt_titles.push(title);
+        }
+    }
+    keep.alt_titles = (!alt_titles.is_empty()).then_some(alt_titles);
+
+    let mut tags = keep.tags.take().unwrap_or_default();
+    for tag in other.tags.into_iter().flatten() {
+        if !tags.iter().any(|t| normalize(t) == normalize(&tag)) {
+            tags.push(tag);
+        }
+    }
+    keep.tags = (!tags.is_empty()).then_some(tags);
+
+    keep.description = keep.description.filter(|d| !d.trim().is_empty()).or(other.description);
+    keep.author = keep.author.or(other.author);
+    keep.artist = keep.artist.or(other.artist);
+    keep.status = keep.status.or(other.status);
+    keep.content_rating = keep.content_rating.or(other.content_rating);
+    keep.year = keep.year.or(other.year);
+    keep.cover_art = keep.cover_art.or(other.cover_art);
+    keep.chapters = keep.chapters.filter(|c| !c.is_empty()).or(other.chapters);
+    keep.created_at = match (keep.created_at, other.created_at) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    keep
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateStatus {
+    Pending,
+    Merged,
+    Dismissed,
+}
+
+/// Two catalog entries that look like the same series, waiting for an
+/// admin to merge or dismiss them
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateCandidate {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The pair in sorted order, so each pair is stored once
+    pub manga_a: String,
+    pub manga_b: String,
+    pub title_a: Option<String>,
+    pub title_b: Option<String>,
+    pub similarity: DuplicateScore,
+    pub status: DuplicateStatus,
+    pub detected_at: String,
+    pub reviewed_at: Option<String>,
+    pub reviewed_by: Option<String>,
+    /// The manga that was kept, once merged
+    pub survivor_id: Option<String>,
+}
+
+/// Finds catalog entries imported more than once under different ids and
+/// merges them on admin approval
+#[derive(Clone)]
+pub struct DedupService {
+    manga_service: MangaService,
+    progress_service: ProgressService,
+    cache: Option<CacheService>,
+    embeddings: Option<Arc<dyn EmbeddingProvider>>,
+    threshold: f32,
+    interval: Duration,
+    wake: Arc<Notify>,
+}
+
+impl DedupService {
+    pub fn new(
+        manga_service: MangaService,
+        progress_service: ProgressService,
+        cache: Option<CacheService>,
+        embeddings: Option<Arc<dyn EmbeddingProvider>>,
+    ) -> Self {
+        let threshold = env::var("DEDUP_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0.85);
+        let interval_secs = env::var("DEDUP_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+
+        Self {
+            manga_service,
+            progress_service,
+            cache,
+            embeddings,
+            threshold,
+            interval: Duration::from_secs(interval_secs),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    fn candidates_collection(&self) -> Collection<DuplicateCandidate> {
+        self.manga_service.db().collection("duplicate_candidates")
+    }
+
+    async fn create_indexes(&self) -> Result<(), Box<dyn std::error::Error>> {
+        use mongodb::{options::IndexOptions, IndexModel};
+
+        // One record per pair, so a dismissed pair stays dismissed
+        self.candidates_collection()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "manga_a": 1, "manga_b": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.candidates_collection()
+            .create_index(IndexModel::builder().keys(doc! { "status": 1, "similarity.score": -1 }).build())
+            .await?;
+        Ok(())
+    }
+
+    /// Start a scan now instead of waiting for the interval
+    pub fn trigger(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Scan the catalog now and then on every interval or trigger
+    pub fn spawn_scheduler(self) {
+        tokio::spawn(async move {
+            if let Err(e) = self.create_indexes().await {
+                tracing::warn!("⚠️  Failed to create duplicate candidate indexes: {}", e);
+            }
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = self.wake.notified() => {}
+                }
+                match self.run_once().await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("🔁 Found {} likely duplicate series", count),
+                    Err(e) => tracing::error!("Duplicate scan failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Pair up manga that share a normalized title or are embedding
+    /// neighbours, and record the pairs scoring above the threshold.
+    /// Returns how many pairs were recorded.
+    pub async fn run_once(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let model = self.embeddings.as_ref().map(|e| e.model_id());
+        let mut cursor = self
+            .manga_service
+            .manga_collection()
+            .find(doc! {})
+            .projection(doc! { "chapters": 0, "ai_summary": 0 })
+            .await?;
+
+        let mut catalog: HashMap<String, Manga> = HashMap::new();
+        let mut by_title: HashMap<String, Vec<String>> = HashMap::new();
+        while cursor.advance().await? {
+            let mut manga: Manga = cursor.deserialize_current()?;
+            let Some(manga_id) = manga.manga_id.clone() else {
+                continue;
+            };
+            if model.is_none() || manga.embedding_model != model {
+                manga.embedding = None;
+            }
+            for key in title_keys(&manga) {
+                by_title.entry(key).or_default().push(manga_id.clone());
+            }
+            catalog.insert(manga_id, manga);
+        }
+
+        let mut pairs: HashSet<(String, String)> = HashSet::new();
+        let mut add_pair = |a: &str, b: &str| {
+            if a != b {
+                let pair = if a < b { (a, b) } else { (b, a) };
+                pairs.insert((pair.0.to_string(), pair.1.to_string()));
+            }
+        };
+        for ids in by_title.values().filter(|ids| ids.len() <= MAX_TITLE_GROUP) {
+            for (i, a) in ids.iter().enumerate() {
+                for b in &ids[i + 1..] {
+                    add_pair(a, b);
+                }
+            }
+        }
+        let vector_index = self.manga_service.vector_index();
+        if vector_index.is_ready() {
+            for (manga_id, manga) in &catalog {
+                let Some(embedding) = manga.embedding.as_deref() else {
+                    continue;
+                };
+                for hit in vector_index.search(embedding, NEIGHBOURS + 1) {
+                    add_pair(manga_id, &hit.manga_id);
+                }
+            }
+        }
+
+        let candidates = self.candidates_collection();
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut recorded = 0;
+        for (a, b) in &pairs {
+            let (Some(manga_a), Some(manga_b)) = (catalog.get(a), catalog.get(b)) else {
+                continue;
+            };
+            let similarity = score_duplicate(manga_a, manga_b);
+            if similarity.score < self.threshold {
+                continue;
+            }
+            candidates
+                .update_one(
+                    doc! { "manga_a": a, "manga_b": b },
+                    doc! {
+                        "$set": {
+                            "title_a": &manga_a.title,
+                            "title_b": &manga_b.title,
+                            "similarity": mongodb::bson::to_bson(&similarity)?
+                        },
+                        "$setOnInsert": {
+                            "status": "pending",
+                            "detected_at": &now,
+                            "reviewed_at": null,
+                            "reviewed_by": null,
+                            "survivor_id": null
+                        }
+                    },
+                )
+                .upsert(true)
+                .await?;
+            recorded += 1;
+        }
+        Ok(recorded)
+    }
+
+    pub async fn candidates(
+        &self,
+        status: DuplicateStatus,
+        limit: u32,
+    ) -> Result<Vec<DuplicateCandidate>, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .candidates_collection()
+            .find(doc! { "status": mongodb::bson::to_bson(&status)? })
+            .sort(doc! { "similarity.score": -1 })
+            .limit(limit.min(500) as i64)
+            .await?;
+
+        let mut results = Vec::new();
+        while cursor.advance().await? {
+            results.push(cursor.deserialize_current()?);
+        }
+        Ok(results)
+    }
+
+    /// Merge a pending pair into `keep_manga_id`: the other manga's titles,
+    /// tags and missing fields are folded in, every reader's library,
+    /// progress, bookmarks and history move over, and the other manga is
+    /// deleted. `None` when there is no such pending pair.
+    pub async fn merge(
+        &self,
+        candidate_id: &str,
+        keep_manga_id: &str,
+        admin_user_id: &str,
+    ) -> Result<Option<(DuplicateCandidate, MangaReassignment)>, Box<dyn std::error::Error>> {
+        let id = ObjectId::parse_str(candidate_id)?;
+        let candidates = self.candidates_collection();
+        let Some(mut candidate) = candidates.find_one(doc! { "_id": id, "status": "pending" }).await? else {
+            return Ok(None);
+        };
+        let duplicate_id = if keep_manga_id == candidate.manga_a {
+            candidate.manga_b.clone()
+        } else if keep_manga_id == candidate.manga_b {
+            candidate.manga_a.clone()
+        } else {
+            return Err(InvalidSurvivor.into());
+        };
+
+        let collection = self.manga_service.manga_collection();
+        let keep = collection
+            .find_one(doc! { "manga_id": keep_manga_id })
+            .await?
+            .ok_or("the surviving manga is no longer in the catalog")?;
+        // A retry after a partial merge finds the duplicate already gone
+        // and only finishes moving reader data
+        if let Some(duplicate) = collection.find_one(doc! { "manga_id": &duplicate_id }).await? {
+            let mut merged = merge_manga(keep, duplicate);
+            merged.updated_at = Some(chrono::Utc::now().to_rfc3339());
+            collection.replace_one(doc! { "manga_id": keep_manga_id }, &merged).await?;
+            self.manga_service.search_index().upsert(&merged);
+        }
+
+        let moved = self.progress_service.reassign_manga(&duplicate_id, keep_manga_id).await?;
+        collection.delete_one(doc! { "manga_id": &duplicate_id }).await?;
+        self.manga_service.search_index().remove(&duplicate_id);
+        self.manga_service.vector_index().remove(&duplicate_id);
+        if let Some(cache) = &self.cache {
+            for manga_id in [keep_manga_id, duplicate_id.as_str()] {
+                let _ = cache.delete(&cache_keys::manga_similar(manga_id)).await;
+            }
+        }
+
+        // Other pairs with the deleted manga can no longer be merged
+        candidates
+            .delete_many(doc! {
+                "_id": { "$ne": id },
+                "status": "pending",
+                "$or": [{ "manga_a": &duplicate_id }, { "manga_b": &duplicate_id }]
+            })
+            .await?;
+
+        candidate.status = DuplicateStatus::Merged;
+        candidate.survivor_id = Some(keep_manga_id.to_string());
+        candidate.reviewed_at = Some(chrono::Utc::now().to_rfc3339());
+        candidate.reviewed_by = Some(admin_user_id.to_string());
+        candidates
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": {
+                        "status": "merged",
+                        "survivor_id": &candidate.survivor_id,
+                        "reviewed_at": &candidate.reviewed_at,
+                        "reviewed_by": &candidate.reviewed_by
+                    }
+                },
+            )
+            .await?;
+        Ok(Some((candidate, moved)))
+    }
+
+    /// Mark a pending pair as not a duplicate, so later scans leave it be.
+    /// `None` when there is no such pending pair.
+    pub async fn dismiss(
+        &self,
+        candidate_id: &str,
+        admin_user_id: &str,
+    ) -> Result<Option<DuplicateCandidate>, Box<dyn std::error::Error>> {
+        let id = ObjectId::parse_str(candidate_id)?;
+        let candidates = self.candidates_collection();
+        let Some(mut candidate) = candidates.find_one(doc! { "_id": id, "status": "pending" }).await? else {
+            return Ok(None);
+        };
+
+        candidate.status = DuplicateStatus::Dismissed;
+        candidate.reviewed_at = Some(chrono::Utc::now().to_rfc3339());
+        candidate.reviewed_by = Some(admin_user_id.to_string());
+        candidates
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": {
+                        "status": "dismissed",
+                        "reviewed_at": &candidate.reviewed_at,
+                        "reviewed_by": &candidate.reviewed_by
+                    }
+                },
+            )
+            .await?;
+        Ok(Some(candidate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manga(id: &str, title: &str, author: Option<&str>, embedding: Option<Vec<f32>>) -> Manga {
+        let mut manga: Manga = serde_json::from_value(serde_json::json!({
+            "manga_id": id,
+            "title": title,
+            "author": author,
+            "embedding_model": "hashing:2"
+        }))
+        .unwrap();
+        manga.embedding = embedding;
+        manga
+    }
+
+    #[test]
+    fn test_keys_ignore_case_accents_punctuation_and_name_order() {
+        assert_eq!(title_key("Berserk!"), title_key("  berserk"));
+        assert_eq!(title_key("Pokémon: Adventures"), "pokemon adventures");
+        assert_eq!(author_key("Miura Kentaro"), author_key("KENTARO MIURA"));
+        assert_eq!(title_similarity("berserk", "berserk"), 1.0);
+        assert!(title_similarity("one piece", "one piece color") > 0.7);
+        assert!(title_similarity("berserk", "naruto") < 0.2);
+    }
+
+    #[test]
+    fn test_score_needs_title_and_agreeing_signals() {
+        let local = manga("local-1", "Berserk", Some("Kentaro Miura"), Some(vec![1.0, 0.1]));
+        let imported = manga("md-1", "BERSERK", Some("Miura Kentaro"), Some(vec![1.0, 0.0]));
+        let score = score_duplicate(&local, &imported);
+        assert_eq!((score.title_similarity, score.author_match), (1.0, Some(true)));
+        assert!(score.score > 0.95);
+
+        // Same title, different creator and no embeddings: a remake or a
+        // different series, not a duplicate
+        let other = manga("md-2", "Berserk", Some("Someone Else"), None);
+        let score = score_duplicate(&local, &other);
+        assert_eq!((score.embedding_similarity, score.author_match), (None, Some(false)));
+        assert!(score.score < 0.85);
+
+        let unknown = manga("md-3", "Berserk", None, None);
+        assert_eq!(score_duplicate(&local, &unknown).author_match, None);
+    }
+
+    #[test]
+    fn test_merge_manga_keeps_survivor_and_folds_in_titles_and_tags() {
+        let mut keep = manga("a", "Berserk", None, None);
+        keep.tags = Some(vec!["Action".to_string()]);
+        let mut other = manga("b", "Berserk: Prototype", Some("Kentaro Miura"), None);
+        other.alt_titles = Some(vec!["berserk".to_string(), "ベルセルク".to_string()]);
+        other.tags = Some(vec!["action".to_string(), "Dark Fantasy".to_string()]);
+        other.year = Some(1989);
+
+        let merged = merge_manga(keep, other);
+        assert_eq!(merged.manga_id.as_deref(), Some("a"));
+        assert_eq!(merged.title.as_deref(), Some("Berserk"));
+        assert_eq!(merged.author.as_deref(), Some("Kentaro Miura"));
+        assert_eq!(merged.year, Some(1989));
+        assert_eq!(
+            merged.alt_titles,
+            Some(vec!["Berserk: Prototype".to_string(), "ベルセルク".to_string()])
+        );
+        assert_eq!(merged.tags, Some(vec!["Action".to_string(), "Dark Fantasy".to_string()]));
+    }
+}
@@ -0,0 +1,687 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Name of the per-chapter manifest written next to the downloaded pages
+pub const MANIFEST_FILE: &str = ".manifest.json";
+
+/// MangaDex@Home network report endpoint
+pub const AT_HOME_REPORT_URL: &str = "https://api.mangadex.network/report";
+
+/// Consecutive page failures before asking MangaDex for a different at-home node
+const MAX_CONSECUTIVE_FAILURES: usize = 3;
+
+/// Upper bound on node reassignments within a single chapter download
+const MAX_SERVER_REFRESHES: usize = 2;
+
+/// Image quality served by a MangaDex@Home node
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Quality {
+    #[default]
+    #[serde(alias = "high")]
+    Data,
+    #[serde(alias = "saver", alias = "data-saver")]
+    DataSaver,
+}
+
+impl Quality {
+    /// Parse the `quality` query parameter accepted by the download endpoints
+    pub fn from_param(param: Option<&str>) -> Self {
+        match param {
+            Some("saver") | Some("dataSaver") | Some("data-saver") => Quality::DataSaver,
+            _ => Quality::Data,
+        }
+    }
+
+    /// Path segment used in at-home image URLs
+    pub fn url_path(&self) -> &'static str {
+        match self {
+            Quality::Data => "data",
+            Quality::DataSaver => "data-saver",
+        }
+    }
+}
+
+/// Response of `GET /at-home/server/{chapter_id}`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AtHomeServer {
+    #[serde(rename = "baseUrl")]
+    pub base_url: String,
+    pub chapter: AtHomeChapter,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AtHomeChapter {
+    pub hash: String,
+    pub data: Vec<String>,
+    #[serde(rename = "dataSaver")]
+    pub data_saver: Vec<String>,
+}
+
+impl AtHomeServer {
+    pub fn images(&self, quality: Quality) -> &[String] {
+        match quality {
+            Quality::Data => &self.chapter.data,
+            Quality::DataSaver => &self.chapter.data_saver,
+        }
+    }
+
+    pub fn image_url(&self, quality: Quality, filename: &str) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.base_url,
+            quality.url_path(),
+            self.chapter.hash,
+            filename
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PageStatus {
+    Pending,
+    Complete,
+    Failed,
+}
+
+/// Download state of a single page
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PageRecord {
+    pub index: usize,
+    pub source: String, // Filename on the at-home server
+    pub file: String,   // Filename on disk
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    pub status: PageStatus,
+    pub error: Option<String>,
+}
+
+/// Per-chapter manifest used to resume partially downloaded chapters
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ChapterManifest {
+    pub chapter_id: String,
+    pub chapter_hash: String,
+    pub quality: Quality,
+    pub pages: Vec<PageRecord>,
+    pub updated_at: String,
+}
+
+impl ChapterManifest {
+    pub fn new(chapter_id: &str, chapter_hash: &str, quality: Quality) -> Self {
+        Self {
+            chapter_id: chapter_id.to_string(),
+            chapter_hash: chapter_hash.to_string(),
+            quality,
+            pages: Vec::new(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Load the manifest from a chapter directory, if one was written before
+    pub async fn load(dir: &Path) -> Option<Self> {
+        let bytes = tokio::fs::read(dir.join(MANIFEST_FILE)).await.ok()?;
+        match serde_json::from_slice(&bytes) {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable manifest in {}: {}", dir.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Persist the manifest through a temp file so a crash never leaves it half written
+    pub async fn save(&mut self, dir: &Path) -> std::io::Result<()> {
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+        let json = serde_json::to_vec_pretty(self)?;
+        write_atomic(&dir.join(MANIFEST_FILE), &json).await
+    }
+
+    /// Bring the manifest in line with the current at-home listing.
+    ///
+    /// Records are kept when the chapter hash and quality are unchanged, so
+    /// completed pages are not fetched again. A re-uploaded chapter starts over.
+    pub fn reconcile(&mut self, chapter_hash: &str, quality: Quality, files: &[(String, String)]) {
+        if self.chapter_hash != chapter_hash || self.quality != quality {
+            self.chapter_hash = chapter_hash.to_string();
+            self.quality = quality;
+            self.pages.clear();
+        }
+
+        let mut pages = Vec::with_capacity(files.len());
+        for (index, (source, file)) in files.iter().enumerate() {
+            let existing = self
+                .pages
+                .iter()
+                .find(|p| &p.source == source && &p.file == file)
+                .cloned();
+            pages.push(existing.unwrap_or_else(|| PageRecord {
+                index,
+                source: source.clone(),
+                file: file.clone(),
+                size: None,
+                sha256: None,
+                status: PageStatus::Pending,
+                error: None,
+            }));
+        }
+        self.pages = pages;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.pages.is_empty() && self.pages.iter().all(|p| p.status == PageStatus::Complete)
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.pages
+            .iter()
+            .filter(|p| p.status == PageStatus::Failed)
+            .count()
+    }
+}
+
+/// Image container detected from the leading bytes of a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageKind {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+}
+
+impl ImageKind {
+    fn format(&self) -> image::ImageFormat {
+        match self {
+            ImageKind::Jpeg => image::ImageFormat::Jpeg,
+            ImageKind::Png => image::ImageFormat::Png,
+            ImageKind::Gif => image::ImageFormat::Gif,
+            ImageKind::WebP => image::ImageFormat::WebP,
+        }
+    }
+}
+
+/// Detect the image type from its magic number
+pub fn detect_image_kind(bytes: &[u8]) -> Option<ImageKind> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageKind::Jpeg)
+    } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(ImageKind::Png)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(ImageKind::Gif)
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some(ImageKind::WebP)
+    } else {
+        None
+    }
+}
+
+/// Check the magic number and fully decode the image.
+///
+/// Decoding is CPU bound, so callers on the runtime should use `verify_image_blocking`.
+pub fn verify_image(bytes: &[u8]) -> Result<ImageKind, String> {
+    let kind = detect_image_kind(bytes).ok_or_else(|| "Not a recognised image".to_string())?;
+    image::load_from_memory_with_format(bytes, kind.format())
+        .map_err(|e| format!("Corrupt image data: {}", e))?;
+    Ok(kind)
+}
+
+pub async fn verify_image_blocking<B>(bytes: B) -> Result<ImageKind, String>
+where
+    B: AsRef<[u8]> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || verify_image(bytes.as_ref()))
+        .await
+        .map_err(|e| format!("Verification task failed: {}", e))?
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+/// MangaDex page filenames look like `1-<sha256>.png`; return the hash part if present
+pub fn hash_from_filename(filename: &str) -> Option<&str> {
+    let stem = Path::new(filename).file_stem()?.to_str()?;
+    let (_, hash) = stem.rsplit_once('-')?;
+    if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+/// Replace path separators so titles can be used as directory names
+pub fn sanitize_path_component(name: &str) -> String {
+    name.replace(['/', '\\'], "_")
+}
+
+/// Write to `<path>.part`, flush it, then rename over the destination
+pub async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".part");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        file.write_all(bytes).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&tmp_path, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Whether a page already on disk can be kept instead of downloaded again
+async fn existing_page_matches(path: &Path, record: &PageRecord) -> bool {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return false,
+    };
+
+    if record.status == PageStatus::Complete && record.size == Some(metadata.len()) {
+        return true;
+    }
+
+    // No usable manifest entry: fall back to comparing content hashes
+    let expected = record
+        .sha256
+        .as_deref()
+        .or_else(|| hash_from_filename(&record.source));
+    match (expected, tokio::fs::read(path).await) {
+        (Some(expected), Ok(bytes)) => sha256_hex(&bytes) == expected,
+        _ => false,
+    }
+}
+
+/// Body of a MangaDex@Home delivery report
+#[derive(Debug, Serialize, Clone)]
+pub struct AtHomeReport {
+    pub url: String,
+    pub success: bool,
+    pub bytes: u64,
+    pub duration: u64, // Milliseconds
+    pub cached: bool,
+}
+
+/// Only community at-home nodes want reports; the mangadex.org fallback servers do not
+pub fn should_report(image_url: &str) -> bool {
+    match reqwest::Url::parse(image_url) {
+        Ok(url) => match url.host_str() {
+            Some(host) => host != "mangadex.org" && !host.ends_with(".mangadex.org"),
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Send a delivery report in the background; failures only get logged
+pub fn spawn_at_home_report(client: &reqwest::Client, report: AtHomeReport) {
+    let client = client.clone();
+    tokio::spawn(async move {
+        match client.post(AT_HOME_REPORT_URL).json(&report).send().await {
+            Ok(resp) if !resp.status().is_success() => {
+                tracing::debug!("At-home report for {} rejected: {}", report.url, resp.status())
+            }
+            Err(e) => tracing::debug!("Failed to send at-home report: {}", e),
+            _ => {}
+        }
+    });
+}
+
+/// At-home nodes send `X-Cache: HIT` when the image was served from their cache
+pub fn is_cache_hit(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get("X-Cache")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("HIT"))
+        .unwrap_or(false)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DownloadError {
+    #[error("Failed to fetch download info from MangaDex: {0}")]
+    Upstream(#[from] reqwest::Error),
+    #[error("Failed to parse download info: {0}")]
+    InvalidResponse(String),
+    #[error("No images found for this chapter")]
+    NoImages,
+    #[error("Failed to create save directory: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub struct ChapterDownloadRequest {
+    pub chapter_id: String,
+    pub save_root: PathBuf,
+    pub quality: Quality,
+    pub manga_title: Option<String>,
+    pub chapter_title: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DownloadReport {
+    pub save_path: String,
+    pub downloaded_files: Vec<String>,
+    pub skipped_files: Vec<String>,
+    pub failed_downloads: Vec<String>,
+    pub total_pages: usize,
+    pub bytes_downloaded: u64,
+    pub complete: bool,
+}
+
+/// Chapter downloader backed by MangaDex@Home
+#[derive(Clone)]
+pub struct Downloader {
+    client: reqwest::Client,
+    api_base: String,
+}
+
+impl Downloader {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            api_base: "https://api.mangadex.org".to_string(),
+        }
+    }
+
+    /// Ask MangaDex for an at-home node serving this chapter
+    pub async fn fetch_at_home_server(&self, chapter_id: &str) -> Result<AtHomeServer, DownloadError> {
+        let url = format!("{}/at-home/server/{}", self.api_base, chapter_id);
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(DownloadError::InvalidResponse(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+        response
+            .json::<AtHomeServer>()
+            .await
+            .map_err(|e| DownloadError::InvalidResponse(e.to_string()))
+    }
+
+    /// Download every page of a chapter, skipping pages already on disk.
+    ///
+    /// Progress is recorded in the chapter manifest after each page, so a
+    /// rerun only fetches pages that are missing or failed verification.
+    pub async fn download_chapter(
+        &self,
+        req: &ChapterDownloadRequest,
+    ) -> Result<DownloadReport, DownloadError> {
+        let server = self.fetch_at_home_server(&req.chapter_id).await?;
+        let images = server.images(req.quality);
+        if images.is_empty() {
+            return Err(DownloadError::NoImages);
+        }
+
+        let manga_title = req.manga_title.as_deref().unwrap_or("Unknown_Manga");
+        let chapter_title = req.chapter_title.as_deref().unwrap_or(&req.chapter_id);
+        let safe_chapter_title = sanitize_path_component(chapter_title);
+        let chapter_dir = req
+            .save_root
+            .join(sanitize_path_component(manga_title))
+            .join(&safe_chapter_title);
+        tokio::fs::create_dir_all(&chapter_dir).await?;
+
+        let files: Vec<(String, String)> = images
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                let extension = Path::new(source)
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .unwrap_or("jpg");
+                let file = format!("{}_{:03}.{}", safe_chapter_title, index + 1, extension);
+                (source.clone(), file)
+            })
+            .collect();
+
+        let mut manifest = ChapterManifest::load(&chapter_dir)
+            .await
+            .unwrap_or_else(|| ChapterManifest::new(&req.chapter_id, &server.chapter.hash, req.quality));
+        manifest.reconcile(&server.chapter.hash, req.quality, &files);
+
+        let mut report = DownloadReport {
+            save_path: chapter_dir.display().to_string(),
+            downloaded_files: Vec::new(),
+            skipped_files: Vec::new(),
+            failed_downloads: Vec::new(),
+            total_pages: files.len(),
+            bytes_downloaded: 0,
+            complete: false,
+        };
+
+        let mut server = server;
+        let mut consecutive_failures = 0;
+        let mut refreshes = 0;
+
+        for i in 0..manifest.pages.len() {
+            let record = manifest.pages[i].clone();
+            let path = chapter_dir.join(&record.file);
+
+            if existing_page_matches(&path, &record).await {
+                if record.status != PageStatus::Complete || record.size.is_none() {
+                    let size = tokio::fs::metadata(&path).await.map(|m| m.len()).ok();
+                    let page = &mut manifest.pages[i];
+                    page.size = size;
+                    page.status = PageStatus::Complete;
+                    page.error = None;
+                }
+                report.skipped_files.push(record.file);
+                continue;
+            }
+
+            let mut result = self
+                .download_page(&server.image_url(req.quality, &record.source), &path)
+                .await;
+
+            if result.is_err() {
+                consecutive_failures += 1;
+                // The assigned node looks unhealthy: get a fresh one and retry this page on it
+                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES && refreshes < MAX_SERVER_REFRESHES {
+                    refreshes += 1;
+                    match self.fetch_at_home_server(&req.chapter_id).await {
+                        Ok(fresh) if fresh.chapter.hash == server.chapter.hash => {
+                            tracing::warn!(
+                                "🔄 Switching chapter {} from {} to {} after {} failures",
+                                req.chapter_id,
+                                server.base_url,
+                                fresh.base_url,
+                                consecutive_failures
+                            );
+                            server = fresh;
+                            consecutive_failures = 0;
+                            result = self
+                                .download_page(&server.image_url(req.quality, &record.source), &path)
+                                .await;
+                        }
+                        Ok(_) => tracing::warn!(
+                            "Chapter {} was re-uploaded mid-download; keeping current node",
+                            req.chapter_id
+                        ),
+                        Err(e) => tracing::warn!("Failed to refresh at-home node: {}", e),
+                    }
+                }
+            }
+
+            let page = &mut manifest.pages[i];
+            match result {
+                Ok((size, hash)) => {
+                    consecutive_failures = 0;
+                    page.size = Some(size);
+                    page.sha256 = Some(hash);
+                    page.status = PageStatus::Complete;
+                    page.error = None;
+                    report.bytes_downloaded += size;
+                    report.downloaded_files.push(record.file);
+                }
+                Err(e) => {
+                    page.status = PageStatus::Failed;
+                    page.error = Some(e.clone());
+                    report
+                        .failed_downloads
+                        .push(format!("Page {}: {}", record.index + 1, e));
+                }
+            }
+
+            if let Err(e) = manifest.save(&chapter_dir).await {
+                tracing::warn!("Failed to write manifest for {}: {}", req.chapter_id, e);
+            }
+        }
+
+        if let Err(e) = manifest.save(&chapter_dir).await {
+            tracing::warn!("Failed to write manifest for {}: {}", req.chapter_id, e);
+        }
+        report.complete = manifest.is_complete();
+
+        tracing::info!(
+            "📥 Chapter {}: {} downloaded, {} skipped, {} failed",
+            req.chapter_id,
+            report.downloaded_files.len(),
+            report.skipped_files.len(),
+            manifest.failed_count()
+        );
+
+        Ok(report)
+    }
+
+    /// Fetch, verify and atomically store one page. Returns its size and SHA-256.
+    ///
+    /// The outcome is reported back to MangaDex@Home when the image came from a community node.
+    async fn download_page(&self, url: &str, path: &Path) -> Result<(u64, String), String> {
+        let started = std::time::Instant::now();
+        let mut cached = false;
+        let mut received = 0u64;
+
+        let result = async {
+            let response = self
+                .client
+                .get(url)
+                .send()
+                .await
+                .map_err(|_| "Network error".to_string())?;
+            cached = is_cache_hit(response.headers());
+            if !response.status().is_success() {
+                return Err(format!("HTTP {}", response.status()));
+            }
+            let bytes = response
+                .bytes()
+                .await
+                .map_err(|_| "Failed to read image data".to_string())?;
+            received = bytes.len() as u64;
+
+            verify_image_blocking(bytes.clone()).await?;
+            Ok(bytes)
+        }
+        .await;
+
+        if should_report(url) {
+            spawn_at_home_report(&self.client, AtHomeReport {
+                url: url.to_string(),
+                success: result.is_ok(),
+                bytes: received,
+                duration: started.elapsed().as_millis() as u64,
+                cached,
+            });
+        }
+
+        let bytes = result?;
+        let hash = sha256_hex(&bytes);
+        write_atomic(path, &bytes)
+            .await
+            .map_err(|_| "Failed to save file".to_string())?;
+
+        Ok((bytes.len() as u64, hash))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<(String, String)> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.to_string(), format!("ch_{:03}.png", i + 1)))
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_image_kind() {
+        assert_eq!(detect_image_kind(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(ImageKind::Jpeg));
+        assert_eq!(
+            detect_image_kind(b"\x89PNG\r\n\x1a\n...."),
+            Some(ImageKind::Png)
+        );
+        assert_eq!(detect_image_kind(b"GIF89a...."), Some(ImageKind::Gif));
+        assert_eq!(detect_image_kind(b"RIFF\0\0\0\0WEBPVP8 "), Some(ImageKind::WebP));
+        assert_eq!(detect_image_kind(b"<html>error</html>"), None);
+    }
+
+    #[test]
+    fn test_verify_image_rejects_truncated_data() {
+        let mut png = Vec::new();
+        image::RgbImage::new(2, 2)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        assert_eq!(verify_image(&png), Ok(ImageKind::Png));
+        assert!(verify_image(&png[..png.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_hash_from_filename() {
+        let hash = "a".repeat(64);
+        assert_eq!(hash_from_filename(&format!("1-{}.png", hash)), Some(hash.as_str()));
+        assert_eq!(hash_from_filename("1-short.png"), None);
+        assert_eq!(hash_from_filename("cover.jpg"), None);
+    }
+
+    #[test]
+    fn test_reconcile_keeps_completed_pages() {
+        let mut manifest = ChapterManifest::new("c1", "h1", Quality::Data);
+        manifest.reconcile("h1", Quality::Data, &files(&["a.png", "b.png"]));
+        manifest.pages[0].status = PageStatus::Complete;
+        manifest.pages[0].size = Some(10);
+
+        manifest.reconcile("h1", Quality::Data, &files(&["a.png", "b.png", "c.png"]));
+        assert_eq!(manifest.pages.len(), 3);
+        assert_eq!(manifest.pages[0].status, PageStatus::Complete);
+        assert_eq!(manifest.pages[2].status, PageStatus::Pending);
+        assert!(!manifest.is_complete());
+    }
+
+    #[test]
+    fn test_reconcile_resets_on_new_chapter_hash() {
+        let mut manifest = ChapterManifest::new("c1", "h1", Quality::Data);
+        manifest.reconcile("h1", Quality::Data, &files(&["a.png"]));
+        manifest.pages[0].status = PageStatus::Complete;
+
+        manifest.reconcile("h2", Quality::Data, &files(&["a.png"]));
+        assert_eq!(manifest.pages[0].status, PageStatus::Pending);
+    }
+
+    #[test]
+    fn test_should_report_only_for_at_home_nodes() {
+        assert!(should_report("https://abc.xyz123.mangadex.network:443/token/data/h/1.png"));
+        assert!(!should_report("https://uploads.mangadex.org/data/h/1.png"));
+        assert!(!should_report("https://mangadex.org/data/h/1.png"));
+        assert!(!should_report("not a url"));
+    }
+
+    #[test]
+    fn test_quality_from_param() {
+        assert_eq!(Quality::from_param(Some("saver")), Quality::DataSaver);
+        assert_eq!(Quality::from_param(Some("high")), Quality::Data);
+        assert_eq!(Quality::from_param(None), Quality::Data);
+    }
+}
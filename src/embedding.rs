@@ -0,0 +1,394 @@
+use crate::search_index::tokenize;
+use axum::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmbeddingError {
+    #[error("Embedding provider is misconfigured: {0}")]
+    Config(String),
+    #[error("Embedding request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("{provider} embedding API error: {message}")]
+    Api { provider: &'static str, message: String },
+}
+
+/// Turns text into vectors for semantic search. Vectors from different
+/// providers (or models) live in different spaces, so `model_id` is stored
+/// alongside each embedding.
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Stable name of the provider and model, e.g. `ollama:nomic-embed-text`
+    fn model_id(&self) -> String;
+
+    /// One vector per document text, in order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError>;
+
+    /// Vector for a search query. Providers that embed queries and documents
+    /// differently override this.
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| EmbeddingError::Api {
+                provider: "embedding",
+                message: "no vector returned".to_string(),
+            })
+    }
+}
+
+/// Pick the provider named by `EMBEDDING_PROVIDER` (default `hashing`)
+pub fn provider_from_env() -> Result<Arc<dyn EmbeddingProvider>, EmbeddingError> {
+    let provider = env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "hashing".to_string());
+    let model = env::var("EMBEDDING_MODEL").ok();
+
+    let provider: Arc<dyn EmbeddingProvider> = match provider.to_lowercase().as_str() {
+        "hashing" => {
+            let dimensions = env::var("EMBEDDING_DIMENSIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(384);
+            Arc::new(HashingEmbeddings::new(dimensions)?)
+        }
+        "ollama" => Arc::new(OllamaEmbeddings {
+            client: http_client()?,
+            url: env::var("OLLAMA_URL").unwrap_or_else(|_| "http://localhost:11434".to_string()),
+            model: model
+                .or_else(|| env::var("OLLAMA_MODEL").ok())
+                .unwrap_or_else(|| "llama2".to_string()),
+        }),
+        "cohere" => Arc::new(CohereEmbeddings {
+            client: http_client()?,
+            api_key: env::var("COHERE_API_KEY")
+                .map_err(|_| EmbeddingError::Config("COHERE_API_KEY not set".to_string()))?,
+            model: model.unwrap_or_else(|| "embed-english-v3.0".to_string()),
+        }),
+        "openai" => Arc::new(OpenAiEmbeddings {
+            client: http_client()?,
+            base_url: env::var("EMBEDDING_API_URL")
+                .unwrap_or_else(|_| "https://api.openai.com/v1".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            // Local OpenAI-compatible servers usually take no key
+            api_key: env::var("EMBEDDING_API_KEY")
+                .or_else(|_| env::var("OPENAI_API_KEY"))
+                .ok(),
+            model: model.unwrap_or_else(|| "text-embedding-3-small".to_string()),
+        }),
+        other => {
+            return Err(EmbeddingError::Config(format!(
+                "unknown EMBEDDING_PROVIDER '{}' (expected hashing, ollama, cohere or openai)",
+                other
+            )))
+        }
+    };
+    Ok(provider)
+}
+
+fn http_client() -> Result<Client, EmbeddingError> {
+    Ok(Client::builder()
+        .user_agent("MangaViewer-AI/1.0")
+        .timeout(REQUEST_TIMEOUT)
+        .build()?)
+}
+
+/// Vectors are paired with their texts by position, so a short reply must
+/// not pass as a full one
+fn check_count(provider: &'static str, expected: usize, got: usize) -> Result<(), EmbeddingError> {
+    if got == expected {
+        Ok(())
+    } else {
+        Err(EmbeddingError::Api {
+            provider,
+            message: format!("expected {} vectors, got {}", expected, got),
+        })
+    }
+}
+
+async fn api_error(provider: &'static str, response: reqwest::Response) -> EmbeddingError {
+    let status = response.status();
+    let message = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    EmbeddingError::Api {
+        provider,
+        message: format!("{}: {}", status, message),
+    }
+}
+
+/// Feature-hashed bag of words and character trigrams. Needs no model or
+/// network and is deterministic, so it works offline and in tests; it
+/// captures shared vocabulary rather than meaning.
+pub struct HashingEmbeddings {
+    dimensions: usize,
+}
+
+impl HashingEmbeddings {
+    pub fn new(dimensions: usize) -> Result<Self, EmbeddingError> {
+        if dimensions == 0 {
+            return Err(EmbeddingError::Config("EMBEDDING_DIMENSIONS must be positive".to_string()));
+        }
+        Ok(Self { dimensions })
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        let mut add = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature.as_bytes());
+            let bucket = (hash % self.dimensions as u64) as usize;
+            // A sign bit keeps colliding features from only ever adding up
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign * weight;
+        };
+
+        for token in tokenize(text) {
+            add(&token, 1.0);
+            let chars: Vec<char> = format!("^{}$", token).chars().collect();
+            for gram in chars.windows(3) {
+                add(&gram.iter().collect::<String>(), 0.5);
+            }
+        }
+
+        normalized(&vector).unwrap_or(vector)
+    }
+}
+
+/// Unit-length copy of `vector`, or `None` for a zero or non-finite vector
+pub fn normalized(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    (norm > 0.0 && norm.is_finite()).then(|| vector.iter().map(|x| x / norm).collect())
+}
+
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Cosine similarity, or `None` when the lengths differ or either vector is zero
+pub fn cosine(a: &[f32], b: &[f32]) -> Option<f32> {
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let (norm_a, norm_b) = (norm(a), norm(b));
+    (a.len() == b.len() && norm_a > 0.0 && norm_b > 0.0).then(|| dot(a, b) / (norm_a * norm_b))
+}
+
+/// 64-bit FNV-1a; stable across builds, unlike std's hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingEmbeddings {
+    fn model_id(&self) -> String {
+        format!("hashing:{}", self.dimensions)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+pub struct OllamaEmbeddings {
+    client: Client,
+    url: String,
+    model: String,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    fn model_id(&self) -> String {
+        format!("ollama:{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        #[derive(Serialize)]
+        struct OllamaEmbedRequest<'a> {
+            model: &'a str,
+            prompt: &'a str,
+        }
+        #[derive(Deserialize)]
+        struct OllamaEmbedResponse {
+            embedding: Vec<f32>,
+        }
+
+        let url = format!("{}/api/embeddings", self.url);
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            let response = self
+                .client
+                .post(&url)
+                .json(&OllamaEmbedRequest {
+                    model: &self.model,
+                    prompt: text,
+                })
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(api_error("Ollama", response).await);
+            }
+            let response: OllamaEmbedResponse = response.json().await?;
+            embeddings.push(response.embedding);
+        }
+        check_count("Ollama", texts.len(), embeddings.len())?;
+        Ok(embeddings)
+    }
+}
+
+pub struct CohereEmbeddings {
+    client: Client,
+    api_key: String,
+    model: String,
+}
+
+#[async_trait]
+impl EmbeddingProvider for CohereEmbeddings {
+    fn model_id(&self) -> String {
+        format!("cohere:{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        self.request(texts, "search_document").await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.request(&[text.to_string()], "search_query")
+            .await?
+            .pop()
+            .ok_or_else(|| EmbeddingError::Api {
+                provider: "Cohere",
+                message: "no vector returned".to_string(),
+            })
+    }
+}
+
+impl CohereEmbeddings {
+    /// v3 models embed queries and documents differently, chosen by `input_type`
+    async fn request(&self, texts: &[String], input_type: &str) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        #[derive(Serialize)]
+        struct CohereEmbedRequest<'a> {
+            texts: &'a [String],
+            model: &'a str,
+            input_type: &'a str,
+        }
+        #[derive(Deserialize)]
+        struct CohereEmbedResponse {
+            embeddings: Vec<Vec<f32>>,
+        }
+
+        let response = self
+            .client
+            .post("https://api.cohere.ai/v1/embed")
+            .bearer_auth(&self.api_key)
+            .json(&CohereEmbedRequest {
+                texts,
+                model: &self.model,
+                input_type,
+            })
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(api_error("Cohere", response).await);
+        }
+        let response: CohereEmbedResponse = response.json().await?;
+        check_count("Cohere", texts.len(), response.embeddings.len())?;
+        Ok(response.embeddings)
+    }
+}
+
+/// Any server exposing OpenAI's `/embeddings` API
+pub struct OpenAiEmbeddings {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbeddings {
+    fn model_id(&self) -> String {
+        format!("openai:{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        #[derive(Serialize)]
+        struct OpenAiEmbedRequest<'a> {
+            model: &'a str,
+            input: &'a [String],
+        }
+        #[derive(Deserialize)]
+        struct OpenAiEmbedResponse {
+            data: Vec<OpenAiEmbedding>,
+        }
+        #[derive(Deserialize)]
+        struct OpenAiEmbedding {
+            index: usize,
+            embedding: Vec<f32>,
+        }
+
+        let mut request = self
+            .client
+            .post(format!("{}/embeddings", self.base_url))
+            .json(&OpenAiEmbedRequest {
+                model: &self.model,
+                input: texts,
+            });
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(api_error("OpenAI-compatible", response).await);
+        }
+        let mut response: OpenAiEmbedResponse = response.json().await?;
+        response.data.sort_by_key(|d| d.index);
+        check_count("OpenAI-compatible", texts.len(), response.data.len())?;
+        Ok(response.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine() {
+        assert!((cosine(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]).unwrap() - 1.0).abs() < 1e-6);
+        assert!(cosine(&[1.0, 0.0], &[0.0, 1.0]).unwrap().abs() < 1e-6);
+        assert!((cosine(&[1.0, 0.0], &[-1.0, 0.0]).unwrap() + 1.0).abs() < 1e-6);
+        assert_eq!(cosine(&[0.0, 0.0, 0.0], &[1.0, 2.0, 3.0]), None);
+        assert_eq!(cosine(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+    }
+
+    #[tokio::test]
+    async fn test_hashing_embeddings_are_deterministic_and_normalized() {
+        let provider = HashingEmbeddings::new(64).unwrap();
+        let texts = vec!["Attack on Titan".to_string(), "".to_string()];
+        let first = provider.embed(&texts).await.unwrap();
+        let second = provider.embed(&texts).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first[0].len(), 64);
+        assert!((dot(&first[0], &first[0]) - 1.0).abs() < 1e-5);
+        assert!(first[1].iter().all(|x| *x == 0.0));
+        assert_eq!(provider.model_id(), "hashing:64");
+        assert!(HashingEmbeddings::new(0).is_err());
+    }
+
+    #[test]
+    fn test_hashing_embeddings_reflect_shared_vocabulary() {
+        let provider = HashingEmbeddings::new(384).unwrap();
+        let query = provider.embed_text("dark fantasy with swords");
+        let close = provider.embed_text("A dark fantasy epic of swords and demons");
+        let far = provider.embed_text("Slice of life comedy about a cooking club");
+
+        assert!(dot(&query, &close) > dot(&query, &far) + 0.2);
+        // Accents and case are folded before hashing
+        assert!((dot(&provider.embed_text("Pokémon"), &provider.embed_text("pokemon")) - 1.0).abs() < 1e-5);
+    }
+}
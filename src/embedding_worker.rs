@@ -0,0 +1,248 @@
+use crate::cache::{cache_keys, CacheService};
+use crate::embedding::EmbeddingProvider;
+use crate::manga_service::{Manga, MangaService};
+use mongodb::bson::{doc, oid::ObjectId};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Bump when `embedding_text` changes, so every manga is re-embedded
+pub const EMBEDDING_VERSION: i32 = 1;
+
+/// The text a manga's embedding is computed from
+pub fn embedding_text(manga: &Manga) -> String {
+    format!(
+        "{} {}",
+        manga.title.as_deref().unwrap_or_default(),
+        manga.description.as_deref().unwrap_or_default()
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn text_hash(text: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+    hash[..16].to_string()
+}
+
+/// Whether a manga has no embedding for `model`, or one computed from text
+/// that has since changed
+pub fn needs_embedding(manga: &Manga, model: &str) -> bool {
+    manga.embedding_model.as_deref() != Some(model)
+        || manga.embedding_version != Some(EMBEDDING_VERSION)
+        || manga.embedding_hash.as_deref() != Some(text_hash(&embedding_text(manga)).as_str())
+}
+
+/// Progress of the current or last embedding pass
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EmbeddingProgress {
+    pub model: String,
+    pub running: bool,
+    pub pending: usize,
+    pub processed: usize,
+    pub failed: usize,
+    pub last_started_at: Option<String>,
+    pub last_finished_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+struct PendingManga {
+    id: ObjectId,
+    manga_id: Option<String>,
+    text: String,
+}
+
+/// Embeds catalog entries in the background, only touching manga whose
+/// embedding is missing or stale
+#[derive(Clone)]
+pub struct EmbeddingWorker {
+    manga_service: MangaService,
+    provider: Arc<dyn EmbeddingProvider>,
+    cache: Option<CacheService>,
+    interval: Duration,
+    batch_size: usize,
+    progress: Arc<RwLock<EmbeddingProgress>>,
+    wake: Arc<Notify>,
+}
+
+impl EmbeddingWorker {
+    pub fn new(manga_service: MangaService, provider: Arc<dyn EmbeddingProvider>, cache: Option<CacheService>) -> Self {
+        let interval_secs = env::var("EMBEDDING_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(600);
+        let batch_size = env::var("EMBEDDING_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|size: &usize| *size > 0)
+            .unwrap_or(32);
+
+        let progress = EmbeddingProgress {
+            model: provider.model_id(),
+            ..Default::default()
+        };
+        Self {
+            manga_service,
+            provider,
+            cache,
+            interval: Duration::from_secs(interval_secs),
+            batch_size,
+            progress: Arc::new(RwLock::new(progress)),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn progress(&self) -> EmbeddingProgress {
+        self.progress.read().unwrap().clone()
+    }
+
+    /// Start a pass now instead of waiting for the interval
+    pub fn trigger(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Embed pending manga now, then on every interval or trigger
+    pub fn spawn_scheduler(self) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = self.wake.notified() => {}
+                }
+                match self.run_once().await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("🧠 Embedded {} manga", count),
+                    Err(e) => {
+                        let message = e.to_string();
+                        tracing::error!("Embedding run failed: {}", message);
+                        let mut progress = self.progress.write().unwrap();
+                        progress.running = false;
+                        progress.last_error = Some(message);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Embed every manga that needs it; returns how many were updated
+    pub async fn run_once(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let model = self.provider.model_id();
+        let pending = self.pending(&model).await?;
+        {
+            let mut progress = self.progress.write().unwrap();
+            progress.running = true;
+            progress.pending = pending.len();
+            progress.processed = 0;
+            progress.failed = 0;
+            progress.last_started_at = Some(chrono::Utc::now().to_rfc3339());
+            progress.last_error = None;
+        }
+
+        let collection = self.manga_service.manga_collection();
+        let vector_index = self.manga_service.vector_index();
+        let mut processed = 0;
+        for batch in pending.chunks(self.batch_size) {
+            let texts: Vec<String> = batch.iter().map(|manga| manga.text.clone()).collect();
+            let embeddings = match self.provider.embed(&texts).await {
+                Ok(embeddings) => embeddings,
+                Err(e) => {
+                    tracing::warn!("⚠️  Embedding batch of {} failed: {}", batch.len(), e);
+                    let mut progress = self.progress.write().unwrap();
+                    progress.failed += batch.len();
+                    progress.last_error = Some(e.to_string());
+                    continue;
+                }
+            };
+
+            for (manga, embedding) in batch.iter().zip(embeddings) {
+                collection
+                    .update_one(
+                        doc! { "_id": manga.id },
+                        doc! {
+                            "$set": {
+                                "embedding": &embedding,
+                                "embedding_model": &model,
+                                "embedding_version": EMBEDDING_VERSION,
+                                "embedding_hash": text_hash(&manga.text)
+                            }
+                        },
+                    )
+                    .await?;
+                if let Some(manga_id) = &manga.manga_id {
+                    if let Err(e) = vector_index.upsert(manga_id, &embedding) {
+                        tracing::warn!("⚠️  Embedding for {} not indexed: {}", manga_id, e);
+                    }
+                    // Similar series were ranked against the old embedding
+                    if let Some(cache) = &self.cache {
+                        let _ = cache.delete(&cache_keys::manga_similar(manga_id)).await;
+                    }
+                }
+                processed += 1;
+            }
+            self.progress.write().unwrap().processed = processed;
+        }
+
+        let mut progress = self.progress.write().unwrap();
+        progress.running = false;
+        progress.last_finished_at = Some(chrono::Utc::now().to_rfc3339());
+        Ok(processed)
+    }
+
+    async fn pending(&self, model: &str) -> Result<Vec<PendingManga>, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .manga_service
+            .manga_collection()
+            .find(doc! {})
+            .projection(doc! {
+                "manga_id": 1, "title": 1, "description": 1,
+                "embedding_model": 1, "embedding_version": 1, "embedding_hash": 1
+            })
+            .await?;
+
+        let mut pending = Vec::new();
+        while cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            if let Some(id) = manga.id.filter(|_| needs_embedding(&manga, model)) {
+                pending.push(PendingManga {
+                    id,
+                    text: embedding_text(&manga),
+                    manga_id: manga.manga_id,
+                });
+            }
+        }
+        Ok(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_embedding_tracks_model_version_and_text() {
+        let mut manga: Manga = serde_json::from_value(serde_json::json!({
+            "manga_id": "a",
+            "title": "Berserk",
+            "description": "A dark fantasy"
+        }))
+        .unwrap();
+        assert!(needs_embedding(&manga, "hashing:384"));
+
+        manga.embedding_model = Some("hashing:384".to_string());
+        manga.embedding_version = Some(EMBEDDING_VERSION);
+        manga.embedding_hash = Some(text_hash(&embedding_text(&manga)));
+        assert!(!needs_embedding(&manga, "hashing:384"));
+        assert!(needs_embedding(&manga, "ollama:llama2"));
+
+        manga.description = Some("A very dark fantasy".to_string());
+        assert!(needs_embedding(&manga, "hashing:384"));
+
+        manga.embedding_hash = Some(text_hash(&embedding_text(&manga)));
+        manga.embedding_version = Some(EMBEDDING_VERSION - 1);
+        assert!(needs_embedding(&manga, "hashing:384"));
+    }
+}
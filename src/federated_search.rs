@@ -0,0 +1,412 @@
+use crate::api::MangaData;
+use crate::cached_api::CachedMangaDexClient;
+use crate::content_filter::ContentFilter;
+use crate::manga_service::{LocalizedTitle, Manga, MangaService};
+use crate::progress::ProgressService;
+use crate::search::bounded_input;
+use crate::search_index::SearchIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+
+/// Where a federated search result was found
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultSource {
+    Local,
+    Mangadex,
+}
+
+/// How a source fared for one federated search
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum SourceStatus {
+    Ok { count: usize },
+    Timeout,
+    Error { message: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FederatedResult {
+    pub manga_id: String,
+    pub sources: Vec<ResultSource>,
+    pub in_library: bool,
+    pub score: f32,
+    pub manga: Manga,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FederatedResponse {
+    pub results: Vec<FederatedResult>,
+    pub local: SourceStatus,
+    pub mangadex: SourceStatus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FederatedSearchParams {
+    pub q: String,
+    pub limit: Option<u32>,
+    pub user_id: Option<String>,
+    pub preferred_language: Option<String>,
+}
+
+/// Convert a MangaDex record to the catalog shape so both sources rank and
+/// serialize the same way
+pub fn manga_from_mangadex(data: &MangaData) -> Manga {
+    let attributes = &data.attributes;
+    // altTitles often repeat the main title, not necessarily next to it
+    let mut seen = HashSet::new();
+    let titles: Vec<LocalizedTitle> = attributes
+        .title
+        .iter()
+        .chain(attributes.alt_titles.iter().flatten())
+        .filter(|pair| seen.insert(*pair))
+        .map(|(language, title)| LocalizedTitle {
+            language: language.clone(),
+            title: title.clone(),
+        })
+        .collect();
+    let title = attributes
+        .title
+        .get("en")
+        .or_else(|| attributes.title.values().next())
+        .cloned();
+    let description = attributes
+        .description
+        .get("en")
+        .or_else(|| attributes.description.values().next())
+        .cloned();
+
+    Manga {
+        id: None,
+        manga_id: Some(data.id.clone()),
+        title,
+        description,
+        author: None,
+        artist: None,
+        status: Some(attributes.status.clone()),
+        titles: Some(titles),
+        alt_titles: None,
+        tags: None,
+        content_rating: attributes.content_rating.clone(),
+        year: attributes.year.map(i32::from),
+        rating: None,
+        rating_count: None,
+        popularity: None,
+        stats_updated_at: None,
+        display_title: None,
+        cover_art: None,
+        embedding: None,
+        embedding_model: None,
+        embedding_version: None,
+        embedding_hash: None,
+        ai_summary: None,
+        ai_summary_failed_at: None,
+        chapters: None,
+        created_at: Some(attributes.created_at.clone()),
+        updated_at: Some(attributes.updated_at.clone()),
+    }
+}
+
+/// Update a catalog entry with what MangaDex knows better: publication
+/// year, status, content rating and localized titles. Local titles,
+/// descriptions and chapters are kept, and only filled in when missing.
+pub fn refresh_from_mangadex(mut existing: Manga, imported: Manga) -> Manga {
+    let mut titles = existing.titles.take().unwrap_or_default();
+    for title in imported.titles.into_iter().flatten() {
+        if !titles.contains(&title) {
+            titles.push(title);
+        }
+    }
+    existing.titles = (!titles.is_empty()).then_some(titles);
+    existing.year = imported.year.or(existing.year);
+    existing.status = imported.status.or(existing.status);
+    existing.content_rating = imported.content_rating.or(existing.content_rating);
+    existing.title = existing.title.or(imported.title);
+    existing.description = existing.description.or(imported.description);
+    existing.updated_at = imported.updated_at.or(existing.updated_at);
+    existing
+}
+
+/// Merge results from both sources, deduplicated by MangaDex id. The local
+/// record wins when a manga is in both, since it carries catalog stats.
+pub fn merge_results(local: Vec<Manga>, remote: Vec<Manga>) -> Vec<(Manga, Vec<ResultSource>)> {
+    let mut merged: Vec<(Manga, Vec<ResultSource>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    let tagged = local
+        .into_iter()
+        .map(|m| (m, ResultSource::Local))
+        .chain(remote.into_iter().map(|m| (m, ResultSource::Mangadex)));
+    for (manga, source) in tagged {
+        let Some(manga_id) = manga.manga_id.clone() else {
+            continue;
+        };
+        match positions.get(&manga_id) {
+            Some(&i) => {
+                if !merged[i].1.contains(&source) {
+                    merged[i].1.push(source);
+                }
+            }
+            None => {
+                positions.insert(manga_id, merged.len());
+                merged.push((manga, vec![source]));
+            }
+        }
+    }
+    merged
+}
+
+/// Rank merged results with the search index's relevance model, so local and
+/// MangaDex results are scored the same way. Results that don't match every
+/// query term keep their source order after the ranked ones.
+pub fn rank_results(query: &str, merged: Vec<(Manga, Vec<ResultSource>)>) -> Vec<(Manga, Vec<ResultSource>, f32)> {
+    let index = SearchIndex::new();
+    for (manga, _) in &merged {
+        index.upsert(manga);
+    }
+    let scores: HashMap<String, (usize, f32)> = index
+        .search(query, merged.len())
+        .into_iter()
+        .enumerate()
+        .map(|(rank, hit)| (hit.manga_id, (rank, hit.score)))
+        .collect();
+
+    let mut ranked: Vec<(usize, Manga, Vec<ResultSource>, f32)> = merged
+        .into_iter()
+        .enumerate()
+        .map(|(position, (manga, sources))| {
+            let (rank, score) = manga
+                .manga_id
+                .as_deref()
+                .and_then(|id| scores.get(id).copied())
+                .unwrap_or((scores.len() + position, 0.0));
+            (rank, manga, sources, score)
+        })
+        .collect();
+    ranked.sort_by_key(|(rank, ..)| *rank);
+    ranked
+        .into_iter()
+        .map(|(_, manga, sources, score)| (manga, sources, score))
+        .collect()
+}
+
+/// Runs one search against the local catalog and MangaDex at once
+#[derive(Clone)]
+pub struct FederatedSearchService {
+    manga_service: MangaService,
+    progress_service: ProgressService,
+    mangadex: CachedMangaDexClient,
+    local_timeout: Duration,
+    remote_timeout: Duration,
+}
+
+impl FederatedSearchService {
+    pub fn new(
+        manga_service: MangaService,
+        progress_service: ProgressService,
+        mangadex: CachedMangaDexClient,
+    ) -> Self {
+        let local_timeout_ms = env::var("FEDERATED_LOCAL_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2000);
+        let remote_timeout_ms = env::var("FEDERATED_REMOTE_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(4000);
+
+        Self {
+            manga_service,
+            progress_service,
+            mangadex,
+            local_timeout: Duration::from_millis(local_timeout_ms),
+            remote_timeout: Duration::from_millis(remote_timeout_ms),
+        }
+    }
+
+    pub async fn search(
+        &self,
+        params: &FederatedSearchParams,
+        content: &ContentFilter,
+    ) -> Result<FederatedResponse, Box<dyn std::error::Error>> {
+        let query = bounded_input(&params.q)?;
+        let limit = params.limit.unwrap_or(20).clamp(1, 100);
+
+        let local = with_timeout(self.local_timeout, async {
+            let response = self
+                .manga_service
+                .search_manga(query, content)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(response.manga.unwrap_or_default())
+        });
+        let remote = with_timeout(self.remote_timeout, async {
+            let response = self
+                .mangadex
+                .search_manga(query, Some(limit), None, content)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(response
+                .data
+                .iter()
+                .map(manga_from_mangadex)
+                .filter(|manga| content.allows(manga.content_rating.as_deref()))
+                .collect())
+        });
+        let ((local, local_status), (remote, remote_status)) = tokio::join!(local, remote);
+
+        let ranked = rank_results(query, merge_results(local, remote));
+        let page: Vec<_> = ranked.into_iter().take(limit as usize).collect();
+
+        let in_library = match &params.user_id {
+            Some(user_id) => {
+                let ids: Vec<String> = page.iter().filter_map(|(m, ..)| m.manga_id.clone()).collect();
+                self.progress_service.library_manga_ids(user_id, &ids).await?
+            }
+            None => Default::default(),
+        };
+
+        let results = page
+            .into_iter()
+            .map(|(mut manga, sources, score)| {
+                manga.localize(params.preferred_language.as_deref());
+                let manga_id = manga.manga_id.clone().unwrap_or_default();
+                FederatedResult {
+                    in_library: in_library.contains(&manga_id),
+                    manga_id,
+                    sources,
+                    score,
+                    manga,
+                }
+            })
+            .collect();
+
+        Ok(FederatedResponse {
+            results,
+            local: local_status,
+            mangadex: remote_status,
+        })
+    }
+}
+
+/// Run one source with its own deadline; a slow or failing source yields no
+/// results instead of failing the whole search
+async fn with_timeout<F>(timeout: Duration, source: F) -> (Vec<Manga>, SourceStatus)
+where
+    F: Future<Output = Result<Vec<Manga>, String>>,
+{
+    match tokio::time::timeout(timeout, source).await {
+        Ok(Ok(manga)) => {
+            let count = manga.len();
+            (manga, SourceStatus::Ok { count })
+        }
+        Ok(Err(message)) => {
+            tracing::warn!("⚠️  Federated search source failed: {}", message);
+            (Vec::new(), SourceStatus::Error { message })
+        }
+        Err(_) => (Vec::new(), SourceStatus::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manga(id: &str, title: &str) -> Manga {
+        serde_json::from_value(serde_json::json!({ "manga_id": id, "title": title })).unwrap()
+    }
+
+    #[test]
+    fn test_refresh_takes_year_and_titles_from_mangadex_but_keeps_local_text() {
+        let mut local = manga("a", "Berserk (local scan)");
+        local.year = Some(1990);
+        let mut imported = manga("a", "Berserk");
+        imported.year = Some(1989);
+        imported.status = Some("ongoing".to_string());
+        imported.description = Some("Guts".to_string());
+        imported.titles = Some(vec![LocalizedTitle {
+            language: "ja".to_string(),
+            title: "ベルセルク".to_string(),
+        }]);
+
+        let refreshed = refresh_from_mangadex(local, imported);
+        assert_eq!(refreshed.year, Some(1989));
+        assert_eq!(refreshed.status.as_deref(), Some("ongoing"));
+        assert_eq!(refreshed.title.as_deref(), Some("Berserk (local scan)"));
+        assert_eq!(refreshed.description.as_deref(), Some("Guts"));
+        assert_eq!(refreshed.best_title(Some("ja")), Some("ベルセルク"));
+    }
+
+    #[test]
+    fn test_mangadex_titles_are_listed_once() {
+        let data: MangaData = serde_json::from_value(serde_json::json!({
+            "id": "a",
+            "type": "manga",
+            "attributes": {
+                "title": { "en": "Berserk" },
+                "altTitles": [{ "ja": "ベルセルク" }, { "en": "Berserk" }, { "ja": "ベルセルク" }],
+                "description": {},
+                "status": "ongoing",
+                "lastVolume": null,
+                "lastChapter": null,
+                "originalLanguage": "ja",
+                "year": 1989,
+                "contentRating": "suggestive",
+                "createdAt": "2018-01-01T00:00:00+00:00",
+                "updatedAt": "2024-01-01T00:00:00+00:00",
+                "version": 1,
+                "latestUploadedChapter": null
+            }
+        }))
+        .unwrap();
+        let titles: Vec<(String, String)> = manga_from_mangadex(&data)
+            .titles
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| (t.language, t.title))
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                ("en".to_string(), "Berserk".to_string()),
+                ("ja".to_string(), "ベルセルク".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_dedups_by_mangadex_id() {
+        let local = vec![manga("a", "Berserk"), manga("b", "Monster")];
+        let remote = vec![manga("b", "Monster"), manga("c", "Berserk of Gluttony")];
+        let merged = merge_results(local, remote);
+        let summary: Vec<(&str, &[ResultSource])> = merged
+            .iter()
+            .map(|(m, s)| (m.manga_id.as_deref().unwrap(), s.as_slice()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", &[ResultSource::Local][..]),
+                ("b", &[ResultSource::Local, ResultSource::Mangadex][..]),
+                ("c", &[ResultSource::Mangadex][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rank_uses_one_model_across_sources() {
+        let merged = vec![
+            (manga("a", "Berserk of Gluttony"), vec![ResultSource::Local]),
+            (manga("b", "Unrelated"), vec![ResultSource::Local]),
+            (manga("c", "Berserk"), vec![ResultSource::Mangadex]),
+        ];
+        let ranked: Vec<String> = rank_results("berserk", merged)
+            .into_iter()
+            .map(|(m, ..)| m.manga_id.unwrap())
+            .collect();
+        assert_eq!(ranked, vec!["c", "a", "b"]);
+    }
+}
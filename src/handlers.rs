@@ -1,15 +1,31 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
 use serde::Deserialize;
 
+use crate::auth_mongodb::{extract_token_from_header, AuthService};
+use crate::auto_download::{AutoDownloadRule, AutoDownloadService};
 use crate::progress::{ReadingStatus, ProgressService};
-use crate::search::{AdvancedSearchParams, SearchService};
+use crate::search::{bounded_input, AdvancedSearchParams, SearchInputError, SearchService};
+use crate::pagination::InvalidCursor;
 use crate::manga_service::MangaService;
 use crate::cache::CacheService;
+use crate::content_filter::{ContentFilter, ContentPolicy, ContentPolicyService};
+use crate::embedding_worker::EmbeddingWorker;
+use crate::api::MangaDexClientError;
+use crate::cached_api::CachedMangaDexClient;
+use crate::federated_search::{FederatedSearchParams, FederatedSearchService};
+use crate::hybrid_search::HybridUnavailable;
+use crate::prefetch::Prefetcher;
+use crate::query_parser::QueryParser;
+use crate::recommendations::RecommendationService;
+use crate::saved_search::SavedSearchService;
+use crate::similar::SimilarService;
+use crate::summaries::{ProposalStatus, SummariesUnavailable, SummaryRecentlyFailed, SummaryService};
+use crate::dedup::{DedupService, DuplicateStatus, InvalidSurvivor};
 
 // AppState type for handlers
 #[derive(Clone)]
@@ -18,6 +34,26 @@ pub struct AppState {
     pub progress_service: ProgressService,
     pub search_service: SearchService,
     pub cache_service: Option<CacheService>,
+    pub auto_download: AutoDownloadService,
+    pub prefetcher: Prefetcher,
+    pub auth_service: AuthService,
+    pub saved_searches: SavedSearchService,
+    pub federated_search: FederatedSearchService,
+    pub mangadex: CachedMangaDexClient,
+    pub content_policy: ContentPolicyService,
+    pub embedding_worker: Option<EmbeddingWorker>,
+    pub recommendations: Option<RecommendationService>,
+    pub similar: SimilarService,
+    pub query_parser: QueryParser,
+    pub summaries: SummaryService,
+    pub dedup: DedupService,
+}
+
+/// The user behind the request's bearer token, if any
+async fn request_user_id(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    let token = extract_token_from_header(headers)?;
+    let claims = state.auth_service.verify_token(&token).await.ok()?;
+    Some(claims.sub)
 }
 
 // ====== Progress Tracking Handlers ======
@@ -57,6 +93,13 @@ pub struct UpdateStatusRequest {
     pub status: ReadingStatus,
 }
 
+#[derive(Deserialize)]
+pub struct SetRatingRequest {
+    pub user_id: String,
+    pub manga_id: String,
+    pub rating: Option<u8>, // 1-5 stars, null clears
+}
+
 #[derive(Deserialize)]
 pub struct RemoveFromLibraryRequest {
     pub user_id: String,
@@ -109,13 +152,19 @@ pub async fn update_progress_handler(
         )
         .await
     {
-        Ok(_) => (
-            StatusCode::OK,
-            Json(serde_json::json!({
-                "success": true,
-                "message": "Progress updated"
-            })),
-        ),
+        Ok(progress) => {
+            let prefetching = state
+                .prefetcher
+                .on_progress(&req.chapter_id, progress.progress_percentage);
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "success": true,
+                    "message": "Progress updated",
+                    "prefetching": prefetching
+                })),
+            )
+        }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({
@@ -201,6 +250,49 @@ pub async fn update_status_handler(
     }
 }
 
+/// Rate a manga in the user's library
+pub async fn set_rating_handler(
+    State(state): State<AppState>,
+    Json(req): Json<SetRatingRequest>,
+) -> impl IntoResponse {
+    if req.rating.is_some_and(|r| !(1..=5).contains(&r)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Rating must be between 1 and 5"
+            })),
+        );
+    }
+
+    match state.progress_service
+        .set_rating(&req.user_id, &req.manga_id, req.rating)
+        .await
+    {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "message": "Rating updated"
+            })),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Manga is not in the user's library"
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
 /// Remove manga from library
 pub async fn remove_from_library_handler(
     State(state): State<AppState>,
@@ -227,28 +319,387 @@ pub async fn remove_from_library_handler(
     }
 }
 
+#[derive(Deserialize)]
+pub struct SetAutoDownloadRequest {
+    pub user_id: String,
+    pub manga_id: String,
+    pub rule: Option<AutoDownloadRule>,
+}
+
+#[derive(Deserialize)]
+pub struct GetAutoDownloadsQuery {
+    pub user_id: String,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+/// Set or clear the auto-download rule of a library entry
+pub async fn set_auto_download_handler(
+    State(state): State<AppState>,
+    Json(req): Json<SetAutoDownloadRequest>,
+) -> impl IntoResponse {
+    match state.progress_service
+        .set_auto_download_rule(&req.user_id, &req.manga_id, req.rule)
+        .await
+    {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "message": "Auto-download rule updated"
+            })),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Manga is not in the user's library"
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// List auto-download jobs and storage usage for a user
+pub async fn get_auto_downloads_handler(
+    State(state): State<AppState>,
+    Query(query): Query<GetAutoDownloadsQuery>,
+) -> impl IntoResponse {
+    let usage = state.auto_download.usage(&query.user_id).await;
+    match state.auto_download.list_jobs(&query.user_id, query.limit).await {
+        Ok(jobs) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "jobs": jobs,
+                "usage": usage
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
 // ====== Advanced Search Handlers ======
 
+/// Bad search input is the client's fault; anything else is ours
+fn search_error_status(e: &(dyn std::error::Error + 'static)) -> StatusCode {
+    if e.is::<SearchInputError>() || e.is::<InvalidCursor>() {
+        StatusCode::BAD_REQUEST
+    } else if e.is::<HybridUnavailable>() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
 /// Advanced search with filters and caching
 pub async fn advanced_search_handler(
     State(state): State<AppState>,
-    Query(params): Query<AdvancedSearchParams>,
+    content: ContentFilter,
+    Query(mut params): Query<AdvancedSearchParams>,
 ) -> impl IntoResponse {
+    params.preferred_language = params.preferred_language.or(content.preferred_language.clone());
+    params.content_rating = Some(content.narrow(params.content_rating.take()));
     match state.search_service
         .advanced_search(&state.manga_service, params)
         .await
     {
-        Ok(results) => (
-            StatusCode::OK,
-            Json(serde_json::json!({
+        Ok(results) => {
+            let mut body = serde_json::json!({
                 "success": results.success,
                 "manga": results.manga,
                 "total_count": results.total_count,
-                "message": results.message
+                "message": results.message,
+                "facets": results.facets,
+                "next_cursor": results.next_cursor
+            });
+            if let Some(scores) = results.scores {
+                body["scores"] = serde_json::json!(scores);
+            }
+            (StatusCode::OK, Json(body))
+        }
+        Err(e) => (
+            search_error_status(e.as_ref()),
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ParseSearchQuery {
+    pub q: String,
+}
+
+/// Turn a natural-language request into advanced search filters
+pub async fn parse_search_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ParseSearchQuery>,
+) -> impl IntoResponse {
+    let text = match bounded_input(&query.q) {
+        Ok(text) if !text.is_empty() => text,
+        Ok(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": "q must not be empty"
+                })),
+            )
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": e.to_string()
+                })),
+            )
+        }
+    };
+
+    match state.query_parser.parse(text).await {
+        Ok(parsed) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "params": parsed.params,
+                "parser": parsed.parser,
+                "unknown_tags": parsed.unknown_tags
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RecommendationsQuery {
+    pub user_id: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Unread series close to the user's library, ratings and history
+pub async fn recommendations_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    content: ContentFilter,
+    Query(query): Query<RecommendationsQuery>,
+) -> impl IntoResponse {
+    let Some(recommendations) = &state.recommendations else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "No embedding provider configured"
+            })),
+        );
+    };
+    let user_id = match query.user_id {
+        Some(user_id) => user_id,
+        None => match request_user_id(&state, &headers).await {
+            Some(user_id) => user_id,
+            None => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({
+                        "success": false,
+                        "error": "Invalid or missing token"
+                    })),
+                )
+            }
+        },
+    };
+    let limit = query.limit.unwrap_or(20).clamp(1, 50);
+
+    match recommendations.recommend(&user_id, limit, &content).await {
+        Ok(recommendations) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "recommendations": recommendations
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SimilarQuery {
+    pub limit: Option<usize>,
+}
+
+/// Series closest to one manga by embedding, tags and creators
+pub async fn similar_manga_handler(
+    State(state): State<AppState>,
+    Path(manga_id): Path<String>,
+    content: ContentFilter,
+    Query(query): Query<SimilarQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(12).clamp(1, 50);
+
+    match state.similar.similar(&manga_id, limit, &content).await {
+        Ok(Some(similar)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "similar": similar
+            })),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Manga not found"
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
             })),
         ),
+    }
+}
+
+/// Add a MangaDex series to the local catalog, or refresh it from MangaDex
+pub async fn import_manga_handler(
+    State(state): State<AppState>,
+    Path(manga_id): Path<String>,
+) -> impl IntoResponse {
+    let data = match state.mangadex.get_manga(&manga_id).await {
+        Ok(data) => data,
+        Err(e) => {
+            let status = match e {
+                MangaDexClientError::NotFound(_) => StatusCode::NOT_FOUND,
+                _ => StatusCode::BAD_GATEWAY,
+            };
+            return (
+                status,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": e.to_string()
+                })),
+            );
+        }
+    };
+    // Only the message is kept, so the error is not held across an await
+    let imported = state
+        .manga_service
+        .import_from_mangadex(&data)
+        .await
+        .map_err(|e| e.to_string());
+    match imported {
+        Ok(response) => {
+            let _ = state.search_service.invalidate_search_cache().await;
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "success": true,
+                    "manga": response.manga
+                })),
+            )
+        }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e
+            })),
+        ),
+    }
+}
+
+fn summary_error_status(e: &(dyn std::error::Error + 'static)) -> StatusCode {
+    if e.is::<SummariesUnavailable>() || e.is::<SummaryRecentlyFailed>() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// AI-written summary of a series, generated on first request
+pub async fn manga_summary_handler(
+    State(state): State<AppState>,
+    Path(manga_id): Path<String>,
+) -> impl IntoResponse {
+    match state.summaries.summary(&manga_id).await {
+        Ok(Some((summary, stale))) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "summary": summary,
+                "stale": stale
+            })),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Manga not found"
+            })),
+        ),
+        Err(e) => (
+            summary_error_status(e.as_ref()),
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// Search the local catalog and MangaDex together
+pub async fn federated_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    content: ContentFilter,
+    Query(mut params): Query<FederatedSearchParams>,
+) -> impl IntoResponse {
+    if params.user_id.is_none() {
+        params.user_id = request_user_id(&state, &headers).await;
+    }
+    params.preferred_language = params.preferred_language.or(content.preferred_language.clone());
+    match state.federated_search.search(&params, &content).await {
+        Ok(response) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "results": response.results,
+                "sources": {
+                    "local": response.local,
+                    "mangadex": response.mangadex
+                }
+            })),
+        ),
+        Err(e) => (
+            search_error_status(e.as_ref()),
             Json(serde_json::json!({
                 "success": false,
                 "error": e.to_string()
@@ -262,6 +713,7 @@ pub struct AutocompleteQuery {
     pub q: String,
     #[serde(default = "default_autocomplete_limit")]
     pub limit: u32,
+    pub preferred_language: Option<String>,
 }
 
 fn default_autocomplete_limit() -> u32 {
@@ -271,13 +723,17 @@ fn default_autocomplete_limit() -> u32 {
 /// Autocomplete suggestions for search
 pub async fn autocomplete_handler(
     State(state): State<AppState>,
+    content: ContentFilter,
     Query(query): Query<AutocompleteQuery>,
 ) -> impl IntoResponse {
+    let language = query.preferred_language.or(content.preferred_language.clone());
     match crate::search::get_autocomplete_suggestions(
         &state.manga_service,
         &state.cache_service,
         &query.q,
         query.limit,
+        language.as_deref(),
+        &content,
     )
     .await
     {
@@ -289,7 +745,7 @@ pub async fn autocomplete_handler(
             })),
         ),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            search_error_status(e.as_ref()),
             Json(serde_json::json!({
                 "success": false,
                 "error": e.to_string()
@@ -422,8 +878,11 @@ pub struct AddHistoryRequest {
 #[derive(Deserialize)]
 pub struct GetHistoryQuery {
     pub user_id: String,
+    #[serde(default = "default_page")]
+    pub page: u32,
     #[serde(default = "default_history_limit")]
     pub limit: u32,
+    pub cursor: Option<String>,
 }
 
 fn default_history_limit() -> u32 {
@@ -480,18 +939,20 @@ pub async fn get_history_handler(
     Query(query): Query<GetHistoryQuery>,
 ) -> impl IntoResponse {
     match state.progress_service
-        .get_reading_history(&query.user_id, query.limit)
+        .get_reading_history(&query.user_id, query.page, query.limit, query.cursor.as_deref())
         .await
     {
         Ok(history) => (
             StatusCode::OK,
             Json(serde_json::json!({
                 "success": true,
-                "history": history
+                "next_cursor": history.pagination.next_cursor,
+                "history": history.data,
+                "pagination": history.pagination
             })),
         ),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            search_error_status(e.as_ref()),
             Json(serde_json::json!({
                 "success": false,
                 "error": e.to_string()
@@ -602,3 +1063,687 @@ pub async fn get_favorites_handler(
         ),
     }
 }
+
+// ====== Saved Search Handlers ======
+
+#[derive(Deserialize)]
+pub struct SaveSearchRequest {
+    pub user_id: String,
+    pub name: String,
+    pub params: AdvancedSearchParams,
+    #[serde(default = "default_alerts_enabled")]
+    pub alerts_enabled: bool,
+}
+
+fn default_alerts_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub struct SavedSearchQuery {
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct SavedSearchRef {
+    pub user_id: String,
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+pub struct RunSavedSearchQuery {
+    pub user_id: String,
+    pub id: String,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchNotificationsQuery {
+    pub user_id: String,
+    #[serde(default)]
+    pub unread_only: bool,
+    #[serde(default = "default_notifications_limit")]
+    pub limit: u32,
+}
+
+fn default_notifications_limit() -> u32 {
+    50
+}
+
+#[derive(Deserialize)]
+pub struct MarkNotificationsReadRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub ids: Vec<String>, // empty marks everything read
+}
+
+/// Save a search (or update the saved search with the same query)
+pub async fn save_search_handler(
+    State(state): State<AppState>,
+    content: ContentFilter,
+    Json(mut req): Json<SaveSearchRequest>,
+) -> impl IntoResponse {
+    req.params.content_rating = Some(content.narrow(req.params.content_rating.take()));
+    match state.saved_searches
+        .save(&req.user_id, &req.name, req.params, req.alerts_enabled)
+        .await
+    {
+        Ok(saved) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "saved_search": saved
+            })),
+        ),
+        Err(e) => (
+            search_error_status(e.as_ref()),
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// List a user's saved searches
+pub async fn list_saved_searches_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SavedSearchQuery>,
+) -> impl IntoResponse {
+    match state.saved_searches.list(&query.user_id).await {
+        Ok(saved_searches) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "saved_searches": saved_searches
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// Delete a saved search and its notifications
+pub async fn delete_saved_search_handler(
+    State(state): State<AppState>,
+    Json(req): Json<SavedSearchRef>,
+) -> impl IntoResponse {
+    match state.saved_searches.delete(&req.user_id, &req.id).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "message": "Saved search deleted"
+            })),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Saved search not found"
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// Replay a saved search
+pub async fn run_saved_search_handler(
+    State(state): State<AppState>,
+    Query(query): Query<RunSavedSearchQuery>,
+) -> impl IntoResponse {
+    let saved = match state.saved_searches.get(&query.user_id, &query.id).await {
+        Ok(Some(saved)) => saved,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": "Saved search not found"
+                })),
+            )
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": e.to_string()
+                })),
+            )
+        }
+    };
+
+    match state.saved_searches.replay(&saved, query.page, query.limit).await {
+        Ok(results) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": results.success,
+                "name": saved.name,
+                "manga": results.manga,
+                "total_count": results.total_count,
+                "message": results.message
+            })),
+        ),
+        Err(e) => (
+            search_error_status(e.as_ref()),
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// New matches found for a user's saved searches
+pub async fn search_notifications_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SearchNotificationsQuery>,
+) -> impl IntoResponse {
+    match state.saved_searches
+        .notifications(&query.user_id, query.unread_only, query.limit)
+        .await
+    {
+        Ok(notifications) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "notifications": notifications
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// Mark saved search notifications as read
+pub async fn mark_notifications_read_handler(
+    State(state): State<AppState>,
+    Json(req): Json<MarkNotificationsReadRequest>,
+) -> impl IntoResponse {
+    match state.saved_searches.mark_read(&req.user_id, &req.ids).await {
+        Ok(updated) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "updated": updated
+            })),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+// ====== Content Policy Handlers ======
+
+/// The bearer token's user when they are an admin
+async fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<String, (StatusCode, &'static str)> {
+    let user_id = request_user_id(state, headers)
+        .await
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid or missing token"))?;
+    let is_admin = state
+        .auth_service
+        .get_user_by_id(&user_id)
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|user| user.is_admin);
+    if is_admin {
+        Ok(user_id)
+    } else {
+        Err((StatusCode::FORBIDDEN, "Insufficient permissions"))
+    }
+}
+
+/// Instance-wide content ceilings
+pub async fn get_content_policy_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err((status, error)) = require_admin(&state, &headers).await {
+        return (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "success": true,
+            "policy": state.content_policy.policy().await
+        })),
+    )
+}
+
+/// Set instance-wide content ceilings
+pub async fn set_content_policy_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(policy): Json<ContentPolicy>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&state, &headers).await {
+        Ok(user_id) => user_id,
+        Err((status, error)) => {
+            return (
+                status,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": error
+                })),
+            )
+        }
+    };
+    match state.content_policy.set_policy(policy, &admin_user_id).await {
+        Ok(policy) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "policy": policy
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+/// The embedding worker for admin requests, or the error response to send
+async fn admin_embedding_worker(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<EmbeddingWorker, (StatusCode, Json<serde_json::Value>)> {
+    let failure = |status: StatusCode, error: &str| {
+        (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+        )
+    };
+    require_admin(state, headers)
+        .await
+        .map_err(|(status, error)| failure(status, error))?;
+    state
+        .embedding_worker
+        .clone()
+        .ok_or_else(|| failure(StatusCode::SERVICE_UNAVAILABLE, "No embedding provider configured"))
+}
+
+/// Progress of the background embedding job
+pub async fn get_embedding_progress_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match admin_embedding_worker(&state, &headers).await {
+        Ok(worker) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "progress": worker.progress()
+            })),
+        ),
+        Err(response) => response,
+    }
+}
+
+/// Start an embedding pass without waiting for the next interval
+pub async fn run_embeddings_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match admin_embedding_worker(&state, &headers).await {
+        Ok(worker) => {
+            worker.trigger();
+            (
+                StatusCode::ACCEPTED,
+                Json(serde_json::json!({
+                    "success": true,
+                    "progress": worker.progress()
+                })),
+            )
+        }
+        Err(response) => response,
+    }
+}
+
+/// Summarize a batch of manga without waiting for the next interval
+pub async fn run_summaries_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err((status, error)) = require_admin(&state, &headers).await {
+        return (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+        );
+    }
+    match state.summaries.trigger() {
+        Ok(()) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({
+                "success": true,
+                "message": "Summary batch started"
+            })),
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TagProposalsQuery {
+    pub status: Option<ProposalStatus>,
+    pub limit: Option<u32>,
+}
+
+/// Tags suggested alongside AI summaries, pending ones by default
+pub async fn list_tag_proposals_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<TagProposalsQuery>,
+) -> impl IntoResponse {
+    if let Err((status, error)) = require_admin(&state, &headers).await {
+        return (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+        );
+    }
+    let status = query.status.unwrap_or(ProposalStatus::Pending);
+    match state.summaries.proposals(status, query.limit.unwrap_or(100)).await {
+        Ok(proposals) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "proposals": proposals
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReviewTagProposalRequest {
+    pub id: String,
+    pub approve: bool,
+}
+
+/// Approve a tag proposal, merging the tag into the manga, or reject it
+pub async fn review_tag_proposal_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ReviewTagProposalRequest>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&state, &headers).await {
+        Ok(user_id) => user_id,
+        Err((status, error)) => {
+            return (
+                status,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": error
+                })),
+            )
+        }
+    };
+    // Only the message is kept, so the error is not held across an await
+    let reviewed = state
+        .summaries
+        .review(&req.id, req.approve, &admin_user_id)
+        .await
+        .map_err(|e| e.to_string());
+    match reviewed {
+        Ok(Some(proposal)) => {
+            if req.approve {
+                let _ = state.search_service.invalidate_search_cache().await;
+            }
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "success": true,
+                    "proposal": proposal
+                })),
+            )
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "No pending proposal with that id"
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e
+            })),
+        ),
+    }
+}
+
+fn dedup_error_status(e: &(dyn std::error::Error + 'static)) -> StatusCode {
+    if e.is::<InvalidSurvivor>() || e.is::<mongodb::bson::oid::Error>() {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// Look for duplicate series without waiting for the next interval
+pub async fn run_dedup_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err((status, error)) = require_admin(&state, &headers).await {
+        return (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+        );
+    }
+    state.dedup.trigger();
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "success": true,
+            "message": "Duplicate scan started"
+        })),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct DuplicatesQuery {
+    pub status: Option<DuplicateStatus>,
+    pub limit: Option<u32>,
+}
+
+/// Likely duplicate series, pending ones by default, best match first
+pub async fn list_duplicates_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<DuplicatesQuery>,
+) -> impl IntoResponse {
+    if let Err((status, error)) = require_admin(&state, &headers).await {
+        return (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": error
+            })),
+        );
+    }
+    let status = query.status.unwrap_or(DuplicateStatus::Pending);
+    match state.dedup.candidates(status, query.limit.unwrap_or(100)).await {
+        Ok(candidates) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "duplicates": candidates
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MergeDuplicateRequest {
+    pub id: String,
+    pub keep_manga_id: String,
+}
+
+/// Merge a duplicate pair into the manga to keep, moving every reader's
+/// library, progress, bookmarks and history over to it
+pub async fn merge_duplicate_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<MergeDuplicateRequest>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&state, &headers).await {
+        Ok(user_id) => user_id,
+        Err((status, error)) => {
+            return (
+                status,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": error
+                })),
+            )
+        }
+    };
+    // Only the status and message are kept, so the error is not held across an await
+    let merged = state
+        .dedup
+        .merge(&req.id, &req.keep_manga_id, &admin_user_id)
+        .await
+        .map_err(|e| (dedup_error_status(e.as_ref()), e.to_string()));
+    match merged {
+        Ok(Some((candidate, moved))) => {
+            let _ = state.search_service.invalidate_search_cache().await;
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "success": true,
+                    "duplicate": candidate,
+                    "moved": moved
+                })),
+            )
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "No pending duplicate with that id"
+            })),
+        ),
+        Err((status, e)) => (
+            status,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e
+            })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DismissDuplicateRequest {
+    pub id: String,
+}
+
+/// Mark a pair as two different series
+pub async fn dismiss_duplicate_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<DismissDuplicateRequest>,
+) -> impl IntoResponse {
+    let admin_user_id = match require_admin(&state, &headers).await {
+        Ok(user_id) => user_id,
+        Err((status, error)) => {
+            return (
+                status,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": error
+                })),
+            )
+        }
+    };
+    match state.dedup.dismiss(&req.id, &admin_user_id).await {
+        Ok(Some(candidate)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "duplicate": candidate
+            })),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "No pending duplicate with that id"
+            })),
+        ),
+        Err(e) => (
+            dedup_error_status(e.as_ref()),
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Rank offset from the original reciprocal rank fusion paper; larger values
+/// flatten the advantage of the very top ranks
+pub const RRF_K: f32 = 60.0;
+
+/// Candidates taken from each ranking before fusion
+pub const HYBRID_CANDIDATES: usize = 200;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Hybrid search needs an embedding provider")]
+pub struct HybridUnavailable;
+
+/// Why a result ranked where it did in a hybrid search. Ranks are 1-based
+/// and absent when the result was not in that ranking.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HybridScore {
+    pub manga_id: String,
+    pub score: f32,
+    pub lexical_rank: Option<usize>,
+    pub lexical_score: Option<f32>,
+    pub semantic_rank: Option<usize>,
+    pub semantic_similarity: Option<f32>,
+}
+
+/// Fuse a lexical and a semantic ranking, each best first, by reciprocal
+/// rank fusion. Only ranks count, so text scores and cosine similarities
+/// never need to be put on the same scale.
+pub fn reciprocal_rank_fusion(lexical: &[(String, Option<f32>)], semantic: &[(String, f32)]) -> Vec<HybridScore> {
+    let mut fused: Vec<HybridScore> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    fn slot<'a>(manga_id: &'a str, fused: &mut Vec<HybridScore>, positions: &mut HashMap<&'a str, usize>) -> usize {
+        *positions.entry(manga_id).or_insert_with(|| {
+            fused.push(HybridScore {
+                manga_id: manga_id.to_string(),
+                score: 0.0,
+                lexical_rank: None,
+                lexical_score: None,
+                semantic_rank: None,
+                semantic_similarity: None,
+            });
+            fused.len() - 1
+        })
+    }
+
+    for (i, (manga_id, score)) in lexical.iter().enumerate() {
+        let at = slot(manga_id, &mut fused, &mut positions);
+        if fused[at].lexical_rank.is_none() {
+            fused[at].lexical_rank = Some(i + 1);
+            fused[at].lexical_score = *score;
+            fused[at].score += 1.0 / (RRF_K + (i + 1) as f32);
+        }
+    }
+    for (i, (manga_id, similarity)) in semantic.iter().enumerate() {
+        let at = slot(manga_id, &mut fused, &mut positions);
+        if fused[at].semantic_rank.is_none() {
+            fused[at].semantic_rank = Some(i + 1);
+            fused[at].semantic_similarity = Some(*similarity);
+            fused[at].score += 1.0 / (RRF_K + (i + 1) as f32);
+        }
+    }
+
+    // Ties go to the better single rank, then to the lexical side
+    let best_rank = |s: &HybridScore| {
+        s.lexical_rank
+            .unwrap_or(usize::MAX)
+            .min(s.semantic_rank.unwrap_or(usize::MAX))
+    };
+    fused.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(best_rank(a).cmp(&best_rank(b)))
+            .then(a.lexical_rank.is_none().cmp(&b.lexical_rank.is_none()))
+    });
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(scores: &[HybridScore]) -> Vec<&str> {
+        scores.iter().map(|s| s.manga_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_fusion_rewards_agreement_between_rankings() {
+        let lexical = vec![
+            ("a".to_string(), Some(9.0)),
+            ("b".to_string(), Some(5.0)),
+            ("c".to_string(), Some(1.0)),
+        ];
+        let semantic = vec![("c".to_string(), 0.9), ("d".to_string(), 0.8), ("b".to_string(), 0.7)];
+        let fused = reciprocal_rank_fusion(&lexical, &semantic);
+
+        assert_eq!(ids(&fused), vec!["c", "b", "a", "d"]);
+        let c = &fused[0];
+        assert_eq!((c.lexical_rank, c.semantic_rank), (Some(3), Some(1)));
+        assert_eq!(c.semantic_similarity, Some(0.9));
+        assert!((c.score - (1.0 / 63.0 + 1.0 / 61.0)).abs() < 1e-6);
+        assert_eq!(fused[3].lexical_rank, None);
+    }
+
+    #[test]
+    fn test_fusion_ties_prefer_lexical_side() {
+        let fused = reciprocal_rank_fusion(&[("a".to_string(), None)], &[("b".to_string(), 0.5)]);
+        assert_eq!(ids(&fused), vec!["a", "b"]);
+        assert!(reciprocal_rank_fusion(&[], &[]).is_empty());
+    }
+}
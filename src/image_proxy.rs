@@ -0,0 +1,638 @@
+use crate::downloader::{
+    detect_image_kind, is_cache_hit, sha256_hex, should_report, spawn_at_home_report, write_atomic,
+    AtHomeReport, ImageKind,
+};
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Widths served by the proxy; requests are rounded up to the next bucket
+pub const WIDTH_BUCKETS: [u32; 6] = [160, 320, 480, 720, 1080, 1440];
+
+/// Upstream image URLs are content addressed, so responses never change
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Hosts the proxy may fetch from; anything else is refused
+const DEFAULT_ALLOWED_HOSTS: &str = "uploads.mangadex.org,mangadex.network";
+
+/// Largest upstream image the proxy will download
+const DEFAULT_MAX_IMAGE_MB: u64 = 20;
+
+/// WebP renditions are lossy so they come out smaller than the JPEG pages
+/// they replace
+const WEBP_QUALITY: f32 = 75.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetFormat {
+    Original,
+    WebP,
+}
+
+/// A resized and/or transcoded rendition of an upstream image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variant {
+    pub width: Option<u32>,
+    pub format: TargetFormat,
+}
+
+impl Variant {
+    pub const ORIGINAL: Variant = Variant {
+        width: None,
+        format: TargetFormat::Original,
+    };
+
+    fn is_original(&self) -> bool {
+        self.width.is_none() && self.format == TargetFormat::Original
+    }
+}
+
+/// Round a requested width up to the nearest bucket, or `None` for full size
+pub fn width_bucket(requested: u32) -> Option<u32> {
+    WIDTH_BUCKETS.iter().copied().find(|b| *b >= requested)
+}
+
+fn cache_key(url: &str, variant: &Variant) -> String {
+    let format = match variant.format {
+        TargetFormat::Original => "orig",
+        TargetFormat::WebP => "webp",
+    };
+    let width = variant.width.map(|w| w.to_string()).unwrap_or_default();
+    sha256_hex(format!("{}|{}|{}", url, width, format).as_bytes())
+}
+
+fn content_type(kind: ImageKind) -> &'static str {
+    match kind {
+        ImageKind::Jpeg => "image/jpeg",
+        ImageKind::Png => "image/png",
+        ImageKind::Gif => "image/gif",
+        ImageKind::WebP => "image/webp",
+    }
+}
+
+/// Size-bounded least-recently-used bookkeeping for the disk cache
+#[derive(Default)]
+struct LruIndex {
+    entries: HashMap<String, (u64, u64)>, // key -> (size, last use)
+    order: BTreeMap<u64, String>,         // last use -> key
+    total: u64,
+    clock: u64,
+}
+
+impl LruIndex {
+    fn touch(&mut self, key: &str) -> bool {
+        let Some((_, last_use)) = self.entries.get(key).copied() else {
+            return false;
+        };
+        self.clock += 1;
+        self.order.remove(&last_use);
+        self.order.insert(self.clock, key.to_string());
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.1 = self.clock;
+        }
+        true
+    }
+
+    /// Record an entry and return the keys evicted to stay under `max_bytes`
+    fn insert(&mut self, key: &str, size: u64, max_bytes: u64) -> Vec<String> {
+        self.remove(key);
+        self.clock += 1;
+        self.entries.insert(key.to_string(), (size, self.clock));
+        self.order.insert(self.clock, key.to_string());
+        self.total += size;
+
+        let mut evicted = Vec::new();
+        while self.total > max_bytes {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if oldest == key {
+                // Never evict the entry just written
+                self.order.insert(self.clock, oldest);
+                break;
+            }
+            if let Some((size, _)) = self.entries.remove(&oldest) {
+                self.total -= size;
+            }
+            evicted.push(oldest);
+        }
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, last_use)) = self.entries.remove(key) {
+            self.order.remove(&last_use);
+            self.total -= size;
+        }
+    }
+}
+
+/// Content-addressed image files on disk with an LRU size cap
+struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<LruIndex>,
+}
+
+impl DiskCache {
+    /// Open the cache directory, indexing existing files oldest first
+    async fn open(dir: PathBuf, max_bytes: u64) -> std::io::Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".part") {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+                continue;
+            }
+            if let Ok(meta) = entry.metadata().await {
+                let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+                files.push((modified, name, meta.len()));
+            }
+        }
+        files.sort();
+
+        let mut index = LruIndex::default();
+        for (_, name, size) in files {
+            for evicted in index.insert(&name, size, max_bytes) {
+                let _ = tokio::fs::remove_file(dir.join(evicted)).await;
+            }
+        }
+
+        Ok(Self {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        })
+    }
+
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        if !self.index.lock().unwrap().touch(key) {
+            return None;
+        }
+        match tokio::fs::read(self.dir.join(key)).await {
+            Ok(bytes) => Some(bytes),
+            Err(_) => {
+                self.index.lock().unwrap().remove(key);
+                None
+            }
+        }
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8]) {
+        if let Err(e) = write_atomic(&self.dir.join(key), bytes).await {
+            tracing::warn!("Failed to write image cache entry {}: {}", key, e);
+            return;
+        }
+        let evicted = self
+            .index
+            .lock()
+            .unwrap()
+            .insert(key, bytes.len() as u64, self.max_bytes);
+        for key in evicted {
+            let _ = tokio::fs::remove_file(self.dir.join(&key)).await;
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProxyError {
+    #[error("Invalid image URL")]
+    InvalidUrl,
+    #[error("Host is not allowed: {0}")]
+    ForbiddenHost(String),
+    #[error("Upstream request failed: {0}")]
+    Upstream(String),
+    #[error("Upstream did not return an image")]
+    NotAnImage,
+    #[error("Upstream image is larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("Failed to transform image: {0}")]
+    Transform(String),
+}
+
+impl ProxyError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ProxyError::InvalidUrl => StatusCode::BAD_REQUEST,
+            ProxyError::ForbiddenHost(_) => StatusCode::FORBIDDEN,
+            ProxyError::Upstream(_) | ProxyError::NotAnImage | ProxyError::TooLarge(_) => StatusCode::BAD_GATEWAY,
+            ProxyError::Transform(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Caching image proxy in front of MangaDex covers and pages
+#[derive(Clone)]
+pub struct ImageProxy {
+    client: reqwest::Client,
+    // Image fetches only; redirects are not followed, since only the first
+    // URL is checked against the allowed hosts
+    fetch_client: reqwest::Client,
+    max_image_bytes: u64,
+    cache: Arc<DiskCache>,
+    allowed_hosts: Arc<Vec<String>>,
+}
+
+impl ImageProxy {
+    pub async fn new(client: reqwest::Client) -> std::io::Result<Self> {
+        let dir = env::var("IMAGE_CACHE_DIR").unwrap_or_else(|_| "image_cache".to_string());
+        let max_mb: u64 = env::var("IMAGE_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1024);
+        let max_image_mb: u64 = env::var("IMAGE_PROXY_MAX_IMAGE_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_IMAGE_MB);
+        let allowed_hosts = env::var("IMAGE_PROXY_ALLOWED_HOSTS")
+            .unwrap_or_else(|_| DEFAULT_ALLOWED_HOSTS.to_string())
+            .split(',')
+            .map(|h| h.trim().to_lowercase())
+            .filter(|h| !h.is_empty())
+            .collect();
+
+        let cache = DiskCache::open(PathBuf::from(dir), max_mb * 1024 * 1024).await?;
+
+        Ok(Self {
+            client,
+            fetch_client: crate::api::upstream_client_without_redirects(),
+            max_image_bytes: max_image_mb * 1024 * 1024,
+            cache: Arc::new(cache),
+            allowed_hosts: Arc::new(allowed_hosts),
+        })
+    }
+
+    /// Only fetch over http(s) from configured hosts or their subdomains
+    fn check_url(&self, url: &str) -> Result<(), ProxyError> {
+        let parsed = reqwest::Url::parse(url).map_err(|_| ProxyError::InvalidUrl)?;
+        if parsed.scheme() != "https" && parsed.scheme() != "http" {
+            return Err(ProxyError::InvalidUrl);
+        }
+        let host = parsed.host_str().ok_or(ProxyError::InvalidUrl)?.to_lowercase();
+        let allowed = self
+            .allowed_hosts
+            .iter()
+            .any(|h| host == *h || host.ends_with(&format!(".{}", h)));
+        if allowed {
+            Ok(())
+        } else {
+            Err(ProxyError::ForbiddenHost(host))
+        }
+    }
+
+    /// Get the original image, from disk when possible
+    pub async fn fetch_original(&self, url: &str) -> Result<Vec<u8>, ProxyError> {
+        self.check_url(url)?;
+        let key = cache_key(url, &Variant::ORIGINAL);
+        if let Some(bytes) = self.cache.get(&key).await {
+            return Ok(bytes);
+        }
+
+        let started = std::time::Instant::now();
+        let result = async {
+            let mut response = self
+                .fetch_client
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| (ProxyError::Upstream(e.to_string()), false))?;
+            let cached = is_cache_hit(response.headers());
+            if !response.status().is_success() {
+                return Err((ProxyError::Upstream(format!("HTTP {}", response.status())), cached));
+            }
+            let too_large = (ProxyError::TooLarge(self.max_image_bytes), cached);
+            if response.content_length().is_some_and(|len| len > self.max_image_bytes) {
+                return Err(too_large);
+            }
+            // Content-Length may be missing or wrong, so the body is capped too
+            let mut bytes = Vec::new();
+            while let Some(chunk) = response
+                .chunk()
+                .await
+                .map_err(|e| (ProxyError::Upstream(e.to_string()), cached))?
+            {
+                if (bytes.len() + chunk.len()) as u64 > self.max_image_bytes {
+                    return Err(too_large);
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            if detect_image_kind(&bytes).is_none() {
+                return Err((ProxyError::NotAnImage, cached));
+            }
+            Ok((bytes, cached))
+        }
+        .await;
+
+        if should_report(url) {
+            let (success, bytes, cached) = match &result {
+                Ok((bytes, cached)) => (true, bytes.len() as u64, *cached),
+                Err((_, cached)) => (false, 0, *cached),
+            };
+            spawn_at_home_report(&self.client, AtHomeReport {
+                url: url.to_string(),
+                success,
+                bytes,
+                duration: started.elapsed().as_millis() as u64,
+                cached,
+            });
+        }
+
+        let (bytes, _) = result.map_err(|(e, _)| e)?;
+        self.cache.put(&key, &bytes).await;
+        Ok(bytes)
+    }
+
+    /// Get an image rendition and its content type
+    pub async fn get(&self, url: &str, variant: Variant) -> Result<(Vec<u8>, &'static str), ProxyError> {
+        if variant.is_original() {
+            let bytes = self.fetch_original(url).await?;
+            let kind = detect_image_kind(&bytes).ok_or(ProxyError::NotAnImage)?;
+            return Ok((bytes, content_type(kind)));
+        }
+
+        self.check_url(url)?;
+        let key = cache_key(url, &variant);
+        let cached = self.cache.get(&key).await;
+        if let Some((bytes, kind)) = cached.and_then(|b| detect_image_kind(&b).map(|k| (b, k))) {
+            return Ok((bytes, content_type(kind)));
+        }
+
+        let original = self.fetch_original(url).await?;
+        let bytes = tokio::task::spawn_blocking(move || transform(&original, variant))
+            .await
+            .map_err(|e| ProxyError::Transform(e.to_string()))??;
+        let kind = detect_image_kind(&bytes).ok_or(ProxyError::NotAnImage)?;
+        self.cache.put(&key, &bytes).await;
+        Ok((bytes, content_type(kind)))
+    }
+}
+
+/// Resize (never upscaling) and re-encode an image. Images that would come
+/// out the same, and animated GIFs, are passed through untouched.
+pub fn transform(original: &[u8], variant: Variant) -> Result<Vec<u8>, ProxyError> {
+    let kind = detect_image_kind(original).ok_or(ProxyError::NotAnImage)?;
+    if kind == ImageKind::Gif && is_animated_gif(original) {
+        return Ok(original.to_vec());
+    }
+    if variant.format == TargetFormat::Original {
+        let width = image::ImageReader::new(std::io::Cursor::new(original))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .map(|(width, _)| width);
+        if variant.width.is_none_or(|w| width.is_some_and(|width| w >= width)) {
+            return Ok(original.to_vec());
+        }
+    }
+
+    let mut img = image::load_from_memory(original).map_err(|e| ProxyError::Transform(e.to_string()))?;
+
+    if let Some(width) = variant.width.filter(|w| *w < img.width()) {
+        let height = ((img.height() as u64 * width as u64) / img.width() as u64).max(1) as u32;
+        img = img.resize_exact(width, height, image::imageops::FilterType::Triangle);
+    }
+
+    let mut out = std::io::Cursor::new(Vec::new());
+    let result = match (variant.format, kind) {
+        (TargetFormat::WebP, _) | (TargetFormat::Original, ImageKind::WebP) => return encode_webp(&img),
+        (TargetFormat::Original, ImageKind::Png) => img.write_to(&mut out, image::ImageFormat::Png),
+        (TargetFormat::Original, ImageKind::Gif) => img.write_to(&mut out, image::ImageFormat::Gif),
+        (TargetFormat::Original, ImageKind::Jpeg) => {
+            image::DynamicImage::ImageRgb8(img.to_rgb8()).write_to(&mut out, image::ImageFormat::Jpeg)
+        }
+    };
+    result.map_err(|e| ProxyError::Transform(e.to_string()))?;
+    Ok(out.into_inner())
+}
+
+fn is_animated_gif(bytes: &[u8]) -> bool {
+    use image::AnimationDecoder;
+
+    image::codecs::gif::GifDecoder::new(std::io::Cursor::new(bytes))
+        .is_ok_and(|decoder| decoder.into_frames().take(2).count() > 1)
+}
+
+/// Lossy WebP; the image crate can only write lossless WebP, which is often
+/// larger than the JPEG it was made from
+fn encode_webp(img: &image::DynamicImage) -> Result<Vec<u8>, ProxyError> {
+    let (width, height) = (img.width(), img.height());
+    let encoded = if img.color().has_alpha() {
+        webp::Encoder::from_rgba(&img.to_rgba8(), width, height).encode_simple(false, WEBP_QUALITY)
+    } else {
+        webp::Encoder::from_rgb(&img.to_rgb8(), width, height).encode_simple(false, WEBP_QUALITY)
+    };
+    encoded
+        .map(|webp| webp.to_vec())
+        .map_err(|e| ProxyError::Transform(format!("WebP encoding failed: {:?}", e)))
+}
+
+#[derive(Deserialize)]
+pub struct ImageQuery {
+    pub url: String,
+    pub w: Option<u32>,
+    pub format: Option<String>, // original, webp or auto (WebP when the client accepts it)
+}
+
+/// Image proxy: GET /api/image?url=...&w=480&format=webp
+pub async fn image_proxy_handler(
+    State(proxy): State<ImageProxy>,
+    headers: HeaderMap,
+    Query(params): Query<ImageQuery>,
+) -> Response {
+    let accepts_webp = headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("image/webp"))
+        .unwrap_or(false);
+    let format = match params.format.as_deref() {
+        Some("webp") => TargetFormat::WebP,
+        Some("auto") if accepts_webp => TargetFormat::WebP,
+        _ => TargetFormat::Original,
+    };
+    let variant = Variant {
+        width: params.w.and_then(width_bucket),
+        format,
+    };
+
+    match proxy.get(&params.url, variant).await {
+        Ok((bytes, content_type)) => {
+            let etag = format!("\"{}\"", &sha256_hex(&bytes)[..32]);
+            let not_modified = headers
+                .get(IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+                .unwrap_or(false);
+
+            let mut response = if not_modified {
+                StatusCode::NOT_MODIFIED.into_response()
+            } else {
+                (StatusCode::OK, [(CONTENT_TYPE, content_type)], bytes).into_response()
+            };
+            let response_headers = response.headers_mut();
+            response_headers.insert(CACHE_CONTROL, HeaderValue::from_static(IMMUTABLE_CACHE_CONTROL));
+            if let Ok(value) = HeaderValue::from_str(&etag) {
+                response_headers.insert(ETAG, value);
+            }
+            if params.format.as_deref() == Some("auto") {
+                response_headers.insert("vary", HeaderValue::from_static("Accept"));
+            }
+            response
+        }
+        Err(e) => {
+            tracing::warn!("Image proxy failed for {}: {}", params.url, e);
+            (
+                e.status(),
+                axum::Json(crate::api::ApiError {
+                    error: "Failed to load image".to_string(),
+                    message: Some(e.to_string()),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(width, height)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_width_bucket() {
+        assert_eq!(width_bucket(100), Some(160));
+        assert_eq!(width_bucket(480), Some(480));
+        assert_eq!(width_bucket(481), Some(720));
+        assert_eq!(width_bucket(5000), None);
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut index = LruIndex::default();
+        assert!(index.insert("a", 40, 100).is_empty());
+        assert!(index.insert("b", 40, 100).is_empty());
+        assert!(index.touch("a"));
+        assert_eq!(index.insert("c", 40, 100), vec!["b".to_string()]);
+        assert_eq!(index.total, 80);
+        assert!(!index.touch("b"));
+    }
+
+    #[test]
+    fn test_lru_keeps_oversized_latest_entry() {
+        let mut index = LruIndex::default();
+        index.insert("a", 10, 100);
+        assert_eq!(index.insert("big", 500, 100), vec!["a".to_string()]);
+        assert!(index.touch("big"));
+    }
+
+    #[test]
+    fn test_transform_resizes_and_converts() {
+        let original = png(800, 400);
+        let out = transform(
+            &original,
+            Variant {
+                width: Some(320),
+                format: TargetFormat::WebP,
+            },
+        )
+        .unwrap();
+        assert_eq!(detect_image_kind(&out), Some(ImageKind::WebP));
+        let img = image::load_from_memory(&out).unwrap();
+        assert_eq!((img.width(), img.height()), (320, 160));
+    }
+
+    #[test]
+    fn test_webp_rendition_is_smaller_than_jpeg_source() {
+        let page = image::RgbImage::from_fn(1000, 1400, |x, y| {
+            let shade = ((x / 7 + y / 5) % 64) as u8 * 3;
+            image::Rgb([shade, shade, 200 - shade / 2])
+        });
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgb8(page)
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+        let jpeg = jpeg.into_inner();
+
+        let out = transform(
+            &jpeg,
+            Variant {
+                width: None,
+                format: TargetFormat::WebP,
+            },
+        )
+        .unwrap();
+        assert_eq!(detect_image_kind(&out), Some(ImageKind::WebP));
+        assert!(out.len() < jpeg.len(), "{} >= {}", out.len(), jpeg.len());
+    }
+
+    #[test]
+    fn test_transform_never_upscales() {
+        let out = transform(
+            &png(100, 50),
+            Variant {
+                width: Some(320),
+                format: TargetFormat::Original,
+            },
+        )
+        .unwrap();
+        let img = image::load_from_memory(&out).unwrap();
+        assert_eq!(img.width(), 100);
+    }
+
+    #[test]
+    fn test_transform_passes_through_unchanged_and_animated_images() {
+        let original = png(100, 50);
+        let same = Variant {
+            width: Some(320),
+            format: TargetFormat::Original,
+        };
+        assert_eq!(transform(&original, same).unwrap(), original);
+
+        let mut gif = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut gif);
+            let frames = [0u8, 255].map(|shade| {
+                image::Frame::new(image::RgbaImage::from_pixel(200, 100, image::Rgba([shade, 0, 0, 255])))
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+        let smaller = Variant {
+            width: Some(160),
+            format: TargetFormat::WebP,
+        };
+        assert_eq!(transform(&gif, smaller).unwrap(), gif);
+    }
+
+    #[test]
+    fn test_cache_key_depends_on_variant() {
+        let url = "https://uploads.mangadex.org/covers/x/y.jpg";
+        assert_ne!(
+            cache_key(url, &Variant::ORIGINAL),
+            cache_key(
+                url,
+                &Variant {
+                    width: Some(320),
+                    format: TargetFormat::Original
+                }
+            )
+        );
+    }
+}
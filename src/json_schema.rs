@@ -0,0 +1,130 @@
+use serde_json::Value;
+
+/// Check `value` against the subset of JSON Schema used for LLM output:
+/// `type`, `enum`, `properties`, `required`, `additionalProperties: false`,
+/// `items`, `minItems`/`maxItems` and `minimum`/`maximum`. Returns one
+/// message per violation, each prefixed with the path to the bad value.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "$", &mut errors);
+    errors
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+            errors.push(format!("{}: expected {}", path, types.join(" or ")));
+            // Nested keywords would only repeat the same mistake
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        errors.push(format!("{}: must be one of {}", path, Value::Array(allowed.clone())));
+    }
+
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(Value::as_f64).filter(|min| n < *min) {
+            errors.push(format!("{}: must be at least {}", path, min));
+        }
+        if let Some(max) = schema.get("maximum").and_then(Value::as_f64).filter(|max| n > *max) {
+            errors.push(format!("{}: must be at most {}", path, max));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for key in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+            if let Some(key) = key.as_str().filter(|key| !object.contains_key(*key)) {
+                errors.push(format!("{}: missing required field \"{}\"", path, key));
+            }
+        }
+        for (key, field) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(field_schema) => check(field_schema, field, &format!("{}.{}", path, key), errors),
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{}: unexpected field \"{}\"", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|min| (items.len() as u64) < *min) {
+            errors.push(format!("{}: needs at least {} items", path, min));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|max| items.len() as u64 > *max) {
+            errors.push(format!("{}: allows at most {} items", path, max));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, &format!("{}[{}]", path, i), errors);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_reports_each_violation_with_its_path() {
+        let schema = json!({
+            "type": "object",
+            "required": ["query", "confidence"],
+            "additionalProperties": false,
+            "properties": {
+                "query": { "type": "string" },
+                "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                "status": { "type": ["string", "null"], "enum": ["ongoing", "completed", null] },
+                "tags": { "type": "array", "maxItems": 2, "items": { "type": "string" } }
+            }
+        });
+
+        let valid = json!({ "query": "isekai", "confidence": 0.8, "status": null, "tags": ["a"] });
+        assert!(validate(&schema, &valid).is_empty());
+
+        let invalid = json!({ "confidence": 1.5, "status": "paused", "tags": ["a", 2, "c"], "extra": true });
+        let mut errors = validate(&schema, &invalid);
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                "$.confidence: must be at most 1",
+                "$.status: must be one of [\"ongoing\",\"completed\",null]",
+                "$.tags: allows at most 2 items",
+                "$.tags[1]: expected string",
+                "$: missing required field \"query\"",
+                "$: unexpected field \"extra\"",
+            ]
+        );
+        assert_eq!(validate(&schema, &json!("text")), vec!["$: expected object"]);
+    }
+}
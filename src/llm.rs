@@ -0,0 +1,391 @@
+use crate::cache::{cache_keys, CacheService};
+use crate::json_schema;
+use axum::async_trait;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    #[error("LLM is not configured: {0}")]
+    Config(String),
+    #[error("LLM request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("LLM request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("LLM API error: {0}")]
+    Api(String),
+    #[error("LLM output did not match the expected format: {0}")]
+    InvalidOutput(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: "system".to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// A chat-completion backend
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    /// Stable name of the endpoint's model, part of every cache key
+    fn model_id(&self) -> String;
+
+    /// Where requests go, so the same model name on two servers is cached apart
+    fn endpoint(&self) -> &str;
+
+    /// The assistant's reply to `messages`
+    async fn chat(&self, messages: &[ChatMessage], max_tokens: u32, temperature: f32) -> Result<String, LlmError>;
+}
+
+/// Any server exposing OpenAI's `/chat/completions` API: OpenAI itself,
+/// llama.cpp, vLLM, LM Studio, Ollama's `/v1` and so on
+pub struct OpenAiCompatibleChat {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiCompatibleChat {
+    pub fn new(base_url: &str, api_key: Option<String>, model: String) -> Result<Self, LlmError> {
+        let client = Client::builder().user_agent("MangaViewer-AI/1.0").build()?;
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OpenAiCompatibleChat {
+    fn model_id(&self) -> String {
+        self.model.clone()
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], max_tokens: u32, temperature: f32) -> Result<String, LlmError> {
+        #[derive(Serialize)]
+        struct ChatRequest<'a> {
+            model: &'a str,
+            messages: &'a [ChatMessage],
+            max_tokens: u32,
+            temperature: f32,
+        }
+        #[derive(Deserialize)]
+        struct ChatResponse {
+            choices: Vec<ChatChoice>,
+        }
+        #[derive(Deserialize)]
+        struct ChatChoice {
+            message: ChatMessage,
+        }
+
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&ChatRequest {
+                model: &self.model,
+                messages,
+                max_tokens,
+                temperature,
+            });
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::Api(format!("{}: {}", status, message)));
+        }
+        let response: ChatResponse = response.json().await?;
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or_else(|| LlmError::Api("no choices returned".to_string()))
+    }
+}
+
+/// The first JSON object or array in a reply, which models often wrap in
+/// prose or a code fence
+pub fn extract_json(reply: &str) -> Option<Value> {
+    let reply = reply.trim();
+    if let Ok(value) = serde_json::from_str(reply) {
+        return Some(value);
+    }
+    reply
+        .char_indices()
+        .filter(|(_, c)| *c == '{' || *c == '[')
+        .find_map(|(start, _)| {
+            // Parses one value and ignores whatever follows it
+            serde_json::Deserializer::from_str(&reply[start..])
+                .into_iter::<Value>()
+                .next()
+                .and_then(Result::ok)
+                .filter(|value| value.is_object() || value.is_array())
+        })
+}
+
+/// Parse a reply and check it against `schema`, describing what is wrong
+/// in a way the model can act on
+fn parse_reply(reply: &str, schema: &Value) -> Result<Value, String> {
+    let value = extract_json(reply).ok_or_else(|| "the reply contained no JSON".to_string())?;
+    let errors = json_schema::validate(schema, &value);
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// Chat completions with timeouts, response caching and schema-checked
+/// structured output
+#[derive(Clone)]
+pub struct LlmClient {
+    provider: Arc<dyn ChatProvider>,
+    cache: Option<CacheService>,
+    timeout: Duration,
+    cache_ttl_secs: u64,
+}
+
+impl LlmClient {
+    pub fn new(provider: Arc<dyn ChatProvider>, cache: Option<CacheService>) -> Self {
+        let timeout_secs = env::var("LLM_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        let cache_ttl_secs = env::var("LLM_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(604800);
+        Self {
+            provider,
+            cache,
+            timeout: Duration::from_secs(timeout_secs),
+            cache_ttl_secs,
+        }
+    }
+
+    /// An OpenAI-compatible endpoint from `LLM_API_URL`, `LLM_API_KEY` and
+    /// `LLM_MODEL`. Configured when there is a key or an explicit URL (local
+    /// servers usually need no key).
+    pub fn from_env(cache: Option<CacheService>) -> Result<Self, LlmError> {
+        let base_url = env::var("LLM_API_URL").ok();
+        let api_key = env::var("LLM_API_KEY")
+            .or_else(|_| env::var("OPENAI_API_KEY"))
+            .ok()
+            .or_else(read_api_key_file)
+            .filter(|key| !key.trim().is_empty());
+        if base_url.is_none() && api_key.is_none() {
+            return Err(LlmError::Config("set LLM_API_URL or LLM_API_KEY".to_string()));
+        }
+        let model = env::var("LLM_MODEL").unwrap_or_else(|_| "gpt-4o-mini".to_string());
+        let provider = OpenAiCompatibleChat::new(
+            base_url.as_deref().unwrap_or("https://api.openai.com/v1"),
+            api_key,
+            model,
+        )?;
+        Ok(Self::new(Arc::new(provider), cache))
+    }
+
+    pub fn model_id(&self) -> String {
+        self.provider.model_id()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], max_tokens: u32, temperature: f32) -> Result<String, LlmError> {
+        tokio::time::timeout(self.timeout, self.provider.chat(messages, max_tokens, temperature))
+            .await
+            .map_err(|_| LlmError::Timeout(self.timeout))?
+    }
+
+    fn cache_key(&self, kind: &str, messages: &[ChatMessage], schema: Option<&Value>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.provider.endpoint());
+        hasher.update([0]);
+        hasher.update(self.model_id());
+        hasher.update([0]);
+        hasher.update(kind);
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(messages).unwrap_or_default());
+        if let Some(schema) = schema {
+            hasher.update(schema.to_string());
+        }
+        cache_keys::llm_response(&format!("{:x}", hasher.finalize()))
+    }
+
+    async fn cached<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.cache.as_ref()?.get(key).await.ok().flatten()
+    }
+
+    async fn store<T: Serialize>(&self, key: &str, value: &T) {
+        if let Some(cache) = &self.cache {
+            let _ = cache.set(key, value, self.cache_ttl_secs).await;
+        }
+    }
+
+    /// A reply matching `schema`, deserialized into `T`. A reply that is not
+    /// valid JSON or breaks the schema is sent back once with the problems
+    /// listed before giving up. Only valid results are cached.
+    pub async fn complete_json<T: DeserializeOwned>(
+        &self,
+        messages: &[ChatMessage],
+        schema: &Value,
+        max_tokens: u32,
+    ) -> Result<T, LlmError> {
+        let key = self.cache_key("json", messages, Some(schema));
+        if let Some(value) = self.cached::<Value>(&key).await
+            && let Ok(result) = serde_json::from_value(value)
+        {
+            return Ok(result);
+        }
+
+        let mut conversation = messages.to_vec();
+        conversation.push(ChatMessage::user(format!(
+            "Respond with only a JSON value matching this JSON Schema, without any other text:\n{}",
+            schema
+        )));
+        let reply = self.chat(&conversation, max_tokens, 0.0).await?;
+        let value = match parse_reply(&reply, schema) {
+            Ok(value) => value,
+            Err(problems) => {
+                tracing::debug!("LLM reply rejected ({}), asking for a repair", problems);
+                conversation.push(ChatMessage::assistant(reply));
+                conversation.push(ChatMessage::user(format!(
+                    "That reply was not valid: {}. Reply again with only the corrected JSON.",
+                    problems
+                )));
+                let repaired = self.chat(&conversation, max_tokens, 0.0).await?;
+                parse_reply(&repaired, schema).map_err(LlmError::InvalidOutput)?
+            }
+        };
+
+        let result = serde_json::from_value(value.clone()).map_err(|e| LlmError::InvalidOutput(e.to_string()))?;
+        self.store(&key, &value).await;
+        Ok(result)
+    }
+}
+
+/// Legacy key file: the key sits on the second line of `.openai_key`
+fn read_api_key_file() -> Option<String> {
+    let content = fs::read_to_string(".openai_key").ok()?;
+    content.lines().nth(1).map(|line| line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Replays canned replies and records every conversation it was sent
+    struct Scripted {
+        replies: Mutex<Vec<&'static str>>,
+        seen: Mutex<Vec<Vec<ChatMessage>>>,
+    }
+
+    #[async_trait]
+    impl ChatProvider for Scripted {
+        fn model_id(&self) -> String {
+            "scripted".to_string()
+        }
+
+        fn endpoint(&self) -> &str {
+            "scripted"
+        }
+
+        async fn chat(&self, messages: &[ChatMessage], _: u32, _: f32) -> Result<String, LlmError> {
+            self.seen.lock().unwrap().push(messages.to_vec());
+            Ok(self.replies.lock().unwrap().remove(0).to_string())
+        }
+    }
+
+    fn client(replies: Vec<&'static str>) -> (LlmClient, Arc<Scripted>) {
+        let provider = Arc::new(Scripted {
+            replies: Mutex::new(replies),
+            seen: Mutex::new(Vec::new()),
+        });
+        (LlmClient::new(provider.clone(), None), provider)
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Keywords {
+        keywords: Vec<String>,
+    }
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["keywords"],
+            "properties": { "keywords": { "type": "array", "items": { "type": "string" } } }
+        })
+    }
+
+    #[test]
+    fn test_extract_json_from_prose_and_fences() {
+        assert_eq!(extract_json(" {\"a\": 1} "), Some(json!({ "a": 1 })));
+        assert_eq!(
+            extract_json("Sure! Here you go:\n```json\n{\"a\": [1, 2]}\n```\nEnjoy [reading]."),
+            Some(json!({ "a": [1, 2] }))
+        );
+        assert_eq!(extract_json("See [note] then {\"b\": true}"), Some(json!({ "b": true })));
+        assert_eq!(extract_json("no json here"), None);
+    }
+
+    #[tokio::test]
+    async fn test_complete_json_repairs_once_then_fails() {
+        let (llm, provider) = client(vec![
+            "Here: {\"keywords\": \"dark fantasy\"}",
+            "{\"keywords\": [\"dark fantasy\"]}",
+        ]);
+        let parsed: Keywords = llm.complete_json(&[ChatMessage::user("q")], &schema(), 100).await.unwrap();
+        assert_eq!(parsed.keywords, vec!["dark fantasy"]);
+        let seen = provider.seen.lock().unwrap().clone();
+        let repair = &seen[1];
+        assert_eq!(repair[repair.len() - 2].role, "assistant");
+        assert!(repair.last().unwrap().content.contains("$.keywords: expected array"));
+
+        let (llm, _) = client(vec!["nope", "still nope"]);
+        let result = llm.complete_json::<Keywords>(&[ChatMessage::user("q")], &schema(), 100).await;
+        assert!(matches!(result, Err(LlmError::InvalidOutput(_))));
+    }
+}
@@ -5,15 +5,14 @@ use axum::{
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
 use dotenv::dotenv;
 use reqwest;
 use serde::Deserialize;
 use serde_json;
-use std::fs;
 use std::net::SocketAddr;
-use std::path::Path as StdPath;
+use std::path::PathBuf;
 use tokio::net::TcpListener;
 use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
@@ -23,34 +22,75 @@ use utoipa::OpenApi;
 mod ai_service;
 mod api;
 mod auth_mongodb;
+mod auto_download;
 mod cache;
 mod cached_api;
+mod catalog_stats;
+mod content_filter;
+mod dedup;
+mod downloader;
+mod embedding;
+mod embedding_worker;
+mod federated_search;
+mod hybrid_search;
 mod handlers;
+mod image_proxy;
+mod json_schema;
+mod llm;
 mod manga_service;
 mod pagination;
+mod prefetch;
 mod progress;
+mod query_parser;
+mod recommendations;
+mod saved_search;
 mod search;
+mod search_index;
+mod similar;
+mod summaries;
 mod validation;
+mod vector_index;
 
-use ai_service::AIService;
-use auth_mongodb::{login_handler, logout_handler, register_handler, profile_handler, update_profile_handler, AuthService};
+use auto_download::AutoDownloadService;
+use auth_mongodb::{list_users_handler, login_handler, logout_handler, register_handler, profile_handler, update_profile_handler, AuthService};
 use cache::CacheService;
 use handlers::{
     add_to_library_handler, advanced_search_handler, autocomplete_handler,
     get_library_handler, get_reading_stats_handler, update_progress_handler,
-    update_status_handler, remove_from_library_handler,
+    update_status_handler, remove_from_library_handler, set_rating_handler,
     add_bookmark_handler, get_bookmarks_handler, delete_bookmark_handler,
-    add_history_handler, get_history_handler, get_continue_reading_handler,
+    add_history_handler, get_history_handler, get_continue_reading_handler, recommendations_handler,
     toggle_favorite_handler, get_favorites_handler,
+    set_auto_download_handler, get_auto_downloads_handler,
+    federated_search_handler, get_content_policy_handler, set_content_policy_handler, get_embedding_progress_handler, run_embeddings_handler, save_search_handler, list_saved_searches_handler, delete_saved_search_handler,
+    run_saved_search_handler, search_notifications_handler, mark_notifications_read_handler, similar_manga_handler, parse_search_handler,
+    import_manga_handler,
+    manga_summary_handler, run_summaries_handler, list_tag_proposals_handler, review_tag_proposal_handler,
+    list_duplicates_handler, run_dedup_handler, merge_duplicate_handler, dismiss_duplicate_handler,
     AppState,
 };
 use manga_service::{
     get_manga_handler, list_manga_handler, save_manga_handler, search_manga_handler,
-    MangaService,
+    semantic_search_handler, MangaService, SemanticSearchState,
 };
+use embedding_worker::EmbeddingWorker;
+use federated_search::FederatedSearchService;
 use progress::ProgressService;
+use recommendations::RecommendationService;
+use saved_search::SavedSearchService;
 use search::SearchService;
+use similar::SimilarService;
+use ai_service::AIService;
+use llm::LlmClient;
+use query_parser::QueryParser;
+use summaries::SummaryService;
+use dedup::DedupService;
 use cached_api::CachedMangaDexClient;
+use catalog_stats::CatalogStatsService;
+use content_filter::{ContentFilter, ContentPolicyService};
+use downloader::{ChapterDownloadRequest, DownloadError, Downloader, Quality};
+use image_proxy::{image_proxy_handler, ImageProxy};
+use prefetch::{at_home_handler, Prefetcher};
 
 // --- HEALTH ENDPOINT ---
 async fn health_handler(State(manga_service): State<MangaService>) -> impl IntoResponse {
@@ -70,37 +110,6 @@ async fn health_handler(State(manga_service): State<MangaService>) -> impl IntoR
     (StatusCode::OK, serde_json::to_string(&response).unwrap())
 }
 
-// --- ADMIN ENDPOINT ---
-// Temporarily disabled - needs state refactoring
-/*
-async fn update_embeddings_admin_handler(
-    State(manga_service): State<MangaService>,
-    State(ai_service): State<AIService>,
-) -> impl IntoResponse {
-    let model = std::env::var("OLLAMA_MODEL").unwrap_or_else(|_| "llama2".to_string());
-    match manga_service
-        .update_all_manga_embeddings(&ai_service, &model)
-        .await
-    {
-        Ok(_) => (
-            StatusCode::OK,
-            serde_json::to_string(&serde_json::json!({
-                "message": "Embeddings updated for all manga"
-            }))
-            .unwrap(),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            serde_json::to_string(&crate::api::ApiError {
-                error: "Failed to update embeddings".to_string(),
-                message: Some(e.to_string()),
-            })
-            .unwrap(),
-        ),
-    }
-}
-*/
-
 #[derive(Deserialize)]
 struct MangaQuery {
     title: Option<String>,
@@ -132,6 +141,7 @@ struct DownloadFilesQuery {
 
 async fn manga_handler(
     State(cached_client): State<CachedMangaDexClient>,
+    content: ContentFilter,
     Query(params): Query<MangaQuery>,
 ) -> impl IntoResponse {
     // Use cached client for search
@@ -139,7 +149,7 @@ async fn manga_handler(
         let limit = params.limit.or(Some(100));
         let offset = params.offset;
         
-        match cached_client.search_manga(&title, limit, offset).await {
+        match cached_client.search_manga(&title, limit, offset, &content).await {
             Ok(response) => {
                 let json = serde_json::to_string(&response).unwrap_or_else(|_| "{}".to_string());
                 (StatusCode::OK, [(CONTENT_TYPE, "application/json")], json)
@@ -172,6 +182,7 @@ async fn manga_handler(
         if !query_parts.is_empty() {
             url.push_str(&format!("?{}", query_parts.join("&")));
         }
+        url.push_str(&content.mangadex_query());
 
         let client = reqwest::Client::new();
 
@@ -208,14 +219,18 @@ async fn manga_handler(
 
 async fn chapters_handler(
     State(cached_client): State<CachedMangaDexClient>,
+    content: ContentFilter,
     Path(manga_id): Path<String>,
     Query(params): Query<ChapterQuery>,
 ) -> impl IntoResponse {
-    // Accept either lang or translatedLanguage[] parameter
-    let language = params.translated_language.or(params.lang).unwrap_or_else(|| "en".to_string());
+    // Accept either lang or translatedLanguage[] parameter, falling back to
+    // the reader's preferred language; both are limited by the content policy
+    let language = content.chapter_language(params.translated_language.or(params.lang));
+    // Cached feeds are per language and content filter
+    let feed_key = format!("{}:{}", language, content.cache_key());
 
     // Try cache first
-    if let Ok(Some(cached)) = cached_client.get_cached_chapters(&manga_id, &language).await {
+    if let Ok(Some(cached)) = cached_client.get_cached_chapters(&manga_id, &feed_key).await {
         tracing::info!("✅ Returning cached chapters for manga {}", manga_id);
         return (
             StatusCode::OK,
@@ -243,6 +258,9 @@ async fn chapters_handler(
     if !query_params.is_empty() {
         url.push_str(&format!("?{}", query_params.join("&")));
     }
+    for rating in &content.ratings {
+        url.push_str(&format!("&contentRating[]={}", rating.as_str()));
+    }
 
     let client = reqwest::Client::new();
 
@@ -256,7 +274,7 @@ async fn chapters_handler(
             Ok(text) => {
                 // Cache the response
                 if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&text) {
-                    if let Err(e) = cached_client.cache_chapters(&manga_id, &language, &json_value).await {
+                    if let Err(e) = cached_client.cache_chapters(&manga_id, &feed_key, &json_value).await {
                         tracing::warn!("Failed to cache chapters: {}", e);
                     }
                 }
@@ -323,166 +341,73 @@ async fn download_handler(Query(params): Query<DownloadQuery>) -> impl IntoRespo
     }
 }
 
-async fn download_files_handler(Query(params): Query<DownloadFilesQuery>) -> impl IntoResponse {
-    let client = reqwest::Client::new();
+async fn download_files_handler(
+    State(downloader): State<Downloader>,
+    Query(params): Query<DownloadFilesQuery>,
+) -> impl IntoResponse {
+    let request = ChapterDownloadRequest {
+        chapter_id: params.chapter_id,
+        save_root: PathBuf::from(params.save_path),
+        quality: Quality::from_param(params.quality.as_deref()),
+        manga_title: params.manga_title,
+        chapter_title: params.chapter_title,
+    };
 
-    let server_url = format!(
-        "https://api.mangadex.org/at-home/server/{}",
-        params.chapter_id
-    );
+    match downloader.download_chapter(&request).await {
+        Ok(report) => {
+            let response = serde_json::json!({
+                "success": true,
+                "message": format!(
+                    "Downloaded {} of {} pages ({} already on disk)",
+                    report.downloaded_files.len(),
+                    report.total_pages,
+                    report.skipped_files.len()
+                ),
+                "save_path": report.save_path,
+                "downloaded_files": report.downloaded_files,
+                "skipped_files": report.skipped_files,
+                "failed_downloads": report.failed_downloads,
+                "total_pages": report.total_pages,
+                "successful_downloads": report.downloaded_files.len() + report.skipped_files.len(),
+                "bytes_downloaded": report.bytes_downloaded,
+                "complete": report.complete
+            });
 
-    let download_response = match client
-        .get(&server_url)
-        .header("User-Agent", "mangadownloader/0.1 (ravin.bhakta@gmail.com)")
-        .send()
-        .await
-    {
-        Ok(resp) => match resp.json::<serde_json::Value>().await {
-            Ok(json) => json,
-            Err(_) => {
-                return (
+            (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "application/json")],
+                response.to_string(),
+            )
+        }
+        Err(e) => {
+            let (status, error) = match &e {
+                DownloadError::Upstream(_) => (
+                    StatusCode::BAD_GATEWAY,
+                    "Failed to fetch download info from MangaDex",
+                ),
+                DownloadError::InvalidResponse(_) => (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    [(CONTENT_TYPE, "application/json")],
-                    serde_json::to_string(&crate::api::ApiError {
-                        error: "Failed to parse download info".to_string(),
-                        message: None,
-                    })
-                    .unwrap(),
-                )
-            }
-        },
-        Err(_) => {
-            return (
-                StatusCode::BAD_GATEWAY,
+                    "Failed to parse download info",
+                ),
+                DownloadError::NoImages => {
+                    (StatusCode::NOT_FOUND, "No images found for this chapter")
+                }
+                DownloadError::Io(_) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to create save directory",
+                ),
+            };
+            (
+                status,
                 [(CONTENT_TYPE, "application/json")],
                 serde_json::to_string(&crate::api::ApiError {
-                    error: "Failed to fetch download info from MangaDex".to_string(),
-                    message: None,
+                    error: error.to_string(),
+                    message: Some(e.to_string()),
                 })
                 .unwrap(),
             )
         }
-    };
-
-    let base_url = download_response["baseUrl"].as_str().unwrap_or("");
-    let chapter_hash = download_response["chapter"]["hash"].as_str().unwrap_or("");
-
-    let quality = params.quality.as_deref().unwrap_or("high");
-    let images_key = if quality == "saver" {
-        "dataSaver"
-    } else {
-        "data"
-    };
-    let url_path = if quality == "saver" {
-        "data-saver"
-    } else {
-        "data"
-    };
-
-    let empty_vec = vec![];
-    let images = download_response["chapter"][images_key]
-        .as_array()
-        .unwrap_or(&empty_vec);
-
-    if images.is_empty() {
-        return (
-            StatusCode::NOT_FOUND,
-            [(CONTENT_TYPE, "application/json")],
-            serde_json::to_string(&crate::api::ApiError {
-                error: "No images found for this chapter".to_string(),
-                message: None,
-            })
-            .unwrap(),
-        );
     }
-
-    let manga_title = params.manga_title.as_deref().unwrap_or("Unknown_Manga");
-    let chapter_title = params
-        .chapter_title
-        .as_deref()
-        .unwrap_or(&params.chapter_id);
-    let safe_manga_title = manga_title.replace("/", "_").replace("\\", "_");
-    let safe_chapter_title = chapter_title.replace("/", "_").replace("\\", "_");
-
-    let full_save_path = format!(
-        "{}/{}/{}",
-        params.save_path, safe_manga_title, safe_chapter_title
-    );
-
-    if let Err(_) = fs::create_dir_all(&full_save_path) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            [(CONTENT_TYPE, "application/json")],
-            serde_json::to_string(&crate::api::ApiError {
-                error: "Failed to create save directory".to_string(),
-                message: None,
-            })
-            .unwrap(),
-        );
-    }
-
-    let mut downloaded_files = Vec::new();
-    let mut failed_downloads = Vec::new();
-
-    for (index, image) in images.iter().enumerate() {
-        if let Some(filename) = image.as_str() {
-            let image_url = format!("{}/{}/{}/{}", base_url, url_path, chapter_hash, filename);
-            let page_number = format!("{:03}", index + 1);
-            let extension = StdPath::new(filename)
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("jpg");
-            let save_filename = format!("{}_{}.{}", safe_chapter_title, page_number, extension);
-            let save_file_path = format!("{}/{}", full_save_path, save_filename);
-
-            match client
-                .get(&image_url)
-                .header("User-Agent", "mangadownloader/0.1 (ravin.bhakta@gmail.com)")
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        match response.bytes().await {
-                            Ok(bytes) => {
-                                if let Err(_) = tokio::fs::write(&save_file_path, &bytes).await {
-                                    failed_downloads
-                                        .push(format!("Page {}: Failed to save file", index + 1));
-                                } else {
-                                    downloaded_files.push(save_filename);
-                                }
-                            }
-                            Err(_) => failed_downloads
-                                .push(format!("Page {}: Failed to read image data", index + 1)),
-                        }
-                    } else {
-                        failed_downloads.push(format!(
-                            "Page {}: HTTP {}",
-                            index + 1,
-                            response.status()
-                        ));
-                    }
-                }
-                Err(_) => failed_downloads.push(format!("Page {}: Network error", index + 1)),
-            }
-        }
-    }
-
-    let response = serde_json::json!({
-        "success": true,
-        "message": format!("Downloaded {} of {} pages", downloaded_files.len(), images.len()),
-        "save_path": full_save_path,
-        "downloaded_files": downloaded_files,
-        "failed_downloads": failed_downloads,
-        "total_pages": images.len(),
-        "successful_downloads": downloaded_files.len()
-    });
-
-    (
-        StatusCode::OK,
-        [(CONTENT_TYPE, "application/json")],
-        response.to_string(),
-    )
 }
 
 async fn root_handler() -> impl IntoResponse {
@@ -567,6 +492,40 @@ async fn main() {
         }
     };
 
+    // Build the in-memory search index without holding up startup
+    let index_service = manga_service.clone();
+    tokio::spawn(async move {
+        match index_service.rebuild_search_index().await {
+            Ok(count) => println!("✅ Search index built ({} manga)", count),
+            Err(e) => eprintln!("⚠️  Search index build failed, using database search: {}", e),
+        }
+    });
+
+    // Embedding provider for semantic search, chosen by EMBEDDING_PROVIDER
+    let embeddings = match embedding::provider_from_env() {
+        Ok(provider) => {
+            println!("✅ Embedding provider: {}", provider.model_id());
+            Some(provider)
+        }
+        Err(e) => {
+            println!("⚠️  {} - semantic search disabled", e);
+            None
+        }
+    };
+
+    // Load the persisted embedding index and catch up on changed embeddings
+    if let Some(provider) = &embeddings {
+        let model = provider.model_id();
+        let vector_service = manga_service.clone();
+        tokio::spawn(async move {
+            match vector_service.rebuild_vector_index(&model).await {
+                Ok(count) => println!("✅ Vector index ready ({} manga)", count),
+                Err(e) => eprintln!("⚠️  Vector index build failed, semantic search will scan embeddings: {}", e),
+            }
+        });
+        manga_service.vector_index().clone().spawn_persister();
+    }
+
     // Initialize Redis cache (optional - fails gracefully if not available)
     let cache_service = match std::env::var("REDIS_URL") {
         Ok(redis_url) => {
@@ -589,6 +548,14 @@ async fn main() {
         }
     };
 
+    // Keep embeddings current in the background
+    let embedding_worker = embeddings.clone().map(|provider| {
+        let worker = EmbeddingWorker::new(manga_service.clone(), provider, cache_service.clone());
+        worker.clone().spawn_scheduler();
+        println!("✅ Embedding worker started");
+        worker
+    });
+
     // Initialize Progress Service
     let progress_service = match ProgressService::new().await {
         Ok(service) => {
@@ -602,7 +569,7 @@ async fn main() {
     };
 
     // Initialize Search Service with caching
-    let search_service = SearchService::new(cache_service.clone());
+    let search_service = SearchService::new(cache_service.clone(), embeddings.clone());
 
     // Initialize Cached MangaDex Client
     let cached_mangadex_client = CachedMangaDexClient::new(cache_service.clone());
@@ -612,38 +579,117 @@ async fn main() {
         println!("ℹ️  MangaDex API caching disabled (Redis not available)");
     }
 
-    // --- Optional AI Service for embeddings (requires OPENAI_API_KEY) ---
-    let ai_service_result = AIService::new();
-    if let Ok(ai_service) = &ai_service_result {
-        let model = std::env::var("OLLAMA_MODEL").unwrap_or_else(|_| "llama2".to_string());
-        match manga_service
-            .update_all_manga_embeddings(&ai_service, &model)
-            .await
-        {
-            Ok(_) => println!("✅ Embeddings updated for all manga on startup"),
-            Err(e) => println!("⚠️  Failed to update embeddings on startup: {}", e),
-        }
-    } else {
-        println!("ℹ️  AI Service not available (OPENAI_API_KEY not set) - semantic search disabled");
-    }
-
     let cors = CorsLayer::new()
         .allow_origin(tower_http::cors::Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::OPTIONS])
         .allow_headers([AUTHORIZATION, CONTENT_TYPE]);
 
+    // Shared client for direct MangaDex and at-home traffic
+    let upstream_client = api::upstream_client();
+    let downloader = Downloader::new(upstream_client.clone());
+
+    // Image proxy with on-disk cache for covers and pages
+    let image_proxy = match ImageProxy::new(upstream_client.clone()).await {
+        Ok(proxy) => {
+            println!("✅ Image proxy cache ready");
+            proxy
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to initialize image cache: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Auto-downloads of new chapters for followed series
+    let auto_download =
+        AutoDownloadService::new(progress_service.clone(), downloader.clone(), upstream_client.clone());
+    auto_download.clone().spawn_scheduler();
+    println!("✅ Auto-download scheduler started");
+
+    // Ratings and popularity aggregated onto catalog entries
+    CatalogStatsService::new(progress_service.clone(), manga_service.clone()).spawn_scheduler();
+    println!("✅ Catalog stats job started");
+
+    // Saved searches with background alert evaluation
+    let saved_searches =
+        SavedSearchService::new(progress_service.clone(), manga_service.clone(), search_service.clone());
+    saved_searches.clone().spawn_scheduler();
+    println!("✅ Saved search alerts started");
+
+    // Content ratings and languages from user preferences, capped by the
+    // admin-set instance policy
+    let content_policy = ContentPolicyService::new(progress_service.db().clone(), auth_service.clone());
+
+    // One search over the local catalog and MangaDex
+    let federated_search = FederatedSearchService::new(
+        manga_service.clone(),
+        progress_service.clone(),
+        cached_mangadex_client.clone(),
+    );
+
+    // Next-chapter prefetching driven by progress updates
+    let prefetcher = Prefetcher::new(upstream_client.clone(), downloader.clone(), image_proxy.clone());
+
+    // Personalized recommendations from library, ratings and history
+    let recommendations = embeddings.clone().map(|provider| {
+        RecommendationService::new(progress_service.clone(), manga_service.clone(), provider)
+    });
+
+    // "More like this" on series pages
+    let similar = SimilarService::new(manga_service.clone(), cache_service.clone(), embeddings.clone());
+
+    // Chat model for AI features; replies are cached in Redis
+    let ai_service = match LlmClient::from_env(cache_service.clone()) {
+        Ok(llm) => {
+            println!("✅ LLM: {}", llm.model_id());
+            Some(AIService::new(llm))
+        }
+        Err(e) => {
+            println!("ℹ️  {} - natural-language search uses keyword rules", e);
+            None
+        }
+    };
+    let query_parser = QueryParser::new(manga_service.clone(), ai_service.clone());
+
+    // AI summaries on demand and in batches; genre suggestions wait for review
+    let summaries = SummaryService::new(manga_service.clone(), ai_service, cache_service.clone());
+    summaries.clone().spawn_scheduler();
+
+    // Series imported more than once under different ids, merged by admins
+    let dedup = DedupService::new(
+        manga_service.clone(),
+        progress_service.clone(),
+        cache_service.clone(),
+        embeddings.clone(),
+    );
+    dedup.clone().spawn_scheduler();
+
     // Create unified AppState for new endpoints
     let app_state = AppState {
         manga_service: manga_service.clone(),
         progress_service,
         search_service,
         cache_service: cache_service.clone(),
+        auto_download,
+        prefetcher: prefetcher.clone(),
+        auth_service: auth_service.clone(),
+        saved_searches,
+        federated_search,
+        mangadex: cached_mangadex_client.clone(),
+        content_policy: content_policy.clone(),
+        embedding_worker,
+        recommendations,
+        similar,
+        query_parser,
+        summaries,
+        dedup,
     };
 
     let auth_routes = Router::new()
         .route("/api/auth/login", post(login_handler))
         .route("/api/auth/register", post(register_handler))
         .route("/api/auth/logout", post(logout_handler))
+        .route("/api/admin/users", get(list_users_handler))
         // Temporarily commented out due to state type mismatch - needs fixing
         // .route("/api/user/profile", get(profile_handler).put(update_profile_handler))
         .with_state(auth_service);
@@ -653,12 +699,28 @@ async fn main() {
         .route("/api/manga/:manga_id", get(get_manga_handler))
         .route("/api/manga/list", get(list_manga_handler))
         .route("/api/manga/search", get(search_manga_handler))
-        // .route("/api/manga/semantic-search", post(semantic_search_handler)) // Temporarily disabled
         .with_state(manga_service.clone());
 
+    let semantic_routes = Router::new()
+        .route("/api/manga/semantic-search", post(semantic_search_handler))
+        .with_state(SemanticSearchState {
+            manga_service: manga_service.clone(),
+            embeddings,
+        });
+
     let search_routes = Router::new()
         .route("/api/search/advanced", get(advanced_search_handler))
         .route("/api/search/autocomplete", get(autocomplete_handler))
+        .route("/api/search/federated", get(federated_search_handler))
+        .route("/api/search/parse", get(parse_search_handler))
+        .route("/api/manga/import/:manga_id", post(import_manga_handler))
+        .route("/api/manga/:manga_id/similar", get(similar_manga_handler))
+        .route("/api/manga/:manga_id/summary", get(manga_summary_handler))
+        .route("/api/saved-searches", get(list_saved_searches_handler).post(save_search_handler))
+        .route("/api/saved-searches/delete", post(delete_saved_search_handler))
+        .route("/api/saved-searches/run", get(run_saved_search_handler))
+        .route("/api/saved-searches/notifications", get(search_notifications_handler))
+        .route("/api/saved-searches/notifications/read", post(mark_notifications_read_handler))
         .with_state(app_state.clone());
 
     let progress_routes = Router::new()
@@ -667,6 +729,7 @@ async fn main() {
         .route("/api/progress/library", get(get_library_handler))
         .route("/api/progress/library/status", post(update_status_handler))
         .route("/api/progress/library/remove", post(remove_from_library_handler))
+        .route("/api/progress/library/rating", post(set_rating_handler))
         .route("/api/progress/stats", get(get_reading_stats_handler))
         .route("/api/bookmarks/add", post(add_bookmark_handler))
         .route("/api/bookmarks", get(get_bookmarks_handler))
@@ -674,15 +737,28 @@ async fn main() {
         .route("/api/history/add", post(add_history_handler))
         .route("/api/history", get(get_history_handler))
         .route("/api/continue-reading", get(get_continue_reading_handler))
+        .route("/api/recommendations", get(recommendations_handler))
         .route("/api/favorites/toggle", post(toggle_favorite_handler))
         .route("/api/favorites", get(get_favorites_handler))
-        .with_state(app_state);
+        .route("/api/progress/library/auto-download", post(set_auto_download_handler))
+        .route("/api/auto-downloads", get(get_auto_downloads_handler))
+        .with_state(app_state.clone());
 
-    let admin_routes = Router::new();
-        // Temporarily disabled - needs state refactoring  
-        // .route(\"/api/admin/update-embeddings\", post(update_embeddings_admin_handler))
-        // .with_state(manga_service.clone())
-        // .with_state(ai_service.clone());
+    let admin_routes = Router::new()
+        .route(
+            "/api/admin/content-policy",
+            get(get_content_policy_handler).put(set_content_policy_handler),
+        )
+        .route("/api/admin/embeddings", get(get_embedding_progress_handler))
+        .route("/api/admin/embeddings/run", post(run_embeddings_handler))
+        .route("/api/admin/summaries/run", post(run_summaries_handler))
+        .route("/api/admin/tag-proposals", get(list_tag_proposals_handler))
+        .route("/api/admin/tag-proposals/review", post(review_tag_proposal_handler))
+        .route("/api/admin/duplicates", get(list_duplicates_handler))
+        .route("/api/admin/duplicates/run", post(run_dedup_handler))
+        .route("/api/admin/duplicates/merge", post(merge_duplicate_handler))
+        .route("/api/admin/duplicates/dismiss", post(dismiss_duplicate_handler))
+        .with_state(app_state);
 
     // Create cached API routes
     let cached_api_routes = Router::new()
@@ -691,19 +767,35 @@ async fn main() {
         .route("/api/manga/:manga_id/chapters", get(chapters_handler))
         .with_state(cached_mangadex_client);
 
+    let download_routes = Router::new()
+        .route("/api/manga/download-files", get(download_files_handler))
+        .with_state(downloader);
+
+    let image_routes = Router::new()
+        .route("/api/image", get(image_proxy_handler))
+        .with_state(image_proxy);
+
+    let prefetch_routes = Router::new()
+        .route("/api/chapter/:chapter_id/at-home", get(at_home_handler))
+        .with_state(prefetcher);
+
     let app = Router::new()
         .route("/", get(root_handler))
         .route("/health", get(health_handler))
         .merge(cached_api_routes)
         .route("/api/manga/download", get(download_handler))
-        .route("/api/manga/download-files", get(download_files_handler))
+        .merge(download_routes)
+        .merge(image_routes)
+        .merge(prefetch_routes)
         .nest_service("/api-doc", ServeDir::new("public"))
         .merge(auth_routes)
         .merge(manga_routes)
+        .merge(semantic_routes)
         .merge(search_routes)
         .merge(progress_routes)
         .merge(admin_routes)
         .with_state(manga_service)
+        .layer(Extension(content_policy))
         .layer(cors);
 
     let port = std::env::var("PORT")
@@ -725,23 +817,60 @@ async fn main() {
         "💾 Download Files: http://{}/api/manga/download-files?chapter_id=id&save_path=path",
         addr
     );
+    println!(
+        "🖼️  Image Proxy: http://{}/api/image?url=...&w=480&format=webp",
+        addr
+    );
+    println!(
+        "🔮 Chapter At-Home: http://{}/api/chapter/:chapter_id/at-home",
+        addr
+    );
     println!("🔐 Auth endpoints:");
     println!("   POST http://{}/api/auth/login", addr);
     println!("   POST http://{}/api/auth/register", addr);
     println!("   POST http://{}/api/auth/logout", addr);
+    println!("   GET  http://{}/api/admin/users?limit=20&cursor=...", addr);
     println!("📖 Manga Storage endpoints:");
     println!("   POST http://{}/api/manga/save", addr);
     println!("   GET  http://{}/api/manga/:manga_id", addr);
     println!("   GET  http://{}/api/manga/list", addr);
     println!("   GET  http://{}/api/manga/search?q=query", addr);
+    println!("   POST http://{}/api/manga/semantic-search", addr);
+    println!("   POST http://{}/api/manga/import/:mangadex_id", addr);
+    println!("   GET  http://{}/api/manga/:manga_id/similar", addr);
+    println!("   GET  http://{}/api/manga/:manga_id/summary", addr);
     println!("🔍 Advanced Search endpoints:");
     println!("   GET  http://{}/api/search/advanced?query=...&tags=...&status=...", addr);
     println!("   GET  http://{}/api/search/autocomplete?q=query", addr);
+    println!("   GET  http://{}/api/search/federated?q=query", addr);
+    println!("   GET  http://{}/api/search/parse?q=completed+romance+after+2015", addr);
+    println!("🛡️  Admin endpoints:");
+    println!("   GET  http://{}/api/admin/content-policy", addr);
+    println!("   PUT  http://{}/api/admin/content-policy", addr);
+    println!("   GET  http://{}/api/admin/embeddings", addr);
+    println!("   POST http://{}/api/admin/embeddings/run", addr);
+    println!("   POST http://{}/api/admin/summaries/run", addr);
+    println!("   GET  http://{}/api/admin/tag-proposals?status=pending", addr);
+    println!("   POST http://{}/api/admin/tag-proposals/review", addr);
+    println!("   GET  http://{}/api/admin/duplicates?status=pending", addr);
+    println!("   POST http://{}/api/admin/duplicates/run", addr);
+    println!("   POST http://{}/api/admin/duplicates/merge", addr);
+    println!("   POST http://{}/api/admin/duplicates/dismiss", addr);
+    println!("   GET  http://{}/api/saved-searches?user_id=...", addr);
+    println!("   POST http://{}/api/saved-searches", addr);
+    println!("   POST http://{}/api/saved-searches/delete", addr);
+    println!("   GET  http://{}/api/saved-searches/run?user_id=...&id=...", addr);
+    println!("   GET  http://{}/api/saved-searches/notifications?user_id=...", addr);
+    println!("   POST http://{}/api/saved-searches/notifications/read", addr);
     println!("📊 Progress Tracking endpoints:");
     println!("   POST http://{}/api/progress/library/add", addr);
     println!("   POST http://{}/api/progress/update", addr);
     println!("   GET  http://{}/api/progress/library?user_id=...", addr);
     println!("   GET  http://{}/api/progress/stats?user_id=...", addr);
+    println!("   POST http://{}/api/progress/library/rating", addr);
+    println!("   POST http://{}/api/progress/library/auto-download", addr);
+    println!("   GET  http://{}/api/recommendations?user_id=...", addr);
+    println!("   GET  http://{}/api/auto-downloads?user_id=...", addr);
     println!();
     println!("✅ Server ready! (Using MongoDB storage)");
     if cache_service.is_some() {
@@ -1,121 +1,134 @@
-use crate::ai_service::AIService;
+use crate::embedding::{cosine, EmbeddingProvider};
 use axum::Json;
 use serde_json::json;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct SemanticSearchState {
+    pub manga_service: MangaService,
+    pub embeddings: Option<Arc<dyn EmbeddingProvider>>,
+}
+
+#[derive(Deserialize)]
+pub struct SemanticSearchRequest {
+    query: String,
+    limit: Option<usize>,
+}
+
 /// Semantic search handler: POST /api/manga/semantic-search
-/// Note: This handler expects both MangaService and AIService in state
 pub async fn semantic_search_handler(
-    manga_service: MangaService,
-    ai_service: AIService,
-    payload: serde_json::Value,
-) -> Json<serde_json::Value> {
-    // For now, return a simple response until state is properly configured
-    Json(json!({"error": "Semantic search temporarily disabled during refactoring"}))
-}
+    State(state): State<SemanticSearchState>,
+    content: ContentFilter,
+    Json(payload): Json<SemanticSearchRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(embeddings) = state.embeddings else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"success": false, "error": "Semantic search is not configured"})),
+        );
+    };
+    let query = match crate::search::bounded_input(&payload.query) {
+        Ok(query) if !query.is_empty() => query,
+        Ok(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"success": false, "error": "Query is required"})),
+            )
+        }
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"success": false, "error": e.to_string()}))),
+    };
+    let limit = payload.limit.unwrap_or(10).clamp(1, 50);
 
-/* Original implementation - will be restored with proper state management
-pub async fn semantic_search_handler_original(
-    manga_service: MangaService,
-    ai_service: AIService,
-    payload: serde_json::Value,
-) -> Json<serde_json::Value> {
-    let query = payload["query"].as_str().unwrap_or("");
-    let model = std::env::var("OLLAMA_MODEL").unwrap_or_else(|_| "llama2".to_string());
-    let query_embedding = match ai_service.embed_with_ollama(query, &model).await {
+    let query_embedding = match embeddings.embed_query(query).await {
         Ok(e) => e,
-        Err(e) => return Json(json!({"error": format!("Failed to embed query: {}", e)})),
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"success": false, "error": format!("Failed to embed query: {}", e)})),
+            )
+        }
     };
-    let collection = manga_service.manga_collection();
-    let mut cursor = match collection.find(doc! {"embedding": {"$exists": true}}).await {
-        Ok(c) => c,
-        Err(e) => return Json(json!({"error": format!("Failed to fetch manga: {}", e)})),
+
+    let mut filter = Document::new();
+    content.apply(&mut filter);
+    let results = match state
+        .manga_service
+        .semantic_search(&query_embedding, &embeddings.model_id(), limit, filter)
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"success": false, "error": format!("Failed to search manga: {}", e)})),
+            )
+        }
     };
-    let mut results = Vec::new();
-    while cursor.advance().await.unwrap_or(false) {
-        let manga = cursor.deserialize_current().unwrap();
-        if let Some(embedding) = manga.embedding {
-            let score = cosine_similarity(&query_embedding, &embedding);
-            results.push(json!({
+    let results: Vec<serde_json::Value> = results
+        .into_iter()
+        .map(|(mut manga, score)| {
+            manga.localize(content.preferred_language.as_deref());
+            json!({
                 "manga_id": manga.manga_id,
-                "title": manga.title,
+                "title": manga.display_title.or(manga.title),
                 "description": manga.description,
                 "score": score
-            }));
-        }
-    }
-    // Sort by score descending and return top 10
-    results.sort_by(|a, b| {
-        b["score"]
-            .as_f64()
-            .partial_cmp(&a["score"].as_f64())
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    Json(json!({"results": results.into_iter().take(10).collect::<Vec<_>>() }))
+            })
+        })
+        .collect();
+    (StatusCode::OK, Json(json!({"success": true, "results": results})))
 }
-*/
-
-fn cosine_similarity(a: &Vec<f32>, b: &Vec<f32>) -> f32 {
-    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot / (norm_a * norm_b)
-    }
+
+/// The caller's filter narrowed to the vector index's neighbours, keeping
+/// any `manga_id` clause the caller already has
+fn neighbour_filter(filter: Document, ids: &[&str]) -> Document {
+    and_filter(filter, Some(doc! { "manga_id": { "$in": ids } }))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::cosine_similarity;
-
     #[test]
-    fn test_identical_vectors() {
-        let a = vec![1.0, 2.0, 3.0];
-        let b = vec![1.0, 2.0, 3.0];
-        let sim = cosine_similarity(&a, &b);
-        assert!(
-            (sim - 1.0).abs() < 1e-6,
-            "Similarity of identical vectors should be 1.0"
-        );
+    fn test_pick_title_prefers_reader_language() {
+        use super::pick_title;
+
+        let titles = [
+            (None, "Attack on Titan"),
+            (Some("ja"), "進撃の巨人"),
+            (Some("ja-ro"), "Shingeki no Kyojin"),
+            (Some("pt-br"), "Ataque dos Titãs"),
+        ];
+        assert_eq!(pick_title(&titles, Some("ja")), Some("進撃の巨人"));
+        assert_eq!(pick_title(&titles, Some("JA-RO")), Some("Shingeki no Kyojin"));
+        assert_eq!(pick_title(&titles, Some("pt")), Some("Ataque dos Titãs"));
+        assert_eq!(pick_title(&titles, Some("de")), Some("Attack on Titan"));
+        assert_eq!(pick_title(&titles, None), Some("Attack on Titan"));
+        assert_eq!(pick_title(&titles[1..], Some("de")), Some("進撃の巨人"));
     }
 
     #[test]
-    fn test_orthogonal_vectors() {
-        let a = vec![1.0, 0.0];
-        let b = vec![0.0, 1.0];
-        let sim = cosine_similarity(&a, &b);
-        assert!(
-            (sim - 0.0).abs() < 1e-6,
-            "Similarity of orthogonal vectors should be 0.0"
-        );
-    }
+    fn test_neighbour_filter_keeps_callers_manga_id_clause() {
+        use super::neighbour_filter;
+        use mongodb::bson::doc;
 
-    #[test]
-    fn test_zero_vector() {
-        let a = vec![0.0, 0.0, 0.0];
-        let b = vec![1.0, 2.0, 3.0];
-        let sim = cosine_similarity(&a, &b);
-        assert!(
-            (sim - 0.0).abs() < 1e-6,
-            "Similarity with zero vector should be 0.0"
-        );
-    }
-
-    #[test]
-    fn test_negative_similarity() {
-        let a = vec![1.0, 0.0];
-        let b = vec![-1.0, 0.0];
-        let sim = cosine_similarity(&a, &b);
-        assert!(
-            (sim + 1.0).abs() < 1e-6,
-            "Similarity of opposite vectors should be -1.0"
+        let filter = doc! { "manga_id": { "$nin": ["read"] }, "status": "ongoing" };
+        assert_eq!(
+            neighbour_filter(filter.clone(), &["read", "new"]),
+            doc! { "$and": [filter, { "manga_id": { "$in": ["read", "new"] } }] }
         );
+        assert_eq!(neighbour_filter(doc! {}, &["new"]), doc! { "manga_id": { "$in": ["new"] } });
     }
 }
 
-use mongodb::bson::{doc, oid::ObjectId};
+use crate::content_filter::ContentFilter;
+use crate::embedding_worker::text_hash;
+use crate::pagination::{and_filter, keyset_sort, next_page_cursor, parse_cursor, InvalidCursor};
+use crate::search_index::{SearchHit, SearchIndex};
+use crate::summaries::summary_source;
+use crate::vector_index::VectorIndex;
+use mongodb::bson::{doc, oid::ObjectId, Document};
 use mongodb::{Client, Collection, Database};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::env;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -128,14 +141,109 @@ pub struct Manga {
     pub author: Option<String>,
     pub artist: Option<String>,
     pub status: Option<String>,    // Now optional
+    pub titles: Option<Vec<LocalizedTitle>>, // Localized titles and altTitles from MangaDex
+    pub alt_titles: Option<Vec<String>>, // Other titles with no known language
     pub tags: Option<Vec<String>>, // Now optional
+    pub content_rating: Option<String>, // safe, suggestive, erotica, pornographic
+    pub year: Option<i32>,
+    // Maintained by the catalog stats job, never taken from clients
+    pub rating: Option<f64>, // average of user ratings, 1-5
+    pub rating_count: Option<u32>,
+    pub popularity: Option<f64>,
+    pub stats_updated_at: Option<String>,
+    // Best title for the requesting user's language; only set in responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_title: Option<String>,
     pub cover_art: Option<String>,
     pub embedding: Option<Vec<f32>>,
+    // Set by the embedding worker alongside `embedding`
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    #[serde(default)]
+    pub embedding_version: Option<i32>,
+    #[serde(default)]
+    pub embedding_hash: Option<String>,
+    // Written by the summary job, never taken from clients
+    #[serde(default)]
+    pub ai_summary: Option<AiSummary>,
+    // When the summary job last failed on this manga, so a batch moves past it
+    #[serde(default)]
+    pub ai_summary_failed_at: Option<String>,
     pub chapters: Option<Vec<Chapter>>, // Now optional
     pub created_at: Option<String>,     // Now optional
     pub updated_at: Option<String>,     // Now optional
 }
 
+/// LLM-written overview of a series and what it was generated from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AiSummary {
+    pub summary: String,
+    pub target_audience: String,
+    pub model: String,
+    pub version: i32,
+    /// Hash of the text the summary was written from
+    pub source_hash: String,
+    pub generated_at: String,
+}
+
+/// A title in one language, e.g. `{ "language": "ja-ro", "title": "Shingeki no Kyojin" }`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LocalizedTitle {
+    pub language: String,
+    pub title: String,
+}
+
+/// Pick the title to show for a language: an exact language match, then the
+/// same base language (`pt` for `pt-br`), then the canonical title, then English
+pub fn pick_title<'a>(titles: &[(Option<&'a str>, &'a str)], preferred_language: Option<&str>) -> Option<&'a str> {
+    let base = |language: &str| language.split('-').next().unwrap_or_default().to_lowercase();
+    if let Some(preferred) = preferred_language {
+        let exact = titles
+            .iter()
+            .find(|(language, _)| language.is_some_and(|l| l.eq_ignore_ascii_case(preferred)));
+        let same_base = || {
+            titles
+                .iter()
+                .find(|(language, _)| language.is_some_and(|l| base(l) == base(preferred)))
+        };
+        if let Some((_, title)) = exact.or_else(same_base) {
+            return Some(title);
+        }
+    }
+    titles
+        .iter()
+        .find(|(language, _)| language.is_none())
+        .or_else(|| titles.iter().find(|(language, _)| *language == Some("en")))
+        .or_else(|| titles.first())
+        .map(|(_, title)| *title)
+}
+
+impl Manga {
+    /// Every title the manga is known by, canonical title first, with its
+    /// language when known
+    pub fn all_titles(&self) -> Vec<(Option<&str>, &str)> {
+        let mut titles: Vec<(Option<&str>, &str)> = Vec::new();
+        titles.extend(self.title.as_deref().map(|t| (None, t)));
+        titles.extend(
+            self.titles
+                .iter()
+                .flatten()
+                .map(|t| (Some(t.language.as_str()), t.title.as_str())),
+        );
+        titles.extend(self.alt_titles.iter().flatten().map(|t| (None, t.as_str())));
+        titles
+    }
+
+    pub fn best_title(&self, preferred_language: Option<&str>) -> Option<&str> {
+        pick_title(&self.all_titles(), preferred_language)
+    }
+
+    /// Set `display_title` for a reader's language
+    pub fn localize(&mut self, preferred_language: Option<&str>) {
+        self.display_title = self.best_title(preferred_language).map(str::to_string);
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Chapter {
     pub chapter_id: String,
@@ -151,6 +259,14 @@ pub struct MangaListResponse {
     pub manga: Option<Vec<Manga>>,
     pub total_count: Option<i64>,
     pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facets: Option<crate::search::SearchFacets>,
+    /// Opaque cursor for the next page, when there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Per-result ranking explanations for hybrid searches, in `manga` order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scores: Option<Vec<crate::hybrid_search::HybridScore>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -163,38 +279,136 @@ pub struct MangaResponse {
 #[derive(Clone)]
 pub struct MangaService {
     db: Database,
+    search_index: SearchIndex,
+    vector_index: VectorIndex,
 }
 
 impl MangaService {
     pub fn db(&self) -> &Database {
         &self.db
     }
-    /// Update embeddings for all manga in the database using Ollama
-    pub async fn update_all_manga_embeddings(
+
+    pub fn search_index(&self) -> &SearchIndex {
+        &self.search_index
+    }
+
+    /// Load every catalog entry into the search index
+    pub async fn rebuild_search_index(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .manga_collection()
+            .find(doc! {})
+            .projection(doc! {
+                "manga_id": 1, "title": 1, "titles": 1, "alt_titles": 1, "author": 1, "artist": 1
+            })
+            .await?;
+
+        while cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            self.search_index.upsert(&manga);
+        }
+        self.search_index.mark_ready();
+        Ok(self.search_index.document_count())
+    }
+
+    pub fn vector_index(&self) -> &VectorIndex {
+        &self.vector_index
+    }
+
+    /// Load the persisted embedding index and bring it in line with the
+    /// catalog's `model` embeddings, so only those that changed since the last
+    /// save are linked
+    pub async fn rebuild_vector_index(&self, model: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let index = self.vector_index.clone();
+        let file_model = model.to_string();
+        match tokio::task::spawn_blocking(move || index.load(&file_model)).await? {
+            Ok(count) => println!("💾 Loaded vector index from disk ({} manga)", count),
+            Err(e) => eprintln!("⚠️  Ignoring unreadable vector index file: {}", e),
+        }
+
+        let mut cursor = self
+            .manga_collection()
+            .find(doc! { "embedding": { "$type": "array" }, "embedding_model": model })
+            .projection(doc! { "manga_id": 1, "embedding": 1 })
+            .await?;
+
+        let mut seen = HashSet::new();
+        while cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            let (Some(manga_id), Some(embedding)) = (manga.manga_id, manga.embedding) else {
+                continue;
+            };
+            let indexed = self.vector_index.contains(&manga_id, &embedding)
+                || self
+                    .vector_index
+                    .upsert(&manga_id, &embedding)
+                    .inspect_err(|e| eprintln!("⚠️  Skipping embedding for {}: {}", manga_id, e))
+                    .is_ok();
+            if !indexed {
+                continue;
+            }
+            seen.insert(manga_id);
+        }
+        self.vector_index.retain(|manga_id| seen.contains(manga_id));
+        self.vector_index.mark_ready();
+
+        let index = self.vector_index.clone();
+        tokio::task::spawn_blocking(move || index.persist()).await??;
+        Ok(self.vector_index.document_count())
+    }
+
+    /// Nearest manga matching `filter` to an embedding, best first. Uses the
+    /// vector index once built and scans stored embeddings until then.
+    pub async fn semantic_search(
         &self,
-        ai_service: &crate::ai_service::AIService,
+        embedding: &[f32],
         model: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        limit: usize,
+        filter: Document,
+    ) -> Result<Vec<(Manga, f32)>, Box<dyn std::error::Error>> {
         let collection = self.manga_collection();
-        let mut cursor = collection.find(doc! {}).await?;
-        while cursor.advance().await? {
-            let manga = cursor.deserialize_current()?;
-            let text = format!(
-                "{} {}",
-                manga.title.clone().unwrap_or_default(),
-                manga.description.clone().unwrap_or_default()
-            );
-            let embedding = ai_service.embed_with_ollama(&text, model).await?;
-            // Update the embedding field in the document
-            collection
-                .update_one(
-                    doc! { "_id": &manga.id },
-                    doc! { "$set": { "embedding": &embedding } },
-                )
-                .await?;
+
+        if !self.vector_index.is_ready() {
+            let mut filter = filter;
+            filter.insert("embedding", doc! { "$type": "array" });
+            filter.insert("embedding_model", model);
+            let mut cursor = collection.find(filter).await?;
+            let mut scored = Vec::new();
+            while cursor.advance().await? {
+                let manga: Manga = cursor.deserialize_current()?;
+                let score = manga.embedding.as_deref().map_or(0.0, |e| cosine(embedding, e).unwrap_or(0.0));
+                scored.push((manga, score));
+            }
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            scored.truncate(limit);
+            return Ok(scored);
+        }
+
+        // Over-fetch so filtering still leaves a full page, and keep widening
+        // while a selective filter drops most of the neighbours
+        let total = self.vector_index.document_count();
+        let mut k = limit * 3;
+        loop {
+            let hits = self.vector_index.search(embedding, k);
+            let ids: Vec<&str> = hits.iter().map(|hit| hit.manga_id.as_str()).collect();
+            let mut cursor = collection.find(neighbour_filter(filter.clone(), &ids)).await?;
+            let mut found: HashMap<String, Manga> = HashMap::new();
+            while cursor.advance().await? {
+                let manga: Manga = cursor.deserialize_current()?;
+                if let Some(manga_id) = manga.manga_id.clone() {
+                    found.insert(manga_id, manga);
+                }
+            }
+            if found.len() >= limit || hits.len() < k || k >= total {
+                return Ok(hits
+                    .into_iter()
+                    .filter_map(|hit| found.remove(&hit.manga_id).map(|manga| (manga, hit.score)))
+                    .take(limit)
+                    .collect());
+            }
+            k = (k * 4).min(total);
         }
-        Ok(())
     }
+
     pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
         // Use separate manga database URL if provided, otherwise fall back to main MONGODB_URI
         let mongodb_uri = env::var("MONGODB_URI_MANGA")
@@ -241,7 +455,11 @@ impl MangaService {
 
         println!("🚀 MangaService initialized successfully");
 
-        Ok(MangaService { db: database })
+        Ok(MangaService {
+            db: database,
+            search_index: SearchIndex::new(),
+            vector_index: VectorIndex::new(),
+        })
     }
 
     async fn create_indexes(
@@ -256,13 +474,33 @@ impl MangaService {
             .options(IndexOptions::builder().sparse(true).build())
             .build();
 
-        // Text index for title, description, and author for full-text search
+        // Text index over every title, the description and the author for
+        // full-text search. A collection has only one text index, so one
+        // built by an older version for fewer fields is dropped first.
+        const TEXT_INDEX_NAME: &str = "catalog_text";
+        if let Ok(mut existing) = collection.list_indexes().await {
+            let mut outdated = Vec::new();
+            while existing.advance().await? {
+                let index = existing.deserialize_current()?;
+                let name = index.options.and_then(|o| o.name);
+                if index.keys.contains_key("_fts") && name.as_deref() != Some(TEXT_INDEX_NAME) {
+                    outdated.extend(name);
+                }
+            }
+            for name in outdated {
+                println!("🔄 Replacing text index {}", name);
+                collection.drop_index(name).await?;
+            }
+        }
         let title_index = IndexModel::builder()
-            .keys(doc! { 
-                "title": "text", 
+            .keys(doc! {
+                "title": "text",
+                "titles.title": "text",
+                "alt_titles": "text",
                 "description": "text",
                 "author": "text"
             })
+            .options(IndexOptions::builder().name(TEXT_INDEX_NAME.to_string()).build())
             .build();
 
         // Tags index for filtering
@@ -285,6 +523,17 @@ impl MangaService {
             .keys(doc! { "updated_at": -1 })
             .build();
 
+        // Indexes for rating/popularity sorts and year range filters
+        let rating_index = IndexModel::builder()
+            .keys(doc! { "rating": -1 })
+            .build();
+        let popularity_index = IndexModel::builder()
+            .keys(doc! { "popularity": -1 })
+            .build();
+        let year_index = IndexModel::builder()
+            .keys(doc! { "year": 1 })
+            .build();
+
         // Vector index for semantic search (if MongoDB Atlas is used)
         let embedding_index = IndexModel::builder()
             .keys(doc! { "embedding": 1 })
@@ -299,6 +548,9 @@ impl MangaService {
                 author_index,
                 status_index,
                 updated_at_index,
+                rating_index,
+                popularity_index,
+                year_index,
                 embedding_index,
             ])
             .await?;
@@ -309,9 +561,17 @@ impl MangaService {
         self.db.collection("manga")
     }
 
+    /// Every tag used in the catalog, sorted
+    pub async fn tag_vocabulary(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let tags = self.manga_collection().distinct("tags", doc! {}).await?;
+        let mut tags: Vec<String> = tags.into_iter().filter_map(|t| t.as_str().map(str::to_string)).collect();
+        tags.sort();
+        Ok(tags)
+    }
+
     pub async fn save_manga(
         &self,
-        manga: Manga,
+        mut manga: Manga,
     ) -> Result<MangaResponse, Box<dyn std::error::Error>> {
         let collection = self.manga_collection();
 
@@ -323,7 +583,37 @@ impl MangaService {
             .find_one(doc! { "manga_id": &manga.manga_id })
             .await?;
 
-        if let Some(_existing_manga) = existing {
+        // Display titles are picked per request, never stored
+        manga.display_title = None;
+        // Aggregated stats belong to the stats job
+        manga.rating = None;
+        manga.rating_count = None;
+        manga.popularity = None;
+        manga.stats_updated_at = None;
+        // Embeddings belong to the embedding worker, which picks up new or
+        // changed text on its next pass
+        manga.embedding = None;
+        manga.embedding_model = None;
+        manga.embedding_version = None;
+        manga.embedding_hash = None;
+        manga.ai_summary = None;
+        manga.ai_summary_failed_at = None;
+
+        if let Some(existing_manga) = existing {
+            // Changed text deserves a fresh summary attempt
+            if text_hash(&summary_source(&manga)) == text_hash(&summary_source(&existing_manga)) {
+                manga.ai_summary_failed_at = existing_manga.ai_summary_failed_at.clone();
+            }
+            manga.rating = existing_manga.rating;
+            manga.rating_count = existing_manga.rating_count;
+            manga.popularity = existing_manga.popularity;
+            manga.stats_updated_at = existing_manga.stats_updated_at;
+            manga.embedding = existing_manga.embedding;
+            manga.embedding_model = existing_manga.embedding_model;
+            manga.embedding_version = existing_manga.embedding_version;
+            manga.embedding_hash = existing_manga.embedding_hash;
+            manga.ai_summary = existing_manga.ai_summary;
+
             // Update existing manga
             let result = collection
                 .replace_one(doc! { "manga_id": &manga.manga_id }, &manga)
@@ -342,6 +632,8 @@ impl MangaService {
             );
         }
 
+        self.search_index.upsert(&manga);
+
         Ok(MangaResponse {
             success: true,
             manga: Some(manga),
@@ -349,6 +641,22 @@ impl MangaService {
         })
     }
 
+    /// Add a MangaDex series to the catalog with its localized titles, or
+    /// refresh the entry already there from MangaDex
+    pub async fn import_from_mangadex(
+        &self,
+        data: &crate::api::MangaData,
+    ) -> Result<MangaResponse, Box<dyn std::error::Error>> {
+        use crate::federated_search::{manga_from_mangadex, refresh_from_mangadex};
+
+        let imported = manga_from_mangadex(data);
+        let manga = match self.manga_collection().find_one(doc! { "manga_id": &data.id }).await? {
+            Some(existing) => refresh_from_mangadex(existing, imported),
+            None => imported,
+        };
+        self.save_manga(manga).await
+    }
+
     pub async fn get_manga(
         &self,
         manga_id: &str,
@@ -375,22 +683,29 @@ impl MangaService {
         &self,
         page: Option<i64>,
         limit: Option<i64>,
+        after: Option<&str>,
+        content: &ContentFilter,
     ) -> Result<MangaListResponse, Box<dyn std::error::Error>> {
         let collection = self.manga_collection();
+        let mut visible = doc! {};
+        content.apply(&mut visible);
 
         // Get total count
-        let total_count = collection.count_documents(doc! {}).await?;
-
-        // Apply pagination
-        let page = page.unwrap_or(1).max(1);
-        let limit = limit.unwrap_or(20).min(100); // Max 100 manga per page
-        let skip = (page - 1) * limit;
+        let total_count = collection.count_documents(visible.clone()).await?;
+
+        // Apply pagination: a cursor continues after the last item it saw,
+        // otherwise fall back to page/limit
+        let limit = limit.unwrap_or(20).clamp(1, 100); // Max 100 manga per page
+        let (filter, skip) = match parse_cursor(after)? {
+            Some(cursor) => (and_filter(visible, Some(cursor.keyset_filter("updated_at", -1)?)), 0),
+            None => (visible, (page.unwrap_or(1).max(1) - 1) * limit),
+        };
 
         let mut cursor = collection
-            .find(doc! {})
-            .sort(doc! {"updated_at": -1})
+            .find(filter)
+            .sort(keyset_sort("updated_at", -1))
             .skip(skip as u64)
-            .limit(limit)
+            .limit(limit + 1)
             .await?;
 
         let mut manga_list = Vec::new();
@@ -398,35 +713,145 @@ impl MangaService {
             let manga = cursor.deserialize_current()?;
             manga_list.push(manga);
         }
+        let next_cursor = next_page_cursor(&mut manga_list, limit as usize, "updated_at");
 
         Ok(MangaListResponse {
             success: true,
             manga: Some(manga_list.clone()),
             total_count: Some(total_count as i64),
             message: Some(format!("Retrieved {} manga", manga_list.len())),
+            facets: None,
+            next_cursor,
+            scores: None,
         })
     }
 
+    /// Text matches for a query: search index hits by score, then matches
+    /// only the database finds (in descriptions, say) with a score of 0, up
+    /// to `limit` of each. `filter` narrows the database matches only.
+    /// `None` when the index cannot answer the query, because it is still
+    /// being built or the query uses phrase or exclusion syntax; callers then
+    /// filter with the `TextQuery` itself.
+    pub async fn text_hits(
+        &self,
+        text_query: &crate::search::TextQuery,
+        mut filter: Document,
+        limit: usize,
+    ) -> Result<Option<Vec<SearchHit>>, Box<dyn std::error::Error>> {
+        use crate::search::text_score_sort;
+
+        if !self.search_index.is_ready() || text_query.has_operators() {
+            return Ok(None);
+        }
+        let mut hits = self.search_index.search(text_query.as_str(), limit);
+        let mut seen: HashSet<String> = hits.iter().map(|hit| hit.manga_id.clone()).collect();
+
+        filter.extend(text_query.filter());
+        let collection = self.manga_collection();
+        let find = collection.find(filter).limit(limit as i64);
+        let mut cursor = if text_query.is_full_text() {
+            find.projection(doc! { "manga_id": 1, "title": 1, "score": { "$meta": "textScore" } })
+                .sort(text_score_sort())
+                .await?
+        } else {
+            find.projection(doc! { "manga_id": 1, "title": 1 })
+                .sort(doc! { "title": 1 })
+                .await?
+        };
+        while cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            if let Some(manga_id) = manga.manga_id.filter(|id| seen.insert(id.clone())) {
+                hits.push(SearchHit {
+                    manga_id,
+                    title: manga.title.unwrap_or_default(),
+                    score: 0.0,
+                });
+            }
+        }
+        Ok(Some(hits))
+    }
+
     pub async fn search_manga(
         &self,
         query: &str,
+        content: &ContentFilter,
     ) -> Result<MangaListResponse, Box<dyn std::error::Error>> {
+        use crate::search::{literal_pattern, text_score_projection, text_score_sort, TextQuery};
+
         let collection = self.manga_collection();
 
-        let search_filter = doc! {
-            "$or": [
-                { "title": { "$regex": query, "$options": "i" } },
-                { "tags": { "$in": [query] } },
-                { "author": { "$regex": query, "$options": "i" } }
-            ]
+        let Some(text_query) = TextQuery::parse(query)? else {
+            return Ok(MangaListResponse {
+                success: true,
+                manga: Some(Vec::new()),
+                total_count: Some(0),
+                message: Some("Empty search query".to_string()),
+                facets: None,
+                next_cursor: None,
+                scores: None,
+            });
         };
 
-        let mut cursor = collection.find(search_filter).await?;
-        let mut manga_list = Vec::new();
+        // The search index handles typos, accents and every title, and the
+        // text index adds description matches; phrase and exclusion queries
+        // and searches before the index is built use the text index alone
+        let mut scope = doc! {};
+        content.apply(&mut scope);
+        let mut manga_list: Vec<Manga> = Vec::new();
+        let hits = self.text_hits(&text_query, scope, 100).await?;
+        if let Some(hits) = hits {
+            let ids: Vec<&str> = hits.iter().map(|hit| hit.manga_id.as_str()).collect();
+            let mut filter = doc! { "manga_id": { "$in": &ids } };
+            content.apply(&mut filter);
+            let mut cursor = collection.find(filter).await?;
+            while cursor.advance().await? {
+                manga_list.push(cursor.deserialize_current()?);
+            }
+            manga_list.sort_by_key(|m| {
+                m.manga_id
+                    .as_deref()
+                    .and_then(|id| ids.iter().position(|hit| *hit == id))
+                    .unwrap_or(usize::MAX)
+            });
+        } else {
+            let mut filter = text_query.filter();
+            content.apply(&mut filter);
+            let mut cursor = if text_query.is_full_text() {
+                collection
+                    .find(filter)
+                    .projection(text_score_projection())
+                    .sort(text_score_sort())
+                    .limit(100)
+                    .await?
+            } else {
+                collection
+                    .find(filter)
+                    .sort(doc! { "title": 1 })
+                    .limit(100)
+                    .await?
+            };
+            while cursor.advance().await? {
+                manga_list.push(cursor.deserialize_current()?);
+            }
+        }
 
-        while cursor.advance().await? {
-            let manga = cursor.deserialize_current()?;
-            manga_list.push(manga);
+        // Neither index covers tags, so a query naming a tag ("Romance")
+        // also finds the series tagged with it
+        let mut tag_filter = doc! {
+            "tags": { "$regex": format!("^{}$", literal_pattern(query)?), "$options": "i" }
+        };
+        content.apply(&mut tag_filter);
+        let mut cursor = collection
+            .find(tag_filter)
+            .sort(doc! { "popularity": -1 })
+            .limit(100)
+            .await?;
+        let found: HashSet<String> = manga_list.iter().filter_map(|m| m.manga_id.clone()).collect();
+        while cursor.advance().await? && manga_list.len() < 100 {
+            let manga: Manga = cursor.deserialize_current()?;
+            if manga.manga_id.as_ref().is_some_and(|id| !found.contains(id)) {
+                manga_list.push(manga);
+            }
         }
 
         Ok(MangaListResponse {
@@ -438,6 +863,9 @@ impl MangaService {
                 manga_list.len(),
                 query
             )),
+            facets: None,
+            next_cursor: None,
+            scores: None,
         })
     }
 }
@@ -452,11 +880,13 @@ use axum::{
 pub struct MangaQuery {
     page: Option<i64>,
     limit: Option<i64>,
+    cursor: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct SearchQuery {
     q: String,
+    preferred_language: Option<String>,
 }
 
 pub async fn save_manga_handler(
@@ -481,13 +911,17 @@ pub async fn get_manga_handler(
 
 pub async fn list_manga_handler(
     State(manga_service): State<MangaService>,
+    content: ContentFilter,
     Query(params): Query<MangaQuery>,
 ) -> Result<Json<MangaListResponse>, StatusCode> {
     println!(
         "📖 List manga endpoint called with params: page={:?}, limit={:?}",
         params.page, params.limit
     );
-    match manga_service.list_manga(params.page, params.limit).await {
+    match manga_service
+        .list_manga(params.page, params.limit, params.cursor.as_deref(), &content)
+        .await
+    {
         Ok(response) => {
             println!(
                 "✅ List manga successful: {} manga found",
@@ -495,6 +929,7 @@ pub async fn list_manga_handler(
             );
             Ok(Json(response))
         }
+        Err(e) if e.is::<InvalidCursor>() => Err(StatusCode::BAD_REQUEST),
         Err(e) => {
             println!("❌ List manga error: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -504,10 +939,18 @@ pub async fn list_manga_handler(
 
 pub async fn search_manga_handler(
     State(manga_service): State<MangaService>,
+    content: ContentFilter,
     Query(params): Query<SearchQuery>,
 ) -> Result<Json<MangaListResponse>, StatusCode> {
-    match manga_service.search_manga(&params.q).await {
-        Ok(response) => Ok(Json(response)),
+    let language = params.preferred_language.or(content.preferred_language.clone());
+    match manga_service.search_manga(&params.q, &content).await {
+        Ok(mut response) => {
+            for manga in response.manga.iter_mut().flatten() {
+                manga.localize(language.as_deref());
+            }
+            Ok(Json(response))
+        }
+        Err(e) if e.is::<crate::search::SearchInputError>() => Err(StatusCode::BAD_REQUEST),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
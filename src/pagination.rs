@@ -1,3 +1,6 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
 use serde::{Deserialize, Serialize};
 
 /// Pagination query parameters
@@ -7,6 +10,8 @@ pub struct PaginationParams {
     pub page: u32,
     #[serde(default = "default_limit")]
     pub limit: u32,
+    /// Opaque cursor from a previous page; takes precedence over `page`
+    pub cursor: Option<String>,
 }
 
 fn default_page() -> u32 {
@@ -57,6 +62,8 @@ pub struct PaginationInfo {
     pub total_pages: u32,
     pub has_next: bool,
     pub has_prev: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 impl PaginationInfo {
@@ -70,6 +77,7 @@ impl PaginationInfo {
             total_pages,
             has_next: page < total_pages,
             has_prev: page > 1,
+            next_cursor: None,
         }
     }
 }
@@ -81,4 +89,195 @@ impl<T> PaginatedResponse<T> {
             pagination: PaginationInfo::new(page, limit, total_items),
         }
     }
+
+    /// Attach the cursor for the page after this one
+    pub fn with_next_cursor(mut self, next_cursor: Option<String>) -> Self {
+        self.pagination.has_next |= next_cursor.is_some();
+        self.pagination.next_cursor = next_cursor;
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid pagination cursor")]
+pub struct InvalidCursor;
+
+/// Where the next page starts. Clients only ever see the encoded token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "t")]
+pub enum Cursor {
+    /// Keyset position: the sort key and `_id` of the last item returned
+    #[serde(rename = "k")]
+    After {
+        #[serde(rename = "f")]
+        field: String,
+        #[serde(rename = "v")]
+        key: Bson,
+        #[serde(rename = "i")]
+        id: ObjectId,
+    },
+    /// Orders that can't be expressed as a query filter (relevance) page by offset
+    #[serde(rename = "o")]
+    Offset {
+        #[serde(rename = "n")]
+        offset: u64,
+    },
+}
+
+impl Cursor {
+    /// Keyset cursor positioned after `item`, which must serialize with an `_id`
+    pub fn after<T: Serialize>(item: &T, field: &str) -> Option<Self> {
+        let document = mongodb::bson::to_document(item).ok()?;
+        let id = document.get_object_id("_id").ok()?;
+        let key = document.get(field).cloned().unwrap_or(Bson::Null);
+        Some(Cursor::After {
+            field: field.to_string(),
+            key,
+            id,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let document = mongodb::bson::to_document(self).unwrap_or_default();
+        let mut bytes = Vec::new();
+        let _ = document.to_writer(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    pub fn decode(token: &str) -> Result<Self, InvalidCursor> {
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| InvalidCursor)?;
+        let document = Document::from_reader(bytes.as_slice()).map_err(|_| InvalidCursor)?;
+        mongodb::bson::from_document(document).map_err(|_| InvalidCursor)
+    }
+
+    /// Filter for the documents after this cursor in a
+    /// `{ field: direction, _id: direction }` sort
+    pub fn keyset_filter(&self, field: &str, direction: i32) -> Result<Document, InvalidCursor> {
+        match self {
+            Cursor::After { field: cursor_field, key, id } if cursor_field == field => {
+                Ok(keyset_filter(field, direction, key, *id))
+            }
+            _ => Err(InvalidCursor),
+        }
+    }
+
+    /// Offset to resume from for offset cursors
+    pub fn offset(&self) -> Result<u64, InvalidCursor> {
+        match self {
+            Cursor::Offset { offset } => Ok(*offset),
+            Cursor::After { .. } => Err(InvalidCursor),
+        }
+    }
+}
+
+/// Parse an optional cursor token from a request
+pub fn parse_cursor(token: Option<&str>) -> Result<Option<Cursor>, InvalidCursor> {
+    token
+        .filter(|t| !t.is_empty())
+        .map(Cursor::decode)
+        .transpose()
+}
+
+/// Drop the extra item fetched past `limit` and return the cursor for the
+/// page after the remaining items, if there is one
+pub fn next_page_cursor<T: Serialize>(items: &mut Vec<T>, limit: usize, field: &str) -> Option<String> {
+    if items.len() <= limit {
+        return None;
+    }
+    items.truncate(limit);
+    items.last().and_then(|item| Cursor::after(item, field)).map(|c| c.encode())
+}
+
+/// Sort by `field` with `_id` as the tiebreaker, so keyset positions are unique
+pub fn keyset_sort(field: &str, direction: i32) -> Document {
+    doc! { field: direction, "_id": direction }
+}
+
+/// Documents strictly after (`key`, `id`) in a `{ field: direction, _id: direction }`
+/// sort. MongoDB sorts missing and null values first, but comparison
+/// operators never match them, so nulls get their own branches.
+pub fn keyset_filter(field: &str, direction: i32, key: &Bson, id: ObjectId) -> Document {
+    let (cmp, id_cmp) = if direction < 0 { ("$lt", "$lt") } else { ("$gt", "$gt") };
+    let same_key = doc! { field: key.clone(), "_id": { id_cmp: id } };
+    let mut branches = match (key, direction < 0) {
+        (Bson::Null, false) => vec![doc! { field: { "$ne": Bson::Null } }],
+        (Bson::Null, true) => vec![],
+        (key, false) => vec![doc! { field: { cmp: key.clone() } }],
+        (key, true) => vec![doc! { field: { cmp: key.clone() } }, doc! { field: Bson::Null }],
+    };
+    branches.insert(0, same_key);
+    doc! { "$or": branches }
+}
+
+/// Combine a query filter with a keyset filter
+pub fn and_filter(filter: Document, keyset: Option<Document>) -> Document {
+    match keyset {
+        Some(keyset) if filter.is_empty() => keyset,
+        Some(keyset) => doc! { "$and": [filter, keyset] },
+        None => filter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let id = ObjectId::new();
+        let cursor = Cursor::After {
+            field: "updated_at".to_string(),
+            key: Bson::String("2024-05-01T00:00:00Z".to_string()),
+            id,
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        let offset = Cursor::Offset { offset: 40 };
+        assert_eq!(Cursor::decode(&offset.encode()).unwrap().offset().unwrap(), 40);
+        assert!(Cursor::decode("not a cursor").is_err());
+        assert!(cursor.keyset_filter("title", -1).is_err());
+    }
+
+    #[test]
+    fn test_keyset_filter_handles_nulls() {
+        let id = ObjectId::new();
+        let desc = keyset_filter("rating", -1, &Bson::Double(4.5), id);
+        assert_eq!(
+            desc,
+            doc! { "$or": [
+                { "rating": 4.5, "_id": { "$lt": id } },
+                { "rating": { "$lt": 4.5 } },
+                { "rating": Bson::Null }
+            ] }
+        );
+
+        let asc_null = keyset_filter("rating", 1, &Bson::Null, id);
+        assert_eq!(
+            asc_null,
+            doc! { "$or": [
+                { "rating": Bson::Null, "_id": { "$gt": id } },
+                { "rating": { "$ne": Bson::Null } }
+            ] }
+        );
+    }
+
+    #[test]
+    fn test_cursor_after_reads_sort_key() {
+        #[derive(Serialize)]
+        struct Item {
+            #[serde(rename = "_id")]
+            id: ObjectId,
+            timestamp: String,
+        }
+        let id = ObjectId::new();
+        let item = Item { id, timestamp: "t1".to_string() };
+        assert_eq!(
+            Cursor::after(&item, "timestamp"),
+            Some(Cursor::After {
+                field: "timestamp".to_string(),
+                key: Bson::String("t1".to_string()),
+                id,
+            })
+        );
+    }
 }
@@ -0,0 +1,324 @@
+use crate::auto_download::{walk_feed, FeedChapter};
+use crate::downloader::{AtHomeServer, Downloader, Quality};
+use crate::image_proxy::ImageProxy;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// A chapter that already triggered a prefetch is ignored for this long
+const TRIGGER_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// At-home node URLs carry a token that MangaDex expires after about 15 minutes
+const AT_HOME_TTL: Duration = Duration::from_secs(10 * 60);
+
+type PrefetchResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Debug, Deserialize)]
+struct ChapterResponse {
+    data: FeedChapter,
+}
+
+/// Dedup bookkeeping and prefetched at-home data
+#[derive(Default)]
+struct PrefetchState {
+    // Source chapter -> when it triggered a prefetch
+    triggered: HashMap<String, Instant>,
+    // Chapter -> at-home data the prefetched pages were fetched from
+    servers: HashMap<String, (Instant, AtHomeServer)>,
+}
+
+impl PrefetchState {
+    /// Claim a source chapter; false if it already triggered a prefetch recently
+    fn claim(&mut self, chapter_id: &str, now: Instant) -> bool {
+        self.triggered
+            .retain(|_, at| now.duration_since(*at) < TRIGGER_TTL);
+        if self.triggered.contains_key(chapter_id) {
+            return false;
+        }
+        self.triggered.insert(chapter_id.to_string(), now);
+        true
+    }
+
+    fn release(&mut self, chapter_id: &str) {
+        self.triggered.remove(chapter_id);
+    }
+
+    fn server(&mut self, chapter_id: &str, now: Instant) -> Option<AtHomeServer> {
+        self.servers
+            .retain(|_, (at, _)| now.duration_since(*at) < AT_HOME_TTL);
+        self.servers.get(chapter_id).map(|(_, server)| server.clone())
+    }
+
+    fn insert_server(&mut self, chapter_id: &str, server: AtHomeServer, now: Instant) {
+        self.servers.insert(chapter_id.to_string(), (now, server));
+    }
+}
+
+fn chapter_number(chapter: &FeedChapter) -> Option<f64> {
+    chapter.attributes.chapter.as_deref()?.trim().parse().ok()
+}
+
+/// The next readable chapter after `current` in the same language, preferring
+/// an upload by one of the current chapter's scanlation groups
+pub fn pick_next_chapter<'a>(current: &FeedChapter, feed: &'a [FeedChapter]) -> Option<&'a FeedChapter> {
+    let current_number = chapter_number(current)?;
+    let current_groups: Vec<&str> = current.group_ids().collect();
+
+    let candidates: Vec<(f64, &FeedChapter)> = feed
+        .iter()
+        .filter(|c| c.id != current.id)
+        .filter(|c| c.attributes.translated_language == current.attributes.translated_language)
+        .filter(|c| c.attributes.external_url.is_none())
+        .filter_map(|c| chapter_number(c).map(|n| (n, c)))
+        .filter(|(n, _)| *n > current_number)
+        .collect();
+
+    let next_number = candidates
+        .iter()
+        .map(|(n, _)| *n)
+        .min_by(|a, b| a.total_cmp(b))?;
+    let mut same_number = candidates
+        .into_iter()
+        .filter(|(n, _)| *n == next_number)
+        .map(|(_, c)| c);
+    let first = same_number.next()?;
+    let same_group = std::iter::once(first)
+        .chain(same_number)
+        .find(|c| c.group_ids().any(|g| current_groups.contains(&g)));
+    Some(same_group.unwrap_or(first))
+}
+
+/// Warms the image cache with the start of the next chapter while a user is
+/// still reading the current one
+#[derive(Clone)]
+pub struct Prefetcher {
+    client: reqwest::Client,
+    downloader: Downloader,
+    proxy: ImageProxy,
+    state: Arc<Mutex<PrefetchState>>,
+    permits: Arc<Semaphore>,
+    threshold: f32,
+    pages: usize,
+    quality: Quality,
+}
+
+impl Prefetcher {
+    pub fn new(client: reqwest::Client, downloader: Downloader, proxy: ImageProxy) -> Self {
+        let threshold = env::var("PREFETCH_THRESHOLD_PERCENT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(70.0);
+        let pages = env::var("PREFETCH_PAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+        let max_concurrent = env::var("PREFETCH_MAX_CONCURRENT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(4);
+        let quality = Quality::from_param(env::var("PREFETCH_QUALITY").ok().as_deref());
+
+        Self {
+            client,
+            downloader,
+            proxy,
+            state: Arc::new(Mutex::new(PrefetchState::default())),
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            threshold,
+            pages,
+            quality,
+        }
+    }
+
+    /// Called on every progress update. Starts a background prefetch of the
+    /// chapter after `chapter_id` once the reader is past the threshold.
+    /// Returns whether a prefetch was started.
+    pub fn on_progress(&self, chapter_id: &str, progress_percentage: f32) -> bool {
+        if self.pages == 0 || progress_percentage < self.threshold {
+            return false;
+        }
+        if !self.state.lock().unwrap().claim(chapter_id, Instant::now()) {
+            return false;
+        }
+        // At the concurrency cap: give the claim back so a later update can retry
+        let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+            self.state.lock().unwrap().release(chapter_id);
+            return false;
+        };
+
+        let prefetcher = self.clone();
+        let chapter_id = chapter_id.to_string();
+        tokio::spawn(async move {
+            let _permit = permit;
+            match prefetcher.prefetch_next(&chapter_id).await {
+                Ok(Some((next_id, warmed))) => tracing::info!(
+                    "🔮 Prefetched {} pages of chapter {} (after {})",
+                    warmed,
+                    next_id,
+                    chapter_id
+                ),
+                Ok(None) => tracing::debug!("No next chapter to prefetch after {}", chapter_id),
+                Err(e) => tracing::warn!("⚠️  Prefetch after chapter {} failed: {}", chapter_id, e),
+            }
+        });
+        true
+    }
+
+    /// At-home data for a chapter, reusing the node the prefetch warmed so
+    /// page URLs match what is already in the image cache
+    pub async fn at_home_server(&self, chapter_id: &str) -> PrefetchResult<(AtHomeServer, bool)> {
+        if let Some(server) = self.state.lock().unwrap().server(chapter_id, Instant::now()) {
+            return Ok((server, true));
+        }
+        let server = self.downloader.fetch_at_home_server(chapter_id).await?;
+        self.state
+            .lock()
+            .unwrap()
+            .insert_server(chapter_id, server.clone(), Instant::now());
+        Ok((server, false))
+    }
+
+    async fn prefetch_next(&self, chapter_id: &str) -> PrefetchResult<Option<(String, usize)>> {
+        let current = self.fetch_chapter(chapter_id).await?;
+        let Some(next_id) = self.find_next_chapter(&current).await? else {
+            return Ok(None);
+        };
+
+        let (server, _) = self.at_home_server(&next_id).await?;
+        let mut warmed = 0;
+        for filename in server.images(self.quality).iter().take(self.pages) {
+            let url = server.image_url(self.quality, filename);
+            match self.proxy.fetch_original(&url).await {
+                Ok(_) => warmed += 1,
+                Err(e) => tracing::debug!("Prefetch of {} failed: {}", url, e),
+            }
+        }
+        Ok(Some((next_id, warmed)))
+    }
+
+    async fn fetch_chapter(&self, chapter_id: &str) -> PrefetchResult<FeedChapter> {
+        let url = format!("https://api.mangadex.org/chapter/{}", chapter_id);
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(format!("MangaDex chapter lookup returned HTTP {}", response.status()).into());
+        }
+        Ok(response.json::<ChapterResponse>().await?.data)
+    }
+
+    async fn find_next_chapter(&self, current: &FeedChapter) -> PrefetchResult<Option<String>> {
+        let Some(manga_id) = current
+            .relationships
+            .iter()
+            .find(|r| r.rel_type == "manga")
+            .map(|r| r.id.clone())
+        else {
+            return Ok(None);
+        };
+
+        // The feed is sorted by chapter number, so stop at the first page
+        // that contains a later chapter
+        let languages = [current.attributes.translated_language.clone()];
+        let next = walk_feed(&self.client, &manga_id, &languages, |feed| {
+            match pick_next_chapter(current, &feed) {
+                Some(next) => ControlFlow::Break(next.id.clone()),
+                None => ControlFlow::Continue(()),
+            }
+        })
+        .await?;
+        Ok(next)
+    }
+}
+
+/// At-home data for a chapter: GET /api/chapter/:chapter_id/at-home
+///
+/// Readers should build page URLs from this response and load them through
+/// `/api/image`, so pages warmed by the prefetcher are served from cache.
+pub async fn at_home_handler(
+    State(prefetcher): State<Prefetcher>,
+    Path(chapter_id): Path<String>,
+) -> impl IntoResponse {
+    match prefetcher.at_home_server(&chapter_id).await {
+        Ok((server, prefetched)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "prefetched": prefetched,
+                "baseUrl": server.base_url,
+                "chapter": server.chapter
+            })),
+        ),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({
+                "success": false,
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(id: &str, number: &str, language: &str, group: &str) -> FeedChapter {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "attributes": {
+                "chapter": number,
+                "translatedLanguage": language,
+                "pages": 20,
+                "externalUrl": null
+            },
+            "relationships": [{ "id": group, "type": "scanlation_group" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pick_next_chapter_prefers_same_group() {
+        let current = chapter("c5", "5", "en", "g1");
+        let feed = vec![
+            chapter("c4", "4", "en", "g1"),
+            current.clone(),
+            chapter("c6-other", "6", "en", "g2"),
+            chapter("c6", "6", "en", "g1"),
+            chapter("c7", "7", "en", "g1"),
+        ];
+        assert_eq!(pick_next_chapter(&current, &feed).unwrap().id, "c6");
+    }
+
+    #[test]
+    fn test_pick_next_chapter_handles_decimals_and_language() {
+        let current = chapter("c5", "5", "en", "g1");
+        let feed = vec![
+            chapter("c5.5-es", "5.5", "es", "g1"),
+            chapter("c10", "10", "en", "g2"),
+            chapter("c5.5", "5.5", "en", "g2"),
+        ];
+        assert_eq!(pick_next_chapter(&current, &feed).unwrap().id, "c5.5");
+        assert!(pick_next_chapter(&chapter("c10", "10", "en", "g2"), &feed).is_none());
+    }
+
+    #[test]
+    fn test_claim_dedups_until_released() {
+        let mut state = PrefetchState::default();
+        let now = Instant::now();
+        assert!(state.claim("c1", now));
+        assert!(!state.claim("c1", now));
+        assert!(state.claim("c2", now));
+        state.release("c1");
+        assert!(state.claim("c1", now));
+        assert!(state.claim("c2", now + TRIGGER_TTL));
+    }
+}
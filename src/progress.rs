@@ -1,8 +1,12 @@
 use mongodb::bson::{doc, oid::ObjectId};
 use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::env;
 
+use crate::auto_download::AutoDownloadRule;
+use crate::pagination::{and_filter, keyset_sort, next_page_cursor, parse_cursor, PaginatedResponse};
+
 /// Reading progress for a specific manga
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ReadingProgress {
@@ -65,6 +69,8 @@ pub struct LibraryEntry {
     pub added_at: String,
     pub updated_at: String,
     pub progress: Option<ReadingProgress>,
+    #[serde(default)]
+    pub auto_download: Option<AutoDownloadRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -77,12 +83,68 @@ pub enum ReadingStatus {
     Dropped,
 }
 
+/// Combine two reading positions of one user for the same series, keeping
+/// the most recent position and the totals of both
+pub fn merge_progress(keep: ReadingProgress, other: ReadingProgress) -> ReadingProgress {
+    let id = keep.id;
+    let (mut latest, older) = if other.last_read_at > keep.last_read_at {
+        (other, keep)
+    } else {
+        (keep, other)
+    };
+    latest.id = id;
+    latest.started_at = latest.started_at.min(older.started_at);
+    latest.completed |= older.completed;
+    latest.reading_time_minutes = latest.reading_time_minutes.saturating_add(older.reading_time_minutes);
+    latest
+}
+
+/// Combine two library entries of one user for the same series. `keep`
+/// wins where both have a value, except that any real reading status beats
+/// plan-to-read.
+pub fn merge_library_entries(keep: LibraryEntry, other: LibraryEntry) -> LibraryEntry {
+    let mut merged = keep;
+    merged.favorite |= other.favorite;
+    merged.rating = merged.rating.or(other.rating);
+    merged.notes = merged.notes.or(other.notes);
+    merged.manga_cover = merged.manga_cover.or(other.manga_cover);
+    merged.auto_download = merged.auto_download.or(other.auto_download);
+    if merged.status == ReadingStatus::PlanToRead {
+        merged.status = other.status;
+    }
+    for tag in other.tags {
+        if !merged.tags.contains(&tag) {
+            merged.tags.push(tag);
+        }
+    }
+    merged.added_at = merged.added_at.min(other.added_at);
+    merged.updated_at = merged.updated_at.max(other.updated_at);
+    merged.progress = match (merged.progress.take(), other.progress) {
+        (Some(keep), Some(other)) => Some(merge_progress(keep, other)),
+        (keep, other) => keep.or(other),
+    };
+    merged
+}
+
+/// How many records moved from one manga id to another
+#[derive(Debug, Serialize, Default)]
+pub struct MangaReassignment {
+    pub library: u64,
+    pub progress: u64,
+    pub bookmarks: u64,
+    pub history: u64,
+}
+
 #[derive(Clone)]
 pub struct ProgressService {
     db: Database,
 }
 
 impl ProgressService {
+    pub fn db(&self) -> &Database {
+        &self.db
+    }
+
     pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let mongodb_uri =
             env::var("MONGODB_URI").map_err(|_| "MONGODB_URI environment variable not set")?;
@@ -238,6 +300,7 @@ impl ProgressService {
             added_at: now.clone(),
             updated_at: now,
             progress: None,
+            auto_download: None,
         };
 
         library.insert_one(&entry).await?;
@@ -389,6 +452,206 @@ impl ProgressService {
         Ok(())
     }
 
+    /// Set or clear a user's 1-5 star rating of a library entry
+    pub async fn set_rating(
+        &self,
+        user_id: &str,
+        manga_id: &str,
+        rating: Option<u8>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if rating.is_some_and(|r| !(1..=5).contains(&r)) {
+            return Err("Rating must be between 1 and 5".into());
+        }
+        let library = self.library_collection();
+
+        let result = library
+            .update_one(
+                doc! {
+                    "user_id": user_id,
+                    "manga_id": manga_id
+                },
+                doc! {
+                    "$set": {
+                        "rating": rating.map(i32::from),
+                        "updated_at": chrono::Utc::now().to_rfc3339()
+                    }
+                },
+            )
+            .await?;
+
+        Ok(result.matched_count > 0)
+    }
+
+    /// Set or clear the auto-download rule of a library entry
+    pub async fn set_auto_download_rule(
+        &self,
+        user_id: &str,
+        manga_id: &str,
+        rule: Option<AutoDownloadRule>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let library = self.library_collection();
+
+        let rule = match rule {
+            Some(rule) => mongodb::bson::to_bson(&rule)?,
+            None => mongodb::bson::Bson::Null,
+        };
+
+        let result = library
+            .update_one(
+                doc! {
+                    "user_id": user_id,
+                    "manga_id": manga_id
+                },
+                doc! {
+                    "$set": {
+                        "auto_download": rule,
+                        "updated_at": chrono::Utc::now().to_rfc3339()
+                    }
+                },
+            )
+            .await?;
+
+        Ok(result.matched_count > 0)
+    }
+
+    /// Library entries being read that have auto-downloads switched on
+    pub async fn get_auto_download_entries(
+        &self,
+    ) -> Result<Vec<LibraryEntry>, Box<dyn std::error::Error>> {
+        let library = self.library_collection();
+
+        let mut cursor = library
+            .find(doc! {
+                "status": "reading",
+                "auto_download.enabled": true
+            })
+            .await?;
+
+        let mut entries = Vec::new();
+        while cursor.advance().await? {
+            entries.push(cursor.deserialize_current()?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Which of `manga_ids` are in the user's library
+    pub async fn library_manga_ids(
+        &self,
+        user_id: &str,
+        manga_ids: &[String],
+    ) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        let library = self.library_collection();
+
+        let mut cursor = library
+            .find(doc! {
+                "user_id": user_id,
+                "manga_id": { "$in": manga_ids }
+            })
+            .await?;
+
+        let mut ids = HashSet::new();
+        while cursor.advance().await? {
+            ids.insert(cursor.deserialize_current()?.manga_id);
+        }
+
+        Ok(ids)
+    }
+
+    /// Every entry in a user's library
+    pub async fn library_entries(&self, user_id: &str) -> Result<Vec<LibraryEntry>, Box<dyn std::error::Error>> {
+        let mut cursor = self.library_collection().find(doc! { "user_id": user_id }).await?;
+
+        let mut entries = Vec::new();
+        while cursor.advance().await? {
+            entries.push(cursor.deserialize_current()?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Manga the user has opened, from reading history
+    pub async fn history_manga_ids(&self, user_id: &str) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        let ids = self
+            .history_collection()
+            .distinct("manga_id", doc! { "user_id": user_id })
+            .await?;
+
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| id.as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Point every library entry, reading position, bookmark and history
+    /// entry for `from` at `to`. Users who had both keep one library entry
+    /// and one reading position, merged.
+    pub async fn reassign_manga(&self, from: &str, to: &str) -> Result<MangaReassignment, Box<dyn std::error::Error>> {
+        let mut moved = MangaReassignment::default();
+
+        let library = self.library_collection();
+        let mut cursor = library.find(doc! { "manga_id": from }).await?;
+        let mut entries: Vec<LibraryEntry> = Vec::new();
+        while cursor.advance().await? {
+            entries.push(cursor.deserialize_current()?);
+        }
+        for entry in entries {
+            let old_id = entry.id;
+            let existing = library
+                .find_one(doc! { "user_id": &entry.user_id, "manga_id": to })
+                .await?;
+            let mut merged = match existing {
+                Some(existing) => {
+                    library.delete_one(doc! { "_id": old_id }).await?;
+                    merge_library_entries(existing, entry)
+                }
+                None => entry,
+            };
+            merged.manga_id = to.to_string();
+            if let Some(progress) = &mut merged.progress {
+                progress.manga_id = to.to_string();
+            }
+            library.replace_one(doc! { "_id": merged.id }, &merged).await?;
+            moved.library += 1;
+        }
+
+        let progress = self.progress_collection();
+        let mut cursor = progress.find(doc! { "manga_id": from }).await?;
+        let mut positions: Vec<ReadingProgress> = Vec::new();
+        while cursor.advance().await? {
+            positions.push(cursor.deserialize_current()?);
+        }
+        for position in positions {
+            let old_id = position.id;
+            let existing = progress
+                .find_one(doc! { "user_id": &position.user_id, "manga_id": to })
+                .await?;
+            let mut merged = match existing {
+                Some(existing) => {
+                    progress.delete_one(doc! { "_id": old_id }).await?;
+                    merge_progress(existing, position)
+                }
+                None => position,
+            };
+            merged.manga_id = to.to_string();
+            progress.replace_one(doc! { "_id": merged.id }, &merged).await?;
+            moved.progress += 1;
+        }
+
+        let update = doc! { "$set": { "manga_id": to } };
+        moved.bookmarks = self
+            .bookmarks_collection()
+            .update_many(doc! { "manga_id": from }, update.clone())
+            .await?
+            .modified_count;
+        moved.history = self
+            .history_collection()
+            .update_many(doc! { "manga_id": from }, update)
+            .await?
+            .modified_count;
+        Ok(moved)
+    }
+
     /// Remove manga from library
     pub async fn remove_from_library(
         &self,
@@ -628,26 +891,40 @@ impl ProgressService {
         Ok(entry)
     }
 
-    /// Get reading history
+    /// Get a page of reading history, newest first. Continues after `after`
+    /// when given, otherwise skips to `page`.
     pub async fn get_reading_history(
         &self,
         user_id: &str,
+        page: u32,
         limit: u32,
-    ) -> Result<Vec<ReadingHistoryEntry>, Box<dyn std::error::Error>> {
+        after: Option<&str>,
+    ) -> Result<PaginatedResponse<ReadingHistoryEntry>, Box<dyn std::error::Error>> {
         let history = self.history_collection();
+        let page = page.max(1);
+
+        let mut filter = doc! { "user_id": user_id };
+        let total_items = history.count_documents(filter.clone()).await?;
+        let mut skip = (page - 1) as u64 * limit as u64;
+        if let Some(cursor) = parse_cursor(after)? {
+            filter = and_filter(filter, Some(cursor.keyset_filter("timestamp", -1)?));
+            skip = 0;
+        }
 
         let mut cursor = history
-            .find(doc! { "user_id": user_id })
-            .sort(doc! { "timestamp": -1 })
-            .limit(limit as i64)
+            .find(filter)
+            .sort(keyset_sort("timestamp", -1))
+            .skip(skip)
+            .limit(limit as i64 + 1)
             .await?;
 
         let mut results = Vec::new();
         while cursor.advance().await? {
             results.push(cursor.deserialize_current()?);
         }
+        let next_cursor = next_page_cursor(&mut results, limit as usize, "timestamp");
 
-        Ok(results)
+        Ok(PaginatedResponse::new(results, page, limit, total_items).with_next_cursor(next_cursor))
     }
 
     /// Get continue reading suggestions (most recent unfinished manga)
@@ -684,3 +961,76 @@ pub struct ReadingStats {
     pub plan_to_read: u32,
     pub total_reading_time_minutes: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(last_read_at: &str, started_at: &str, minutes: u32) -> ReadingProgress {
+        ReadingProgress {
+            id: None,
+            user_id: "u".to_string(),
+            manga_id: "m".to_string(),
+            current_chapter: last_read_at.to_string(),
+            current_page: 1,
+            total_pages: 10,
+            progress_percentage: 10.0,
+            last_read_at: last_read_at.to_string(),
+            started_at: started_at.to_string(),
+            completed: false,
+            reading_time_minutes: minutes,
+        }
+    }
+
+    fn entry(status: ReadingStatus, rating: Option<u8>, tags: &[&str], added_at: &str) -> LibraryEntry {
+        LibraryEntry {
+            id: None,
+            user_id: "u".to_string(),
+            manga_id: "m".to_string(),
+            manga_title: "Berserk".to_string(),
+            manga_cover: None,
+            status,
+            rating,
+            favorite: false,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            notes: None,
+            added_at: added_at.to_string(),
+            updated_at: added_at.to_string(),
+            progress: None,
+            auto_download: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_progress_keeps_latest_position_and_totals() {
+        let keep_id = ObjectId::new();
+        let keep = ReadingProgress {
+            id: Some(keep_id),
+            ..progress("2024-01-01T00:00:00+00:00", "2023-06-01T00:00:00+00:00", 30)
+        };
+        let newer = progress("2024-03-01T00:00:00+00:00", "2024-02-01T00:00:00+00:00", 15);
+        let merged = merge_progress(keep, newer);
+        assert_eq!(merged.id, Some(keep_id));
+        assert_eq!(merged.current_chapter, "2024-03-01T00:00:00+00:00");
+        assert_eq!(merged.started_at, "2023-06-01T00:00:00+00:00");
+        assert_eq!(merged.reading_time_minutes, 45);
+    }
+
+    #[test]
+    fn test_merge_library_entries_prefers_kept_entry_but_real_status() {
+        let keep = entry(ReadingStatus::PlanToRead, None, &["dark"], "2024-02-01T00:00:00+00:00");
+        let mut other = entry(ReadingStatus::Reading, Some(5), &["dark", "reread"], "2023-01-01T00:00:00+00:00");
+        other.favorite = true;
+        let merged = merge_library_entries(keep, other);
+        assert_eq!(merged.status, ReadingStatus::Reading);
+        assert_eq!(merged.rating, Some(5));
+        assert!(merged.favorite);
+        assert_eq!(merged.tags, vec!["dark", "reread"]);
+        assert_eq!(merged.added_at, "2023-01-01T00:00:00+00:00");
+
+        let keep = entry(ReadingStatus::Completed, Some(3), &[], "2024-02-01T00:00:00+00:00");
+        let other = entry(ReadingStatus::Dropped, Some(1), &[], "2024-02-01T00:00:00+00:00");
+        let merged = merge_library_entries(keep, other);
+        assert_eq!((merged.status, merged.rating), (ReadingStatus::Completed, Some(3)));
+    }
+}
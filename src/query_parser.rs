@@ -0,0 +1,345 @@
+use crate::ai_service::{AIService, StructuredSearchQuery};
+use crate::manga_service::MangaService;
+use crate::search::{AdvancedSearchParams, SortField, SortOrder};
+use crate::search_index::{normalize, words};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Statuses the catalog uses
+pub const STATUSES: [&str; 4] = ["ongoing", "completed", "hiatus", "cancelled"];
+
+/// Words that turn the tag after them into an exclusion
+const NEGATIONS: &[&str] = &["no", "not", "without", "except", "excluding", "exclude", "non", "minus"];
+
+const STATUS_PHRASES: &[(&str, &str)] = &[
+    ("still running", "ongoing"),
+    ("ongoing", "ongoing"),
+    ("publishing", "ongoing"),
+    ("completed", "completed"),
+    ("complete", "completed"),
+    ("finished", "completed"),
+    ("ended", "completed"),
+    ("hiatus", "hiatus"),
+    ("cancelled", "cancelled"),
+    ("canceled", "cancelled"),
+];
+
+/// Longer phrases first, so "recently updated" wins over "recent"
+const SORT_PHRASES: &[(&str, SortField, SortOrder)] = &[
+    ("most popular", SortField::Popularity, SortOrder::Desc),
+    ("top rated", SortField::Rating, SortOrder::Desc),
+    ("highest rated", SortField::Rating, SortOrder::Desc),
+    ("best rated", SortField::Rating, SortOrder::Desc),
+    ("recently updated", SortField::UpdatedAt, SortOrder::Desc),
+    ("popular", SortField::Popularity, SortOrder::Desc),
+    ("trending", SortField::Popularity, SortOrder::Desc),
+    ("best", SortField::Rating, SortOrder::Desc),
+    ("newest", SortField::CreatedAt, SortOrder::Desc),
+    ("latest", SortField::CreatedAt, SortOrder::Desc),
+    ("recent", SortField::CreatedAt, SortOrder::Desc),
+    ("oldest", SortField::CreatedAt, SortOrder::Asc),
+    ("alphabetical", SortField::Title, SortOrder::Asc),
+];
+
+/// Words that carry no search meaning once tags, status, years and sort
+/// have been taken out
+const FILLER: &[&str] = &[
+    "a", "an", "the", "and", "or", "of", "with", "about", "from", "in", "on", "for", "by", "to", "that", "is",
+    "are", "was", "were", "which", "where", "than", "me", "i", "want", "show", "find", "some", "any", "something",
+    "like", "manga", "series", "comic", "comics", "story", "stories", "please", "year", "years", "released",
+    "published", "ones", "one", "between",
+];
+
+/// Natural-language search turned into filters
+#[derive(Debug, Serialize)]
+pub struct ParsedSearch {
+    pub params: AdvancedSearchParams,
+    /// `llm` or `rules`
+    pub parser: &'static str,
+    /// Tags the language model produced that the catalog does not have
+    pub unknown_tags: Vec<String>,
+}
+
+fn empty_params() -> AdvancedSearchParams {
+    AdvancedSearchParams {
+        sort_by: None,
+        sort_order: None,
+        page: None,
+        limit: None,
+        ..Default::default()
+    }
+}
+
+fn year(word: &str) -> Option<u16> {
+    let year: u16 = word.parse().ok().filter(|_| word.len() == 4)?;
+    (1900..=2100).contains(&year).then_some(year)
+}
+
+/// Unconsumed positions where `phrase` starts; the last word may carry a
+/// plural "s"
+fn find_phrase(words: &[String], used: &[bool], phrase: &[String]) -> Vec<usize> {
+    if phrase.is_empty() || phrase.len() > words.len() {
+        return Vec::new();
+    }
+    (0..=words.len() - phrase.len())
+        .filter(|&i| {
+            phrase.iter().enumerate().all(|(j, p)| {
+                let word = &words[i + j];
+                !used[i + j] && (word == p || (j == phrase.len() - 1 && word.strip_suffix('s') == Some(p.as_str())))
+            })
+        })
+        .collect()
+}
+
+/// Keyword rules for when no language model is configured (or it fails).
+/// Catches tags from the catalog vocabulary with "no"/"without"
+/// exclusions, status words, year ranges and a few sort phrases; whatever
+/// is left becomes the text query.
+pub fn parse_with_rules(text: &str, vocabulary: &[String]) -> AdvancedSearchParams {
+    let words = words(text);
+    let mut used = vec![false; words.len()];
+    let mut params = empty_params();
+    let mut tags: Vec<String> = Vec::new();
+    let mut exclude_tags: Vec<String> = Vec::new();
+    let mut statuses: Vec<String> = Vec::new();
+
+    let mut by_length: Vec<(&String, Vec<String>)> = vocabulary.iter().map(|tag| (tag, self::words(tag))).collect();
+    by_length.sort_by_key(|(_, phrase)| std::cmp::Reverse(phrase.len()));
+    for (tag, phrase) in &by_length {
+        for start in find_phrase(&words, &used, phrase) {
+            used[start..start + phrase.len()].iter_mut().for_each(|u| *u = true);
+            let negated = start > 0 && NEGATIONS.contains(&words[start - 1].as_str());
+            let target = if negated {
+                used[start - 1] = true;
+                &mut exclude_tags
+            } else {
+                &mut tags
+            };
+            if !target.contains(tag) {
+                target.push((*tag).clone());
+            }
+        }
+    }
+
+    for (phrase, status) in STATUS_PHRASES {
+        let phrase = self::words(phrase);
+        for start in find_phrase(&words, &used, &phrase) {
+            used[start..start + phrase.len()].iter_mut().for_each(|u| *u = true);
+            // "not completed" says too little to pick a status
+            let negated = start > 0 && NEGATIONS.contains(&words[start - 1].as_str());
+            if negated {
+                used[start - 1] = true;
+            } else if !statuses.iter().any(|s| s == status) {
+                statuses.push(status.to_string());
+            }
+        }
+    }
+
+    for i in 0..words.len() {
+        if used[i] {
+            continue;
+        }
+        if let Some(decade) = words[i].strip_suffix('s').and_then(year).filter(|y| y % 10 == 0) {
+            used[i] = true;
+            params.year_from = Some(decade);
+            params.year_to = Some(decade + 9);
+            continue;
+        }
+        let Some(y) = year(&words[i]) else {
+            continue;
+        };
+        used[i] = true;
+        let before = if i > 0 { words[i - 1].as_str() } else { "" };
+        let before_that = if i > 1 { words[i - 2].as_str() } else { "" };
+        let after = words.get(i + 1).map(String::as_str).unwrap_or_default();
+
+        // "2010 to 2015", "2010-2015", "between 2010 and 2015"
+        let range_end = match after {
+            "to" | "and" | "through" | "until" => words.get(i + 2).and_then(|w| year(w)).map(|end| (end, 2)),
+            _ => year(after).map(|end| (end, 1)),
+        };
+        if let Some((end, width)) = range_end {
+            used[i + 1..=i + width].iter_mut().for_each(|u| *u = true);
+            params.year_from = Some(y.min(end));
+            params.year_to = Some(y.max(end));
+            continue;
+        }
+        // "after 2015", "newer than 2015", "2015 onwards", "in 2015"
+        let (from, to, words_before, word_after) = match (before_that, before, after) {
+            (_, "after" | "post", _) => (Some(y + 1), None, 1, false),
+            ("newer" | "later", "than", _) => (Some(y + 1), None, 2, false),
+            (_, "since" | "from", _) => (Some(y), None, 1, false),
+            (_, "before" | "pre", _) => (None, Some(y - 1), 1, false),
+            ("older" | "earlier", "than", _) => (None, Some(y - 1), 2, false),
+            (_, "until" | "till" | "through", _) => (None, Some(y), 1, false),
+            (_, _, "onwards" | "onward" | "later" | "newer") => (Some(y), None, 0, true),
+            (_, _, "earlier" | "older") => (None, Some(y), 0, true),
+            _ => (Some(y), Some(y), 0, false),
+        };
+        used[i - words_before..i].iter_mut().for_each(|u| *u = true);
+        if word_after {
+            used[i + 1] = true;
+        }
+        if let Some(from) = from {
+            params.year_from = Some(from);
+        }
+        if let Some(to) = to {
+            params.year_to = Some(to);
+        }
+    }
+
+    for (phrase, field, order) in SORT_PHRASES {
+        let phrase = self::words(phrase);
+        if let Some(start) = find_phrase(&words, &used, &phrase).first() {
+            used[*start..*start + phrase.len()].iter_mut().for_each(|u| *u = true);
+            if params.sort_by.is_none() {
+                params.sort_by = Some(field.clone());
+                params.sort_order = Some(order.clone());
+            }
+        }
+    }
+
+    let rest: Vec<&str> = words
+        .iter()
+        .zip(&used)
+        .filter(|(word, used)| !**used && !FILLER.contains(&word.as_str()) && !NEGATIONS.contains(&word.as_str()))
+        .map(|(word, _)| word.as_str())
+        .collect();
+    params.query = (!rest.is_empty()).then(|| rest.join(" "));
+    params.tags = (!tags.is_empty()).then_some(tags);
+    params.exclude_tags = (!exclude_tags.is_empty()).then_some(exclude_tags);
+    params.status = (!statuses.is_empty()).then_some(statuses);
+    params
+}
+
+/// Map model output onto the catalog: tags are matched to the vocabulary
+/// ignoring case and accents, statuses to `STATUSES`, and a reversed year
+/// range is put right. Returns the tags that matched nothing.
+pub fn from_structured(parsed: StructuredSearchQuery, vocabulary: &[String]) -> (AdvancedSearchParams, Vec<String>) {
+    let canonical: HashMap<String, &String> = vocabulary.iter().map(|tag| (normalize(tag), tag)).collect();
+    let mut unknown = Vec::new();
+    let mut known = |requested: Vec<String>| -> Option<Vec<String>> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in requested {
+            match canonical.get(&normalize(tag.trim())) {
+                Some(tag) if !tags.contains(tag) => tags.push((*tag).clone()),
+                Some(_) => {}
+                None => unknown.push(tag),
+            }
+        }
+        (!tags.is_empty()).then_some(tags)
+    };
+
+    let mut params = empty_params();
+    params.tags = known(parsed.tags);
+    params.exclude_tags = known(parsed.exclude_tags);
+    let statuses: Vec<String> = parsed
+        .status
+        .iter()
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| STATUSES.contains(&s.as_str()))
+        .collect();
+    params.status = (!statuses.is_empty()).then_some(statuses);
+    params.query = parsed.query.map(|q| q.trim().to_string()).filter(|q| !q.is_empty());
+    params.year_from = parsed.year_from;
+    params.year_to = parsed.year_to;
+    if let (Some(from), Some(to)) = (params.year_from, params.year_to)
+        && from > to
+    {
+        params.year_from = Some(to);
+        params.year_to = Some(from);
+    }
+    params.sort_by = parsed.sort_by;
+    params.sort_order = parsed.sort_order;
+    (params, unknown)
+}
+
+/// Turns sentences like "completed romance from after 2015, no harem" into
+/// `AdvancedSearchParams`, with a language model when one is configured
+#[derive(Clone)]
+pub struct QueryParser {
+    manga_service: MangaService,
+    ai: Option<AIService>,
+}
+
+impl QueryParser {
+    pub fn new(manga_service: MangaService, ai: Option<AIService>) -> Self {
+        Self { manga_service, ai }
+    }
+
+    pub async fn parse(&self, text: &str) -> Result<ParsedSearch, Box<dyn std::error::Error>> {
+        let vocabulary = self.manga_service.tag_vocabulary().await?;
+        if let Some(ai) = &self.ai {
+            match ai.parse_search_query(text, &vocabulary).await {
+                Ok(parsed) => {
+                    let (params, unknown_tags) = from_structured(parsed, &vocabulary);
+                    return Ok(ParsedSearch {
+                        params,
+                        parser: "llm",
+                        unknown_tags,
+                    });
+                }
+                Err(e) => tracing::warn!("⚠️  LLM query parsing failed, using rules: {}", e),
+            }
+        }
+        Ok(ParsedSearch {
+            params: parse_with_rules(text, &vocabulary),
+            parser: "rules",
+            unknown_tags: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vocabulary() -> Vec<String> {
+        ["Romance", "Harem", "Slice of Life", "Sci-Fi", "Action", "Isekai"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rules_parse_tags_status_years_and_exclusions() {
+        let params = parse_with_rules("completed romance from after 2015, no harem", &vocabulary());
+        assert_eq!(params.tags, Some(vec!["Romance".to_string()]));
+        assert_eq!(params.exclude_tags, Some(vec!["Harem".to_string()]));
+        assert_eq!(params.status, Some(vec!["completed".to_string()]));
+        assert_eq!((params.year_from, params.year_to), (Some(2016), None));
+        assert_eq!(params.query, None);
+
+        let text = "most popular slice of life sci-fi between 2010 and 2014 about cooking";
+        let params = parse_with_rules(text, &vocabulary());
+        assert_eq!(params.tags, Some(vec!["Slice of Life".to_string(), "Sci-Fi".to_string()]));
+        assert_eq!((params.year_from, params.year_to), (Some(2010), Some(2014)));
+        assert!(matches!(params.sort_by, Some(SortField::Popularity)));
+        assert_eq!(params.query.as_deref(), Some("cooking"));
+
+        let params = parse_with_rules("ongoing isekai from the 2000s without harems", &vocabulary());
+        assert_eq!((params.year_from, params.year_to), (Some(2000), Some(2009)));
+        assert_eq!(params.exclude_tags, Some(vec!["Harem".to_string()]));
+        assert_eq!(params.status, Some(vec!["ongoing".to_string()]));
+    }
+
+    #[test]
+    fn test_structured_output_is_checked_against_vocabulary() {
+        let parsed = StructuredSearchQuery {
+            query: Some("  ".to_string()),
+            tags: vec!["romance".to_string(), "Magical Girls".to_string()],
+            exclude_tags: vec!["HAREM".to_string()],
+            status: vec!["Completed".to_string(), "abandoned".to_string()],
+            year_from: Some(2020),
+            year_to: Some(2015),
+            ..Default::default()
+        };
+        let (params, unknown) = from_structured(parsed, &vocabulary());
+        assert_eq!(params.tags, Some(vec!["Romance".to_string()]));
+        assert_eq!(params.exclude_tags, Some(vec!["Harem".to_string()]));
+        assert_eq!(params.status, Some(vec!["completed".to_string()]));
+        assert_eq!((params.year_from, params.year_to), (Some(2015), Some(2020)));
+        assert_eq!(params.query, None);
+        assert_eq!(unknown, vec!["Magical Girls"]);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::content_filter::ContentFilter;
+use crate::embedding::{dot, normalized, EmbeddingProvider};
+use crate::manga_service::{Manga, MangaService};
+use crate::progress::{LibraryEntry, ProgressService, ReadingStatus};
+use mongodb::bson::doc;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Nearest neighbours considered before diversifying
+const CANDIDATES: usize = 100;
+
+/// Taste weight of a favorite
+const FAVORITE_WEIGHT: f32 = 1.0;
+
+/// Taste weight of a completed series
+const COMPLETED_WEIGHT: f32 = 0.5;
+
+/// Taste weight of a series only seen in reading history
+const HISTORY_WEIGHT: f32 = 0.3;
+
+/// How strongly tag overlap with earlier picks pushes a candidate down
+const DIVERSITY: f32 = 0.5;
+
+/// Library items listed as the reason for each recommendation
+const MAX_SOURCES: usize = 3;
+
+/// Ratings above the midpoint pull the taste vector towards a series,
+/// ratings below push it away
+fn rating_weight(rating: u8) -> f32 {
+    (rating.clamp(1, 5) as f32 - 2.5) / 2.5
+}
+
+/// How much one library entry says about a user's taste
+pub fn entry_weight(entry: &LibraryEntry) -> f32 {
+    let mut weight = entry.rating.map_or(0.0, rating_weight);
+    if entry.favorite {
+        weight += FAVORITE_WEIGHT;
+    }
+    if entry.status == ReadingStatus::Completed {
+        weight += COMPLETED_WEIGHT;
+    }
+    weight
+}
+
+/// A library item that led to a recommendation
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RecommendationSource {
+    pub manga_id: String,
+    pub title: String,
+    pub similarity: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Recommendation {
+    pub manga: Manga,
+    pub score: f32,
+    pub because: Vec<RecommendationSource>,
+}
+
+/// A series in the user's taste profile
+struct Seed {
+    manga_id: String,
+    title: String,
+    weight: f32,
+    embedding: Vec<f32>,
+}
+
+/// Weighted sum of the seeds' normalized embeddings
+fn taste_vector(seeds: &[Seed]) -> Option<Vec<f32>> {
+    let dimensions = seeds.first()?.embedding.len();
+    let mut taste = vec![0.0; dimensions];
+    for seed in seeds.iter().filter(|s| s.embedding.len() == dimensions) {
+        for (t, x) in taste.iter_mut().zip(&seed.embedding) {
+            *t += seed.weight * x;
+        }
+    }
+    normalized(&taste)
+}
+
+pub fn tag_jaccard(a: &[String], b: &[String]) -> f32 {
+    let a: HashSet<&String> = a.iter().collect();
+    let b: HashSet<&String> = b.iter().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(&b).count() as f32 / union as f32
+    }
+}
+
+/// The first `limit` candidates the user has not read yet
+fn unread(candidates: Vec<(Manga, f32)>, excluded: &[&String], limit: usize) -> Vec<(Manga, f32)> {
+    candidates
+        .into_iter()
+        .filter(|(manga, _)| !manga.manga_id.as_ref().is_some_and(|id| excluded.contains(&id)))
+        .take(limit)
+        .collect()
+}
+
+/// Greedily pick `limit` candidates, each time taking the best score after a
+/// penalty for sharing tags with what was already picked. Returns indices in
+/// pick order.
+pub fn diversify(candidates: &[(f32, Vec<String>)], limit: usize) -> Vec<usize> {
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut picked: Vec<usize> = Vec::new();
+    while picked.len() < limit && !remaining.is_empty() {
+        let adjusted = |i: usize| {
+            let overlap = picked
+                .iter()
+                .map(|&p| tag_jaccard(&candidates[i].1, &candidates[p].1))
+                .fold(0.0, f32::max);
+            candidates[i].0 - DIVERSITY * overlap
+        };
+        let (position, _) = remaining
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| adjusted(**a).total_cmp(&adjusted(**b)).then(b.cmp(a)))
+            .expect("remaining is not empty");
+        picked.push(remaining.remove(position));
+    }
+    picked
+}
+
+/// Recommends unread catalog series close to what a user rated, favorited,
+/// finished or read
+#[derive(Clone)]
+pub struct RecommendationService {
+    progress_service: ProgressService,
+    manga_service: MangaService,
+    embeddings: Arc<dyn EmbeddingProvider>,
+}
+
+impl RecommendationService {
+    pub fn new(
+        progress_service: ProgressService,
+        manga_service: MangaService,
+        embeddings: Arc<dyn EmbeddingProvider>,
+    ) -> Self {
+        Self {
+            progress_service,
+            manga_service,
+            embeddings,
+        }
+    }
+
+    pub async fn recommend(
+        &self,
+        user_id: &str,
+        limit: usize,
+        content: &ContentFilter,
+    ) -> Result<Vec<Recommendation>, Box<dyn std::error::Error>> {
+        let model = self.embeddings.model_id();
+        let library = self.progress_service.library_entries(user_id).await?;
+        let history = self.progress_service.history_manga_ids(user_id).await?;
+
+        let mut weights: HashMap<String, f32> = history
+            .iter()
+            .map(|manga_id| (manga_id.clone(), HISTORY_WEIGHT))
+            .collect();
+        for entry in &library {
+            // Explicit signals replace the weaker history signal
+            let weight = entry_weight(entry);
+            if weight != 0.0 {
+                weights.insert(entry.manga_id.clone(), weight);
+            }
+        }
+        let seeds = self.seeds(&weights, &model).await?;
+        let Some(taste) = taste_vector(&seeds) else {
+            return Ok(Vec::new());
+        };
+
+        // Anything in the library or history counts as already read
+        let excluded: Vec<&String> = library.iter().map(|e| &e.manga_id).chain(&history).collect();
+        let mut filter = doc! { "manga_id": { "$nin": &excluded } };
+        content.apply(&mut filter);
+        // Read series sit closest to the taste vector, so ask for enough
+        // neighbours that some survive the filter
+        let candidates = self
+            .manga_service
+            .semantic_search(&taste, &model, CANDIDATES + excluded.len(), filter)
+            .await?;
+        let candidates = unread(candidates, &excluded, CANDIDATES);
+
+        let scored: Vec<(f32, Vec<String>)> = candidates
+            .iter()
+            .map(|(manga, score)| (*score, manga.tags.clone().unwrap_or_default()))
+            .collect();
+        let mut candidates: Vec<Option<(Manga, f32)>> = candidates.into_iter().map(Some).collect();
+
+        let positive: Vec<&Seed> = seeds.iter().filter(|s| s.weight > 0.0).collect();
+        let recommendations = diversify(&scored, limit)
+            .into_iter()
+            .filter_map(|i| candidates[i].take())
+            .map(|(mut manga, score)| {
+                let embedding = manga.embedding.take().as_deref().and_then(normalized).unwrap_or_default();
+                let mut because: Vec<RecommendationSource> = positive
+                    .iter()
+                    .map(|seed| RecommendationSource {
+                        manga_id: seed.manga_id.clone(),
+                        title: seed.title.clone(),
+                        similarity: dot(&embedding, &seed.embedding),
+                    })
+                    .filter(|source| source.similarity > 0.0)
+                    .collect();
+                because.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+                because.truncate(MAX_SOURCES);
+                manga.localize(content.preferred_language.as_deref());
+                Recommendation { manga, score, because }
+            })
+            .collect();
+        Ok(recommendations)
+    }
+
+    /// Catalog embeddings for the weighted series, skipping any not yet
+    /// embedded with the current model
+    async fn seeds(&self, weights: &HashMap<String, f32>, model: &str) -> Result<Vec<Seed>, Box<dyn std::error::Error>> {
+        let ids: Vec<&String> = weights.keys().collect();
+        let mut cursor = self
+            .manga_service
+            .manga_collection()
+            .find(doc! { "manga_id": { "$in": ids }, "embedding_model": model })
+            .projection(doc! { "manga_id": 1, "title": 1, "embedding": 1 })
+            .await?;
+
+        let mut seeds = Vec::new();
+        while cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            let (Some(manga_id), Some(embedding)) = (manga.manga_id, manga.embedding.as_deref().and_then(normalized))
+            else {
+                continue;
+            };
+            seeds.push(Seed {
+                weight: weights[&manga_id],
+                title: manga.title.unwrap_or_default(),
+                manga_id,
+                embedding,
+            });
+        }
+        Ok(seeds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diversify_skips_near_duplicates() {
+        let candidates = vec![
+            (0.90, tags(&["action", "fantasy"])),
+            (0.89, tags(&["action", "fantasy"])),
+            (0.80, tags(&["romance"])),
+            (0.50, tags(&[])),
+        ];
+        assert_eq!(diversify(&candidates, 3), vec![0, 2, 3]);
+        assert_eq!(diversify(&candidates, 10).len(), 4);
+        assert!(diversify(&[], 5).is_empty());
+    }
+
+    #[test]
+    fn test_taste_follows_rating_weights() {
+        let seed = |id: &str, weight: f32, embedding: Vec<f32>| Seed {
+            manga_id: id.to_string(),
+            title: id.to_string(),
+            weight,
+            embedding,
+        };
+        let seeds = vec![seed("loved", 1.0, vec![1.0, 0.0]), seed("disliked", -0.6, vec![0.0, 1.0])];
+        let taste = taste_vector(&seeds).unwrap();
+        assert!(taste[0] > 0.0 && taste[1] < 0.0);
+
+        assert_eq!(rating_weight(5), 1.0);
+        assert!(rating_weight(1) < 0.0);
+        assert!(taste_vector(&[]).is_none());
+    }
+
+    #[test]
+    fn test_unread_never_returns_excluded_series() {
+        let manga = |id: &str| -> (Manga, f32) {
+            (serde_json::from_value(serde_json::json!({ "manga_id": id, "title": id })).unwrap(), 0.9)
+        };
+        let (read, dropped) = ("read".to_string(), "dropped".to_string());
+        let candidates = vec![manga("read"), manga("new"), manga("dropped"), manga("other"), manga("third")];
+        let ids: Vec<String> = unread(candidates, &[&read, &dropped], 2)
+            .into_iter()
+            .filter_map(|(manga, _)| manga.manga_id)
+            .collect();
+        assert_eq!(ids, vec!["new", "other"]);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::manga_service::{Manga, MangaListResponse, MangaService};
+use crate::progress::ProgressService;
+use crate::search::{query_identity, AdvancedSearchParams, SearchService};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::time::Duration;
+
+/// Results checked per evaluation; alerts cover the top of the result list
+const ALERT_RESULT_LIMIT: u32 = 100;
+
+/// Manga IDs remembered per saved search to tell new matches from old ones
+const MAX_SEEN_IDS: usize = 1000;
+
+/// A named search a user can replay and get alerts for
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedSearch {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub name: String,
+    pub params: AdvancedSearchParams,
+    // `query_identity` of the params; one saved search per query and user
+    pub query_hash: String,
+    pub alerts_enabled: bool,
+    #[serde(default)]
+    pub seen_manga_ids: Vec<String>,
+    pub last_run_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// A manga that newly matched a saved search
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchNotification {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub saved_search_id: ObjectId,
+    pub saved_search_name: String,
+    pub manga_id: String,
+    pub manga_title: Option<String>,
+    pub read: bool,
+    pub created_at: String,
+}
+
+/// Split results into manga not seen before and the updated seen list,
+/// newest results first and capped at `MAX_SEEN_IDS`
+pub fn diff_new_matches<'a>(seen: &[String], results: &'a [Manga]) -> (Vec<&'a Manga>, Vec<String>) {
+    let known: HashSet<&str> = seen.iter().map(String::as_str).collect();
+    let new: Vec<&Manga> = results
+        .iter()
+        .filter(|m| m.manga_id.as_deref().is_some_and(|id| !known.contains(id)))
+        .collect();
+
+    let mut updated: Vec<String> = results.iter().filter_map(|m| m.manga_id.clone()).collect();
+    let current: HashSet<String> = updated.iter().cloned().collect();
+    updated.extend(seen.iter().filter(|id| !current.contains(*id)).cloned());
+    updated.truncate(MAX_SEEN_IDS);
+    (new, updated)
+}
+
+#[derive(Clone)]
+pub struct SavedSearchService {
+    progress_service: ProgressService,
+    manga_service: MangaService,
+    search_service: SearchService,
+    interval: Duration,
+}
+
+impl SavedSearchService {
+    pub fn new(progress_service: ProgressService, manga_service: MangaService, search_service: SearchService) -> Self {
+        let interval_secs = env::var("SAVED_SEARCH_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+
+        Self {
+            progress_service,
+            manga_service,
+            search_service,
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+
+    fn searches_collection(&self) -> Collection<SavedSearch> {
+        self.progress_service.db().collection("saved_searches")
+    }
+
+    fn notifications_collection(&self) -> Collection<SearchNotification> {
+        self.progress_service.db().collection("search_notifications")
+    }
+
+    async fn create_indexes(&self) -> Result<(), Box<dyn std::error::Error>> {
+        use mongodb::{options::IndexOptions, IndexModel};
+
+        self.searches_collection()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "user_id": 1, "query_hash": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.notifications_collection()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "user_id": 1, "read": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Evaluate alerting searches now and then on every interval
+    pub fn spawn_scheduler(self) {
+        tokio::spawn(async move {
+            if let Err(e) = self.create_indexes().await {
+                tracing::warn!("⚠️  Failed to create saved search indexes: {}", e);
+            }
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                match self.run_alerts().await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("🔔 Saved searches produced {} new matches", count),
+                    Err(e) => tracing::error!("Saved search run failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Save a search, or rename/update the existing saved search for the
+    /// same query. The current results become the baseline for alerts.
+    pub async fn save(
+        &self,
+        user_id: &str,
+        name: &str,
+        params: AdvancedSearchParams,
+        alerts_enabled: bool,
+    ) -> Result<SavedSearch, Box<dyn std::error::Error>> {
+        let params = AdvancedSearchParams { cursor: None, ..params };
+        let query_hash = query_identity(&params);
+        let results = self.evaluate(&params).await?;
+        let (_, seen_manga_ids) = diff_new_matches(&[], &results);
+        let now = chrono::Utc::now().to_rfc3339();
+
+        let searches = self.searches_collection();
+        let filter = doc! { "user_id": user_id, "query_hash": &query_hash };
+        let existing = searches.find_one(filter.clone()).await?;
+
+        let saved = SavedSearch {
+            id: existing.as_ref().and_then(|s| s.id),
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            params,
+            query_hash,
+            alerts_enabled,
+            seen_manga_ids,
+            last_run_at: Some(now.clone()),
+            created_at: existing.map(|s| s.created_at).unwrap_or_else(|| now.clone()),
+            updated_at: now,
+        };
+
+        if saved.id.is_some() {
+            searches.replace_one(filter, &saved).await?;
+            Ok(saved)
+        } else {
+            let result = searches.insert_one(&saved).await?;
+            Ok(SavedSearch {
+                id: result.inserted_id.as_object_id(),
+                ..saved
+            })
+        }
+    }
+
+    pub async fn list(&self, user_id: &str) -> Result<Vec<SavedSearch>, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .searches_collection()
+            .find(doc! { "user_id": user_id })
+            .sort(doc! { "created_at": -1 })
+            .await?;
+
+        let mut results = Vec::new();
+        while cursor.advance().await? {
+            results.push(cursor.deserialize_current()?);
+        }
+        Ok(results)
+    }
+
+    pub async fn get(&self, user_id: &str, search_id: &str) -> Result<Option<SavedSearch>, Box<dyn std::error::Error>> {
+        let id = ObjectId::parse_str(search_id)?;
+        Ok(self
+            .searches_collection()
+            .find_one(doc! { "_id": id, "user_id": user_id })
+            .await?)
+    }
+
+    pub async fn delete(&self, user_id: &str, search_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let id = ObjectId::parse_str(search_id)?;
+        let result = self
+            .searches_collection()
+            .delete_one(doc! { "_id": id, "user_id": user_id })
+            .await?;
+        self.notifications_collection()
+            .delete_many(doc! { "saved_search_id": id, "user_id": user_id })
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Run a saved search again with the caller's paging
+    pub async fn replay(
+        &self,
+        saved: &SavedSearch,
+        page: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<MangaListResponse, Box<dyn std::error::Error>> {
+        let mut params = saved.params.clone();
+        params.page = page.or(params.page);
+        params.limit = limit.or(params.limit);
+        self.search_service
+            .advanced_search(&self.manga_service, params)
+            .await
+    }
+
+    /// Check every alerting saved search; returns the number of new matches
+    pub async fn run_alerts(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .searches_collection()
+            .find(doc! { "alerts_enabled": true })
+            .await?;
+        let mut searches = Vec::new();
+        while cursor.advance().await? {
+            searches.push(cursor.deserialize_current()?);
+        }
+
+        let mut total = 0;
+        for saved in &searches {
+            match self.check_for_new_matches(saved).await {
+                Ok(count) => total += count,
+                Err(e) => tracing::warn!(
+                    "Failed to evaluate saved search '{}' of user {}: {}",
+                    saved.name,
+                    saved.user_id,
+                    e
+                ),
+            }
+        }
+        Ok(total)
+    }
+
+    async fn check_for_new_matches(&self, saved: &SavedSearch) -> Result<usize, Box<dyn std::error::Error>> {
+        let Some(search_id) = saved.id else {
+            return Ok(0);
+        };
+        let results = self.evaluate(&saved.params).await?;
+        let (new, seen) = diff_new_matches(&saved.seen_manga_ids, &results);
+        let now = chrono::Utc::now().to_rfc3339();
+
+        if !new.is_empty() {
+            let notifications: Vec<SearchNotification> = new
+                .iter()
+                .map(|manga| SearchNotification {
+                    id: None,
+                    user_id: saved.user_id.clone(),
+                    saved_search_id: search_id,
+                    saved_search_name: saved.name.clone(),
+                    manga_id: manga.manga_id.clone().unwrap_or_default(),
+                    manga_title: manga.title.clone(),
+                    read: false,
+                    created_at: now.clone(),
+                })
+                .collect();
+            self.notifications_collection().insert_many(notifications).await?;
+        }
+
+        self.searches_collection()
+            .update_one(
+                doc! { "_id": search_id },
+                doc! { "$set": { "seen_manga_ids": seen, "last_run_at": &now } },
+            )
+            .await?;
+        Ok(new.len())
+    }
+
+    /// Fresh results for alerting, ignoring the cached search responses
+    async fn evaluate(&self, params: &AdvancedSearchParams) -> Result<Vec<Manga>, Box<dyn std::error::Error>> {
+        let mut params = params.clone();
+        params.page = Some(1);
+        params.limit = Some(ALERT_RESULT_LIMIT);
+        params.cursor = None;
+        params.facets = None;
+        let response = self
+            .search_service
+            .search_uncached(&self.manga_service, params)
+            .await?;
+        Ok(response.manga.unwrap_or_default())
+    }
+
+    pub async fn notifications(
+        &self,
+        user_id: &str,
+        unread_only: bool,
+        limit: u32,
+    ) -> Result<Vec<SearchNotification>, Box<dyn std::error::Error>> {
+        let mut filter = doc! { "user_id": user_id };
+        if unread_only {
+            filter.insert("read", false);
+        }
+        let mut cursor = self
+            .notifications_collection()
+            .find(filter)
+            .sort(doc! { "created_at": -1 })
+            .limit(limit.min(200) as i64)
+            .await?;
+
+        let mut results = Vec::new();
+        while cursor.advance().await? {
+            results.push(cursor.deserialize_current()?);
+        }
+        Ok(results)
+    }
+
+    /// Mark some notifications read, or all of them when `ids` is empty
+    pub async fn mark_read(&self, user_id: &str, ids: &[String]) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut filter = doc! { "user_id": user_id, "read": false };
+        if !ids.is_empty() {
+            let ids = ids
+                .iter()
+                .map(ObjectId::parse_str)
+                .collect::<Result<Vec<_>, _>>()?;
+            filter.insert("_id", doc! { "$in": ids });
+        }
+        let result = self
+            .notifications_collection()
+            .update_many(filter, doc! { "$set": { "read": true } })
+            .await?;
+        Ok(result.modified_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manga(id: &str) -> Manga {
+        serde_json::from_value(serde_json::json!({ "manga_id": id })).unwrap()
+    }
+
+    #[test]
+    fn test_diff_new_matches_reports_only_unseen() {
+        let seen = vec!["a".to_string(), "b".to_string()];
+        let results = vec![manga("c"), manga("a"), manga("d")];
+        let (new, updated) = diff_new_matches(&seen, &results);
+
+        let new_ids: Vec<_> = new.iter().filter_map(|m| m.manga_id.as_deref()).collect();
+        assert_eq!(new_ids, vec!["c", "d"]);
+        assert_eq!(updated, vec!["c", "a", "d", "b"]);
+    }
+
+    #[test]
+    fn test_query_identity_ignores_paging() {
+        let mut params = AdvancedSearchParams {
+            query: Some("romance".to_string()),
+            ..Default::default()
+        };
+        let identity = query_identity(&params);
+        params.page = Some(4);
+        params.limit = Some(50);
+        params.preferred_language = Some("ja".to_string());
+        assert_eq!(query_identity(&params), identity);
+
+        params.query = Some("horror".to_string());
+        assert_ne!(query_identity(&params), identity);
+    }
+
+    #[test]
+    fn test_saved_params_roundtrip_through_bson() {
+        let params = AdvancedSearchParams {
+            tags: Some(vec!["action".to_string()]),
+            min_rating: Some(4.0),
+            ..Default::default()
+        };
+        let document = mongodb::bson::to_document(&params).unwrap();
+        let restored: AdvancedSearchParams = mongodb::bson::from_document(document).unwrap();
+        assert_eq!(query_identity(&restored), query_identity(&params));
+    }
+}
@@ -1,19 +1,222 @@
 use crate::cache::CacheService;
+use crate::content_filter::{rating_condition, ContentFilter};
+use crate::embedding::EmbeddingProvider;
+use crate::hybrid_search::{reciprocal_rank_fusion, HybridUnavailable, HYBRID_CANDIDATES};
 use crate::manga_service::{Manga, MangaListResponse};
-use mongodb::bson::doc;
-use serde::{Deserialize, Serialize};
+use crate::pagination::{and_filter, keyset_sort, next_page_cursor, parse_cursor, Cursor};
+use crate::search_index::{SearchHit, MAX_HITS};
+use mongodb::bson::{doc, Bson, Document};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Single-word queries up to this many characters are matched as title
+/// prefixes; the text index only matches whole (stemmed) words
+pub const PREFIX_QUERY_MAX_CHARS: usize = 3;
+
+/// Longest query or filter value accepted from users
+pub const MAX_QUERY_CHARS: usize = 200;
+
+/// Wildcards allowed in a single filter value
+pub const MAX_WILDCARDS: usize = 2;
+
+/// Rejected search input
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum SearchInputError {
+    #[error("Search input is too long (max {MAX_QUERY_CHARS} characters)")]
+    TooLong,
+    #[error("Search input may contain at most {MAX_WILDCARDS} wildcards")]
+    TooManyWildcards,
+    #[error("Search input must contain more than wildcards")]
+    OnlyWildcards,
+}
+
+/// Trim user input and enforce the length limit
+pub fn bounded_input(input: &str) -> Result<&str, SearchInputError> {
+    let input = input.trim();
+    if input.chars().count() > MAX_QUERY_CHARS {
+        return Err(SearchInputError::TooLong);
+    }
+    Ok(input)
+}
+
+/// `$regex` pattern matching user input literally
+pub fn literal_pattern(input: &str) -> Result<String, SearchInputError> {
+    Ok(regex::escape(bounded_input(input)?))
+}
+
+/// `$regex` pattern for filter values. Input is matched literally except
+/// for `*`, which is the only wildcard and matches any run of characters.
+pub fn wildcard_pattern(input: &str) -> Result<String, SearchInputError> {
+    let input = bounded_input(input)?;
+    let parts: Vec<&str> = input.split('*').collect();
+    if parts.len() - 1 > MAX_WILDCARDS {
+        return Err(SearchInputError::TooManyWildcards);
+    }
+    if parts.iter().all(|p| p.trim().is_empty()) {
+        return Err(SearchInputError::OnlyWildcards);
+    }
+    Ok(parts
+        .iter()
+        .map(|p| regex::escape(p))
+        .collect::<Vec<_>>()
+        .join(".*"))
+}
+
+/// How a free-text query is matched against the catalog
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextQuery {
+    /// Anchored, case-insensitive match on the start of the title
+    Prefix(String),
+    /// `$text` search over the text index of titles, description and author.
+    /// Supports `"exact phrases"` and `-excluded` words.
+    FullText(String),
+}
+
+impl TextQuery {
+    /// Classify a raw query, or `None` when it is blank
+    pub fn parse(query: &str) -> Result<Option<Self>, SearchInputError> {
+        let query = bounded_input(query)?;
+        if query.is_empty() {
+            return Ok(None);
+        }
+        let is_short_word = query.chars().count() <= PREFIX_QUERY_MAX_CHARS
+            && query.chars().all(char::is_alphanumeric);
+        if is_short_word {
+            Ok(Some(TextQuery::Prefix(query.to_string())))
+        } else {
+            Ok(Some(TextQuery::FullText(query.to_string())))
+        }
+    }
+
+    pub fn filter(&self) -> Document {
+        match self {
+            TextQuery::Prefix(prefix) => {
+                let pattern = doc! { "$regex": format!("^{}", regex::escape(prefix)), "$options": "i" };
+                doc! {
+                    "$or": [
+                        { "title": pattern.clone() },
+                        { "titles.title": pattern.clone() },
+                        { "alt_titles": pattern },
+                    ]
+                }
+            }
+            TextQuery::FullText(query) => doc! { "$text": { "$search": query } },
+        }
+    }
+
+    pub fn is_full_text(&self) -> bool {
+        matches!(self, TextQuery::FullText(_))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            TextQuery::Prefix(query) | TextQuery::FullText(query) => query,
+        }
+    }
+
+    /// Whether the query uses `"phrase"` or `-exclusion` syntax, which only
+    /// `$text` understands
+    pub fn has_operators(&self) -> bool {
+        let query = self.as_str();
+        query.contains('"') || query.split_whitespace().any(|word| word.len() > 1 && word.starts_with('-'))
+    }
+}
+
+/// Projection that exposes the text score so results can be ranked by it
+pub fn text_score_projection() -> Document {
+    doc! { "score": { "$meta": "textScore" } }
+}
+
+/// Sort by text score, most relevant first
+pub fn text_score_sort() -> Document {
+    doc! { "score": { "$meta": "textScore" }, "updated_at": -1 }
+}
+
+/// Most buckets returned per facet
+pub const FACET_BUCKET_LIMIT: i64 = 20;
+
+/// Accept list parameters either as a sequence (JSON bodies) or as a
+/// comma-separated string (`?tags=action,comedy`)
+fn deserialize_list<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Many(Vec<String>),
+        One(String),
+    }
+
+    let items = match Option::<List>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(List::Many(items)) => items,
+        Some(List::One(items)) => items.split(',').map(str::to_string).collect(),
+    };
+    Ok(Some(
+        items
+            .into_iter()
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    ))
+}
+
+/// Compact hash of search parameters
+pub fn params_hash(params: &AdvancedSearchParams) -> String {
+    use sha2::{Digest, Sha256};
+
+    // Serialize params to JSON
+    let json = serde_json::to_string(params).unwrap_or_default();
+
+    // Hash to create compact key
+    let mut hasher = Sha256::new();
+    hasher.update(json.as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+
+    hash[..16].to_string()
+}
+
+/// Hash identifying what a search matches, ignoring paging and presentation
+pub fn query_identity(params: &AdvancedSearchParams) -> String {
+    let mut params = params.clone();
+    params.page = None;
+    params.limit = None;
+    params.cursor = None;
+    params.facets = None;
+    params.preferred_language = None;
+    params_hash(&params)
+}
+
+/// How search results are ranked by relevance
+enum Ranking {
+    /// MongoDB `$text` score
+    TextScore,
+    /// Order of the embedded search index hits
+    Index(Vec<SearchHit>),
+    Unranked,
+}
 
 /// Advanced search parameters
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AdvancedSearchParams {
     // Basic search
     pub query: Option<String>,
     
     // Filters
+    #[serde(default, deserialize_with = "deserialize_list")]
     pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub exclude_tags: Option<Vec<String>>,
+    pub tag_mode: Option<TagMode>,
+    #[serde(default, deserialize_with = "deserialize_list")]
     pub status: Option<Vec<String>>,
     pub author: Option<String>,
     pub artist: Option<String>,
+    // Capped server-side by the caller's content filter
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub content_rating: Option<Vec<String>>,
     
     // Year range
     pub year_from: Option<u16>,
@@ -29,6 +232,167 @@ pub struct AdvancedSearchParams {
     // Pagination
     pub page: Option<u32>,
     pub limit: Option<u32>,
+    /// Opaque cursor from a previous page; takes precedence over `page`
+    pub cursor: Option<String>,
+
+    // Include facet counts computed over the filtered results
+    pub facets: Option<bool>,
+
+    // Language used to pick each result's display title
+    pub preferred_language: Option<String>,
+
+    // How the text query is matched
+    pub mode: Option<SearchMode>,
+}
+
+/// How the free-text query ranks results
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    /// Keyword matching through the search index or text index
+    #[default]
+    Lexical,
+    /// Keyword and embedding rankings fused by reciprocal rank fusion;
+    /// always ordered by fused relevance
+    Hybrid,
+}
+
+/// How multiple `tags` combine
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMode {
+    /// Manga must have every tag
+    #[serde(alias = "and")]
+    All,
+    /// Manga must have at least one of the tags
+    #[default]
+    #[serde(alias = "or")]
+    Any,
+}
+
+/// One facet value and how many matching manga have it
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct FacetBucket {
+    #[serde(rename = "_id")]
+    pub value: Bson,
+    pub count: i64,
+}
+
+/// Facet buckets for the current filter, most common values first
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SearchFacets {
+    #[serde(default)]
+    pub tags: Vec<FacetBucket>,
+    #[serde(default)]
+    pub status: Vec<FacetBucket>,
+    #[serde(default)]
+    pub author: Vec<FacetBucket>,
+    #[serde(default)]
+    pub artist: Vec<FacetBucket>,
+    #[serde(default)]
+    pub year: Vec<FacetBucket>,
+    #[serde(default)]
+    pub content_rating: Vec<FacetBucket>,
+}
+
+/// `$facet` stage counting values of each facet field
+fn facet_stage() -> Document {
+    let count_field = |field: &str| -> Vec<Document> {
+        vec![
+            doc! { "$match": { field: { "$ne": null } } },
+            doc! { "$sortByCount": format!("${}", field) },
+            doc! { "$limit": FACET_BUCKET_LIMIT },
+        ]
+    };
+    doc! {
+        "$facet": {
+            "tags": [
+                { "$unwind": "$tags" },
+                { "$sortByCount": "$tags" },
+                { "$limit": FACET_BUCKET_LIMIT },
+            ],
+            "status": count_field("status"),
+            "author": count_field("author"),
+            "artist": count_field("artist"),
+            "year": count_field("year"),
+            "content_rating": count_field("content_rating"),
+        }
+    }
+}
+
+/// Build the MongoDB filter for a search, along with the parsed text query
+pub fn build_filter(params: &AdvancedSearchParams) -> Result<(Document, Option<TextQuery>), SearchInputError> {
+    let mut filter = attribute_filter(params)?;
+
+    // Text search
+    let text_query = match params.query.as_deref() {
+        Some(query) => TextQuery::parse(query)?,
+        None => None,
+    };
+    if let Some(text_query) = &text_query {
+        filter.extend(text_query.filter());
+    }
+
+    Ok((filter, text_query))
+}
+
+/// Filter for everything except the free-text query
+pub fn attribute_filter(params: &AdvancedSearchParams) -> Result<Document, SearchInputError> {
+    let mut filter = doc! {};
+
+    // Tags filter: include (any or all) and exclude
+    let mut tags_filter = doc! {};
+    if let Some(tags) = params.tags.as_ref().filter(|t| !t.is_empty()) {
+        let operator = match params.tag_mode.unwrap_or_default() {
+            TagMode::All => "$all",
+            TagMode::Any => "$in",
+        };
+        tags_filter.insert(operator, tags);
+    }
+    if let Some(exclude) = params.exclude_tags.as_ref().filter(|t| !t.is_empty()) {
+        tags_filter.insert("$nin", exclude);
+    }
+    if !tags_filter.is_empty() {
+        filter.insert("tags", tags_filter);
+    }
+
+    // Status filter
+    if let Some(statuses) = params.status.as_ref().filter(|s| !s.is_empty()) {
+        filter.insert("status", doc! { "$in": statuses });
+    }
+
+    if let Some(ratings) = &params.content_rating {
+        filter.insert("content_rating", rating_condition(ratings));
+    }
+
+    // Author filter
+    if let Some(author) = &params.author {
+        filter.insert("author", doc! { "$regex": wildcard_pattern(author)?, "$options": "i" });
+    }
+
+    // Artist filter
+    if let Some(artist) = &params.artist {
+        filter.insert("artist", doc! { "$regex": wildcard_pattern(artist)?, "$options": "i" });
+    }
+
+    // Year range filter
+    let mut year_filter = doc! {};
+    if let Some(year_from) = params.year_from {
+        year_filter.insert("$gte", year_from as i32);
+    }
+    if let Some(year_to) = params.year_to {
+        year_filter.insert("$lte", year_to as i32);
+    }
+    if !year_filter.is_empty() {
+        filter.insert("year", year_filter);
+    }
+
+    // Minimum average rating
+    if let Some(min_rating) = params.min_rating {
+        filter.insert("rating", doc! { "$gte": min_rating as f64 });
+    }
+
+    Ok(filter)
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -39,6 +403,7 @@ pub enum SortField {
     CreatedAt,
     Rating,
     Popularity,
+    Relevance,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -53,9 +418,12 @@ impl Default for AdvancedSearchParams {
         Self {
             query: None,
             tags: None,
+            exclude_tags: None,
+            tag_mode: None,
             status: None,
             author: None,
             artist: None,
+            content_rating: None,
             year_from: None,
             year_to: None,
             min_rating: None,
@@ -63,6 +431,10 @@ impl Default for AdvancedSearchParams {
             sort_order: Some(SortOrder::Desc),
             page: Some(1),
             limit: Some(20),
+            cursor: None,
+            facets: None,
+            preferred_language: None,
+            mode: None,
         }
     }
 }
@@ -71,11 +443,12 @@ impl Default for AdvancedSearchParams {
 #[derive(Clone)]
 pub struct SearchService {
     cache: Option<CacheService>,
+    embeddings: Option<Arc<dyn EmbeddingProvider>>,
 }
 
 impl SearchService {
-    pub fn new(cache: Option<CacheService>) -> Self {
-        Self { cache }
+    pub fn new(cache: Option<CacheService>, embeddings: Option<Arc<dyn EmbeddingProvider>>) -> Self {
+        Self { cache, embeddings }
     }
 
     /// Perform advanced search with caching
@@ -95,66 +468,69 @@ impl SearchService {
             }
         }
 
-        // Build MongoDB query
-        let mut filter = doc! {};
-
-        // Text search
-        if let Some(query) = &params.query {
-            if !query.is_empty() {
-                filter.insert(
-                    "$or",
-                    vec![
-                        doc! { "title": { "$regex": query, "$options": "i" } },
-                        doc! { "description": { "$regex": query, "$options": "i" } },
-                        doc! { "author": { "$regex": query, "$options": "i" } },
-                    ],
-                );
-            }
-        }
+        let response = self.search_uncached(manga_service, params).await?;
 
-        // Tags filter
-        if let Some(tags) = &params.tags {
-            if !tags.is_empty() {
-                filter.insert("tags", doc! { "$in": tags });
-            }
-        }
-
-        // Status filter
-        if let Some(statuses) = &params.status {
-            if !statuses.is_empty() {
-                filter.insert("status", doc! { "$in": statuses });
-            }
+        // Cache the results (5 minutes)
+        if let Some(cache) = &self.cache {
+            let _ = cache.set(&cache_key, &response, 300).await;
         }
 
-        // Author filter
-        if let Some(author) = &params.author {
-            filter.insert("author", doc! { "$regex": author, "$options": "i" });
-        }
+        Ok(response)
+    }
 
-        // Artist filter
-        if let Some(artist) = &params.artist {
-            filter.insert("artist", doc! { "$regex": artist, "$options": "i" });
+    /// Run an advanced search against the database, bypassing the cache
+    pub async fn search_uncached(
+        &self,
+        manga_service: &crate::manga_service::MangaService,
+        params: AdvancedSearchParams,
+    ) -> Result<MangaListResponse, Box<dyn std::error::Error>> {
+        if params.mode == Some(SearchMode::Hybrid)
+            && let Some(query) = params.query.as_deref().map(bounded_input).transpose()?.filter(|q| !q.is_empty())
+        {
+            let query = query.to_string();
+            return self.hybrid_search(manga_service, &params, &query).await;
         }
 
-        // Year range filter
-        let mut year_filter = doc! {};
-        if let Some(year_from) = params.year_from {
-            year_filter.insert("$gte", year_from as i32);
-        }
-        if let Some(year_to) = params.year_to {
-            year_filter.insert("$lte", year_to as i32);
-        }
-        if !year_filter.is_empty() {
-            filter.insert("year", year_filter);
-        }
+        // Build MongoDB query. Once the search index is built it answers the
+        // text part of the query together with the text index, and the
+        // database then applies the other filters.
+        let text_query = match params.query.as_deref() {
+            Some(query) => TextQuery::parse(query)?,
+            None => None,
+        };
+        let index_hits = match &text_query {
+            Some(text_query) => {
+                manga_service
+                    .text_hits(text_query, attribute_filter(&params)?, MAX_HITS)
+                    .await?
+            }
+            None => None,
+        };
+        let (filter, ranking) = match index_hits {
+            None => {
+                let (filter, text_query) = build_filter(&params)?;
+                let ranking = match text_query {
+                    Some(text_query) if text_query.is_full_text() => Ranking::TextScore,
+                    _ => Ranking::Unranked,
+                };
+                (filter, ranking)
+            }
+            Some(index_hits) => {
+                let mut filter = attribute_filter(&params)?;
+                let ids: Vec<&str> = index_hits.iter().map(|hit| hit.manga_id.as_str()).collect();
+                filter.insert("manga_id", doc! { "$in": ids });
+                (filter, Ranking::Index(index_hits))
+            }
+        };
 
-        // Build sort document
-        let sort_field = match params.sort_by.unwrap_or(SortField::UpdatedAt) {
-            SortField::Title => "title",
-            SortField::UpdatedAt => "updated_at",
-            SortField::CreatedAt => "created_at",
-            SortField::Rating => "rating",
-            SortField::Popularity => "popularity",
+        // Build sort document. Text queries rank by relevance unless another
+        // order is asked for; relevance needs a text query.
+        let ranked = !matches!(ranking, Ranking::Unranked);
+        let sort_by = match params.sort_by {
+            Some(SortField::Relevance) if !ranked => SortField::UpdatedAt,
+            Some(sort_by) => sort_by,
+            None if ranked => SortField::Relevance,
+            None => SortField::UpdatedAt,
         };
 
         let sort_direction = match params.sort_order.unwrap_or(SortOrder::Desc) {
@@ -162,58 +538,247 @@ impl SearchService {
             SortOrder::Desc => -1,
         };
 
-        let sort = doc! { sort_field: sort_direction };
+        // Field orders page by keyset cursors; relevance orders by offset
+        let sort_key = match sort_by {
+            SortField::Title => Some("title"),
+            SortField::UpdatedAt => Some("updated_at"),
+            SortField::CreatedAt => Some("created_at"),
+            SortField::Rating => Some("rating"),
+            SortField::Popularity => Some("popularity"),
+            SortField::Relevance => None,
+        };
+        let sort = match sort_key {
+            Some(field) => keyset_sort(field, sort_direction),
+            None => text_score_sort(),
+        };
 
-        // Pagination
+        // Pagination: a cursor takes precedence over page
         let page = params.page.unwrap_or(1).max(1);
-        let limit = params.limit.unwrap_or(20).min(100) as i64;
-        let skip = ((page - 1) * limit as u32) as u64;
+        let limit = params.limit.unwrap_or(20).clamp(1, 100) as i64;
+        let cursor = parse_cursor(params.cursor.as_deref())?;
+        let (keyset, skip) = match (&cursor, sort_key) {
+            (Some(cursor), Some(field)) => (Some(cursor.keyset_filter(field, sort_direction)?), 0),
+            (Some(cursor), None) => (None, cursor.offset()?),
+            (None, _) => (None, ((page - 1) * limit as u32) as u64),
+        };
 
         // Execute query
         let collection = manga_service.manga_collection();
-        
-        let total_count = collection.count_documents(filter.clone()).await?;
+        let filter_for_facets = filter.clone();
+
+        let (manga_list, total_count, next_cursor) = match (&ranking, sort_by) {
+            // Index relevance: order the (bounded) matches in memory
+            (Ranking::Index(hits), SortField::Relevance) => {
+                let rank: HashMap<&str, usize> = hits
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hit)| (hit.manga_id.as_str(), i))
+                    .collect();
+                let mut cursor = collection.find(filter).await?;
+                let mut matches: Vec<Manga> = Vec::new();
+                while cursor.advance().await? {
+                    matches.push(cursor.deserialize_current()?);
+                }
+                matches.sort_by_key(|m| {
+                    m.manga_id
+                        .as_deref()
+                        .and_then(|id| rank.get(id).copied())
+                        .unwrap_or(usize::MAX)
+                });
+                let total_count = matches.len() as u64;
+                let next_offset = skip + limit as u64;
+                let next_cursor = (next_offset < total_count)
+                    .then(|| Cursor::Offset { offset: next_offset }.encode());
+                let page_items = matches
+                    .into_iter()
+                    .skip(skip as usize)
+                    .take(limit as usize)
+                    .collect();
+                (page_items, total_count, next_cursor)
+            }
+            _ => {
+                let total_count = collection.count_documents(filter.clone()).await?;
+                let mut find = collection
+                    .find(and_filter(filter, keyset))
+                    .sort(sort)
+                    .skip(skip)
+                    .limit(limit + 1);
+                if matches!(ranking, Ranking::TextScore) {
+                    find = find.projection(text_score_projection());
+                }
+                let mut cursor = find.await?;
+
+                let mut manga_list = Vec::new();
+                while cursor.advance().await? {
+                    manga_list.push(cursor.deserialize_current()?);
+                }
+                let next_cursor = match sort_key {
+                    Some(field) => next_page_cursor(&mut manga_list, limit as usize, field),
+                    None if manga_list.len() > limit as usize => {
+                        manga_list.truncate(limit as usize);
+                        Some(Cursor::Offset { offset: skip + limit as u64 }.encode())
+                    }
+                    None => None,
+                };
+                (manga_list, total_count, next_cursor)
+            }
+        };
+
+        let mut manga_list = manga_list;
+        for manga in &mut manga_list {
+            manga.localize(params.preferred_language.as_deref());
+        }
+
+        let facets = if params.facets.unwrap_or(false) {
+            Some(self.compute_facets(manga_service, filter_for_facets).await?)
+        } else {
+            None
+        };
 
-        let mut cursor = collection
-            .find(filter)
-            .sort(sort)
-            .skip(skip)
-            .limit(limit)
-            .await?;
+        let response = MangaListResponse {
+            success: true,
+            manga: Some(manga_list),
+            total_count: Some(total_count as i64),
+            message: Some(format!("Found {} manga", total_count)),
+            facets,
+            next_cursor,
+            scores: None,
+        };
 
-        let mut manga_list = Vec::new();
+        Ok(response)
+    }
+
+    /// Rank by keyword and embedding similarity together. Filters apply to
+    /// both candidate lists before they are fused.
+    async fn hybrid_search(
+        &self,
+        manga_service: &crate::manga_service::MangaService,
+        params: &AdvancedSearchParams,
+        query: &str,
+    ) -> Result<MangaListResponse, Box<dyn std::error::Error>> {
+        let embeddings = self.embeddings.as_ref().ok_or(HybridUnavailable)?;
+        let filter = attribute_filter(params)?;
+        let collection = manga_service.manga_collection();
+
+        let lexical = self.lexical_candidates(manga_service, &filter, query).await?;
+        let vector = embeddings.embed_query(query).await?;
+        let semantic: Vec<(String, f32)> = manga_service
+            .semantic_search(&vector, &embeddings.model_id(), HYBRID_CANDIDATES, filter.clone())
+            .await?
+            .into_iter()
+            .filter_map(|(manga, similarity)| manga.manga_id.map(|id| (id, similarity)))
+            .collect();
+        let fused = reciprocal_rank_fusion(&lexical, &semantic);
+
+        // Relevance order pages by offset
+        let limit = params.limit.unwrap_or(20).clamp(1, 100) as usize;
+        let skip = match parse_cursor(params.cursor.as_deref())? {
+            Some(cursor) => cursor.offset()? as usize,
+            None => (params.page.unwrap_or(1).max(1) as usize - 1) * limit,
+        };
+        let total_count = fused.len();
+        let next_cursor = (skip + limit < total_count).then(|| Cursor::Offset { offset: (skip + limit) as u64 }.encode());
+        let scores: Vec<_> = fused.into_iter().skip(skip).take(limit).collect();
+
+        let ids: Vec<&str> = scores.iter().map(|s| s.manga_id.as_str()).collect();
+        let mut cursor = collection.find(doc! { "manga_id": { "$in": &ids } }).await?;
+        let mut found: HashMap<String, Manga> = HashMap::new();
         while cursor.advance().await? {
-            manga_list.push(cursor.deserialize_current()?);
+            let mut manga: Manga = cursor.deserialize_current()?;
+            manga.localize(params.preferred_language.as_deref());
+            if let Some(manga_id) = manga.manga_id.clone() {
+                found.insert(manga_id, manga);
+            }
         }
+        let (manga_list, scores): (Vec<Manga>, Vec<_>) = scores
+            .into_iter()
+            .filter_map(|score| found.remove(&score.manga_id).map(|manga| (manga, score)))
+            .unzip();
+
+        let facets = if params.facets.unwrap_or(false) {
+            Some(self.compute_facets(manga_service, filter).await?)
+        } else {
+            None
+        };
 
-        let response = MangaListResponse {
+        Ok(MangaListResponse {
             success: true,
             manga: Some(manga_list),
             total_count: Some(total_count as i64),
             message: Some(format!("Found {} manga", total_count)),
+            facets,
+            next_cursor,
+            scores: Some(scores),
+        })
+    }
+
+    /// Keyword matches for a hybrid search, best first, with the search
+    /// index's score when it answered
+    async fn lexical_candidates(
+        &self,
+        manga_service: &crate::manga_service::MangaService,
+        filter: &Document,
+        query: &str,
+    ) -> Result<Vec<(String, Option<f32>)>, Box<dyn std::error::Error>> {
+        let collection = manga_service.manga_collection();
+        let Some(text_query) = TextQuery::parse(query)? else {
+            return Ok(Vec::new());
         };
 
-        // Cache the results (5 minutes)
-        if let Some(cache) = &self.cache {
-            let _ = cache.set(&cache_key, &response, 300).await;
+        let hits = manga_service.text_hits(&text_query, filter.clone(), MAX_HITS).await?;
+        if let Some(hits) = hits {
+            let ids: Vec<&str> = hits.iter().map(|hit| hit.manga_id.as_str()).collect();
+            let mut matching = filter.clone();
+            matching.insert("manga_id", doc! { "$in": &ids });
+            let mut cursor = collection.find(matching).projection(doc! { "manga_id": 1 }).await?;
+            let mut allowed = HashSet::new();
+            while cursor.advance().await? {
+                allowed.extend(cursor.deserialize_current()?.manga_id);
+            }
+            return Ok(hits
+                .into_iter()
+                .filter(|hit| allowed.contains(&hit.manga_id))
+                .take(HYBRID_CANDIDATES)
+                // Database-only matches have no index score
+                .map(|hit| (hit.manga_id, (hit.score > 0.0).then_some(hit.score)))
+                .collect());
         }
 
-        Ok(response)
+        let mut matching = filter.clone();
+        matching.extend(text_query.filter());
+        let mut find = collection.find(matching).limit(HYBRID_CANDIDATES as i64);
+        find = if text_query.is_full_text() {
+            find.projection(text_score_projection()).sort(text_score_sort())
+        } else {
+            find.sort(doc! { "title": 1 })
+        };
+        let mut cursor = find.await?;
+        let mut candidates = Vec::new();
+        while cursor.advance().await? {
+            candidates.extend(cursor.deserialize_current()?.manga_id.map(|id| (id, None)));
+        }
+        Ok(candidates)
+    }
+
+    /// Count facet values over every manga matching the filter
+    async fn compute_facets(
+        &self,
+        manga_service: &crate::manga_service::MangaService,
+        filter: Document,
+    ) -> Result<SearchFacets, Box<dyn std::error::Error>> {
+        let pipeline = vec![doc! { "$match": filter }, facet_stage()];
+        let mut cursor = manga_service.manga_collection().aggregate(pipeline).await?;
+        if cursor.advance().await? {
+            let document = cursor.deserialize_current()?;
+            Ok(mongodb::bson::from_document(document)?)
+        } else {
+            Ok(SearchFacets::default())
+        }
     }
 
     /// Generate cache key from search parameters
     fn generate_cache_key(&self, params: &AdvancedSearchParams) -> String {
-        use sha2::{Digest, Sha256};
-        
-        // Serialize params to JSON
-        let json = serde_json::to_string(params).unwrap_or_default();
-        
-        // Hash to create compact key
-        let mut hasher = Sha256::new();
-        hasher.update(json.as_bytes());
-        let hash = format!("{:x}", hasher.finalize());
-        
-        format!("search:advanced:{}", &hash[..16])
+        format!("search:advanced:{}", params_hash(params))
     }
 
     /// Invalidate search cache (call after manga updates)
@@ -232,12 +797,20 @@ pub async fn get_autocomplete_suggestions(
     cache: &Option<CacheService>,
     query: &str,
     limit: u32,
+    preferred_language: Option<&str>,
+    content: &ContentFilter,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let query = bounded_input(query)?;
     if query.len() < 2 {
         return Ok(Vec::new());
     }
 
-    let cache_key = format!("autocomplete:{}", query);
+    let cache_key = format!(
+        "autocomplete:{}:{}:{}",
+        preferred_language.unwrap_or_default(),
+        content.cache_key(),
+        query
+    );
 
     // Try cache first
     if let Some(cache) = cache {
@@ -246,20 +819,34 @@ pub async fn get_autocomplete_suggestions(
         }
     }
 
+    // Prefer the search index: prefix and typo tolerant over every title
+    let index = manga_service.search_index();
+    if index.is_ready() {
+        let suggestions = index.autocomplete(query, limit as usize, preferred_language, content);
+        if let Some(cache) = cache {
+            let _ = cache.set(&cache_key, &suggestions, 600).await;
+        }
+        return Ok(suggestions);
+    }
+
     // Query database
     let collection = manga_service.manga_collection();
-    let mut cursor = collection
-        .find(doc! {
-            "title": { "$regex": query, "$options": "i" }
-        })
-        .limit(limit as i64)
-        .await?;
+    let pattern = doc! { "$regex": literal_pattern(query)?, "$options": "i" };
+    let mut filter = doc! {
+        "$or": [
+            { "title": pattern.clone() },
+            { "titles.title": pattern.clone() },
+            { "alt_titles": pattern },
+        ]
+    };
+    content.apply(&mut filter);
+    let mut cursor = collection.find(filter).limit(limit as i64).await?;
 
     let mut suggestions = Vec::new();
     while cursor.advance().await? {
         let manga: Manga = cursor.deserialize_current()?;
-        if let Some(title) = manga.title {
-            suggestions.push(title);
+        if let Some(title) = manga.best_title(preferred_language) {
+            suggestions.push(title.to_string());
         }
     }
 
@@ -270,3 +857,110 @@ pub async fn get_autocomplete_suggestions(
 
     Ok(suggestions)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_words_use_prefix_match() {
+        assert_eq!(TextQuery::parse(" one "), Ok(Some(TextQuery::Prefix("one".to_string()))));
+        assert_eq!(
+            TextQuery::parse("one piece"),
+            Ok(Some(TextQuery::FullText("one piece".to_string())))
+        );
+        assert_eq!(TextQuery::parse("-ab"), Ok(Some(TextQuery::FullText("-ab".to_string()))));
+        assert_eq!(TextQuery::parse("   "), Ok(None));
+    }
+
+    #[test]
+    fn test_full_text_filter_keeps_search_syntax() {
+        let filter = TextQuery::parse("\"attack on\" -titan").unwrap().unwrap().filter();
+        assert_eq!(
+            filter,
+            doc! { "$text": { "$search": "\"attack on\" -titan" } }
+        );
+
+        let has_operators = |q: &str| TextQuery::parse(q).unwrap().unwrap().has_operators();
+        assert!(has_operators("\"attack on\" -titan"));
+        assert!(has_operators("pirates -romance"));
+        assert!(!has_operators("spider-man"));
+        assert!(!has_operators("one - piece"));
+    }
+
+    #[test]
+    fn test_malicious_patterns_are_matched_literally() {
+        assert_eq!(literal_pattern("(a+)+$").unwrap(), r"\(a\+\)\+\$");
+        assert_eq!(literal_pattern(".*").unwrap(), r"\.\*");
+        assert_eq!(wildcard_pattern("(a|aa)*b").unwrap(), r"\(a\|aa\).*b");
+        assert_eq!(wildcard_pattern("[^x]{1000}").unwrap(), r"\[\^x\]\{1000\}");
+
+        // Escaped patterns only ever match the literal text
+        let pattern = regex::Regex::new(&literal_pattern("(a+)+$").unwrap()).unwrap();
+        assert!(!pattern.is_match(&"a".repeat(50)));
+        assert!(pattern.is_match("title (a+)+$"));
+    }
+
+    #[test]
+    fn test_wildcards_are_opt_in_and_bounded() {
+        assert_eq!(wildcard_pattern("Oda*").unwrap(), "Oda.*");
+        assert_eq!(literal_pattern("Oda*").unwrap(), r"Oda\*");
+        assert_eq!(wildcard_pattern("*"), Err(SearchInputError::OnlyWildcards));
+        assert_eq!(wildcard_pattern(" * * "), Err(SearchInputError::OnlyWildcards));
+        assert_eq!(wildcard_pattern("a*b*c*d"), Err(SearchInputError::TooManyWildcards));
+    }
+
+    #[test]
+    fn test_query_length_is_bounded() {
+        let long = "a".repeat(MAX_QUERY_CHARS + 1);
+        assert_eq!(literal_pattern(&long), Err(SearchInputError::TooLong));
+        assert_eq!(TextQuery::parse(&long), Err(SearchInputError::TooLong));
+        assert!(TextQuery::parse(&"a".repeat(MAX_QUERY_CHARS)).is_ok());
+    }
+
+    #[test]
+    fn test_tag_filter_include_exclude_and_mode() {
+        let mut params = AdvancedSearchParams {
+            tags: Some(vec!["action".to_string(), "comedy".to_string()]),
+            exclude_tags: Some(vec!["horror".to_string()]),
+            ..Default::default()
+        };
+        let (filter, _) = build_filter(&params).unwrap();
+        assert_eq!(
+            filter.get_document("tags").unwrap(),
+            &doc! { "$in": ["action", "comedy"], "$nin": ["horror"] }
+        );
+
+        params.tag_mode = Some(TagMode::All);
+        params.exclude_tags = None;
+        let (filter, _) = build_filter(&params).unwrap();
+        assert_eq!(filter.get_document("tags").unwrap(), &doc! { "$all": ["action", "comedy"] });
+    }
+
+    #[test]
+    fn test_list_params_accept_comma_separated_strings() {
+        let params: AdvancedSearchParams = serde_json::from_value(serde_json::json!({
+            "tags": "action, comedy",
+            "exclude_tags": ["horror"],
+            "tag_mode": "and"
+        }))
+        .unwrap();
+        assert_eq!(params.tags, Some(vec!["action".to_string(), "comedy".to_string()]));
+        assert_eq!(params.exclude_tags, Some(vec!["horror".to_string()]));
+        assert_eq!(params.tag_mode, Some(TagMode::All));
+        assert_eq!(params.status, None);
+    }
+
+    #[test]
+    fn test_facet_result_deserializes() {
+        let facets: SearchFacets = mongodb::bson::from_document(doc! {
+            "tags": [{ "_id": "action", "count": 3 }],
+            "year": [{ "_id": 2020, "count": 1_i64 }],
+        })
+        .unwrap();
+        assert_eq!(facets.tags[0].value, Bson::String("action".to_string()));
+        assert_eq!(facets.tags[0].count, 3);
+        assert_eq!(facets.year[0].value, Bson::Int32(2020));
+        assert!(facets.status.is_empty());
+    }
+}
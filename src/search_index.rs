@@ -0,0 +1,490 @@
+use crate::manga_service::{pick_title, Manga};
+use crate::content_filter::ContentFilter;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+/// Most hits a single index search returns
+pub const MAX_HITS: usize = 500;
+
+const TITLE_WEIGHT: f32 = 3.0;
+const ALT_TITLE_WEIGHT: f32 = 2.0;
+const PEOPLE_WEIGHT: f32 = 1.0;
+
+/// Score multipliers for non-exact term matches
+const PREFIX_FACTOR: f32 = 0.7;
+const FUZZY_FACTOR: f32 = 0.5;
+
+/// Han, kana and Hangul; these scripts are indexed as character bigrams
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x31F0..=0x31FF   // Katakana phonetic extensions
+        | 0x3400..=0x4DBF   // CJK extension A
+        | 0x4E00..=0x9FFF   // CJK unified ideographs
+        | 0xF900..=0xFAFF   // CJK compatibility ideographs
+        | 0x1100..=0x11FF   // Hangul jamo
+        | 0x3130..=0x318F   // Hangul compatibility jamo
+        | 0xAC00..=0xD7AF   // Hangul syllables
+    )
+}
+
+/// Lowercase and fold accents and full/half-width forms, leaving CJK text
+/// untouched so kana voicing marks and Hangul syllables survive
+pub fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_cjk(c) {
+            out.push(c);
+            continue;
+        }
+        decompose_compatible(c, |d| {
+            if !is_combining_mark(d) {
+                out.extend(d.to_lowercase());
+            }
+        });
+    }
+    out
+}
+
+/// Lowercased, accent-free words of a title or name, without punctuation
+pub fn words(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Split text into index terms: words for alphabetic scripts and
+/// overlapping bigrams for CJK runs
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    fn flush_cjk(run: &mut Vec<char>, tokens: &mut Vec<String>) {
+        match run.len() {
+            0 => {}
+            1 => tokens.push(run[0].to_string()),
+            _ => tokens.extend(run.windows(2).map(|w| w.iter().collect::<String>())),
+        }
+        run.clear();
+    }
+
+    for c in normalize(text).chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.push(c);
+        } else if c == '\'' || c == '\u{2019}' {
+            // "Frieren's" indexes as "frierens"
+        } else {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    flush_cjk(&mut cjk_run, &mut tokens);
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// Typos tolerated for a term of this many characters
+fn max_edits(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Optimal string alignment distance (Levenshtein plus adjacent
+/// transpositions), or `None` once it exceeds `max`
+pub fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut prev2: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        let mut row_min = current[0];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (prev[j] + 1).min(current[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(prev2[j - 2] + 1);
+            }
+            row_min = row_min.min(current[j]);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut current);
+    }
+    Some(prev[b.len()]).filter(|d| *d <= max)
+}
+
+/// A manga matching an index search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub manga_id: String,
+    pub title: String,
+    pub score: f32,
+}
+
+#[derive(Default)]
+struct IndexedManga {
+    title: String,
+    content_rating: Option<String>,
+    // Every title with its language, canonical title first
+    titles: Vec<(Option<String>, String)>,
+    terms: Vec<String>,
+}
+
+impl IndexedManga {
+    fn best_title(&self, preferred_language: Option<&str>) -> Option<&str> {
+        let titles: Vec<(Option<&str>, &str)> = self
+            .titles
+            .iter()
+            .map(|(language, title)| (language.as_deref(), title.as_str()))
+            .collect();
+        pick_title(&titles, preferred_language)
+    }
+}
+
+#[derive(Default)]
+struct IndexInner {
+    // Term -> manga_id -> best field weight the term appears in
+    postings: BTreeMap<String, HashMap<String, f32>>,
+    docs: HashMap<String, IndexedManga>,
+}
+
+impl IndexInner {
+    fn remove(&mut self, manga_id: &str) {
+        let Some(old) = self.docs.remove(manga_id) else {
+            return;
+        };
+        for term in old.terms {
+            if let Some(posting) = self.postings.get_mut(&term) {
+                posting.remove(manga_id);
+                if posting.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Best weighted match of one query term in every manga
+    fn match_term(&self, token: &str) -> HashMap<&str, f32> {
+        fn add<'a>(matches: &mut HashMap<&'a str, f32>, posting: &'a HashMap<String, f32>, factor: f32) {
+            for (manga_id, weight) in posting {
+                let score = matches.entry(manga_id.as_str()).or_insert(0.0);
+                *score = score.max(weight * factor);
+            }
+        }
+
+        let mut matches: HashMap<&str, f32> = HashMap::new();
+        if let Some(posting) = self.postings.get(token) {
+            add(&mut matches, posting, 1.0);
+        }
+
+        // Prefix matches let partial words ("shinge") find full terms
+        if token.chars().count() >= 2 {
+            for (term, posting) in self.postings.range(token.to_string()..) {
+                if !term.starts_with(token) {
+                    break;
+                }
+                if term != token {
+                    add(&mut matches, posting, PREFIX_FACTOR);
+                }
+            }
+        }
+
+        // Typos are only considered for terms nothing matches as typed, so
+        // "pirate" does not also find "private"
+        let max = max_edits(token);
+        if max > 0 && matches.is_empty() {
+            for (term, posting) in &self.postings {
+                if term == token {
+                    continue;
+                }
+                if let Some(distance) = edit_distance(token, term, max) {
+                    add(&mut matches, posting, FUZZY_FACTOR / distance as f32);
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// In-memory inverted index over catalog titles and creators, with prefix,
+/// typo-tolerant and CJK bigram matching
+#[derive(Clone, Default)]
+pub struct SearchIndex {
+    inner: Arc<RwLock<IndexInner>>,
+    ready: Arc<AtomicBool>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the initial build has finished; until then callers should
+    /// fall back to database queries
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.inner.read().unwrap().docs.len()
+    }
+
+    pub fn remove(&self, manga_id: &str) {
+        self.inner.write().unwrap().remove(manga_id);
+    }
+
+    /// Add or replace a manga; entries without a `manga_id` are skipped
+    pub fn upsert(&self, manga: &Manga) {
+        let Some(manga_id) = manga.manga_id.as_deref() else {
+            return;
+        };
+
+        let mut weights: HashMap<String, f32> = HashMap::new();
+        let mut add_field = |text: &str, weight: f32| {
+            for term in tokenize(text) {
+                let best = weights.entry(term).or_insert(0.0);
+                *best = best.max(weight);
+            }
+        };
+        if let Some(title) = &manga.title {
+            add_field(title, TITLE_WEIGHT);
+        }
+        for localized in manga.titles.iter().flatten() {
+            add_field(&localized.title, ALT_TITLE_WEIGHT);
+        }
+        for alt_title in manga.alt_titles.iter().flatten() {
+            add_field(alt_title, ALT_TITLE_WEIGHT);
+        }
+        for person in manga.author.iter().chain(manga.artist.iter()) {
+            add_field(person, PEOPLE_WEIGHT);
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        inner.remove(manga_id);
+        for (term, weight) in &weights {
+            inner
+                .postings
+                .entry(term.clone())
+                .or_default()
+                .insert(manga_id.to_string(), *weight);
+        }
+        inner.docs.insert(
+            manga_id.to_string(),
+            IndexedManga {
+                title: manga.title.clone().unwrap_or_default(),
+                content_rating: manga.content_rating.clone(),
+                titles: manga
+                    .all_titles()
+                    .into_iter()
+                    .map(|(language, title)| (language.map(str::to_string), title.to_string()))
+                    .collect(),
+                terms: weights.into_keys().collect(),
+            },
+        );
+    }
+
+    /// Manga matching every query term, best first
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut tokens = tokenize(query);
+        let mut seen = HashSet::new();
+        tokens.retain(|t| seen.insert(t.clone()));
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let inner = self.inner.read().unwrap();
+        let mut scores: Option<HashMap<&str, f32>> = None;
+        for token in &tokens {
+            let matches = inner.match_term(token);
+            scores = Some(match scores {
+                None => matches,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(id, score)| matches.get(id).map(|s| (id, score + s)))
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(manga_id, score)| {
+                inner.docs.get(manga_id).map(|doc| SearchHit {
+                    manga_id: manga_id.to_string(),
+                    title: doc.title.clone(),
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+        hits.truncate(limit);
+        hits
+    }
+
+    /// Title suggestions for a partially typed query, in the reader's
+    /// language where the manga has a title in it
+    pub fn autocomplete(
+        &self,
+        query: &str,
+        limit: usize,
+        preferred_language: Option<&str>,
+        content: &ContentFilter,
+    ) -> Vec<String> {
+        let hits = self.search(query, MAX_HITS);
+        let inner = self.inner.read().unwrap();
+        let mut seen = HashSet::new();
+        hits.iter()
+            .filter_map(|hit| inner.docs.get(&hit.manga_id))
+            .filter(|doc| content.allows(doc.content_rating.as_deref()))
+            .filter_map(|doc| doc.best_title(preferred_language))
+            .filter(|title| !title.is_empty() && seen.insert(title.to_string()))
+            .take(limit)
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manga(id: &str, title: &str, alt_titles: &[&str], author: &str) -> Manga {
+        serde_json::from_value(serde_json::json!({
+            "manga_id": id,
+            "title": title,
+            "alt_titles": alt_titles,
+            "author": author,
+        }))
+        .unwrap()
+    }
+
+    fn index() -> SearchIndex {
+        let index = SearchIndex::new();
+        index.upsert(&manga("op", "One Piece", &["ワンピース"], "Eiichiro Oda"));
+        index.upsert(&manga("aot", "Attack on Titan", &["Shingeki no Kyojin", "進撃の巨人"], "Hajime Isayama"));
+        index.upsert(&manga("solo", "Solo Leveling", &["나 혼자만 레벨업"], "Chugong"));
+        index
+    }
+
+    fn ids(hits: Vec<SearchHit>) -> Vec<String> {
+        hits.into_iter().map(|h| h.manga_id).collect()
+    }
+
+    #[test]
+    fn test_normalize_folds_accents_and_width() {
+        assert_eq!(normalize("Shingéki ＮＯ Kyōjin"), "shingeki no kyojin");
+        assert_eq!(normalize("がんばれ"), "がんばれ");
+    }
+
+    #[test]
+    fn test_tokenize_uses_bigrams_for_cjk() {
+        assert_eq!(tokenize("進撃の巨人"), vec!["進撃", "撃の", "の巨", "巨人"]);
+        assert_eq!(tokenize("Frieren's 2nd"), vec!["frierens", "2nd"]);
+        assert_eq!(tokenize("One Piece ワンピース"), vec!["one", "piece", "ワン", "ンピ", "ピー", "ース"]);
+    }
+
+    #[test]
+    fn test_edit_distance_counts_transpositions() {
+        assert_eq!(edit_distance("peice", "piece", 1), Some(1));
+        assert_eq!(edit_distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+    }
+
+    #[test]
+    fn test_search_is_typo_and_accent_tolerant() {
+        let index = index();
+        assert_eq!(ids(index.search("one peice", 10)), vec!["op"]);
+        assert_eq!(ids(index.search("shingéki", 10)), vec!["aot"]);
+        assert_eq!(ids(index.search("isayama", 10)), vec!["aot"]);
+        assert!(index.search("bleach", 10).is_empty());
+    }
+
+    #[test]
+    fn test_typos_only_considered_without_exact_matches() {
+        let index = index();
+        index.upsert(&manga("pk", "Pirate King", &[], "Someone"));
+        index.upsert(&manga("pp", "Private Pirate", &[], "Someone"));
+        index.upsert(&manga("pl", "Private Lessons", &[], "Someone"));
+        let mut found = ids(index.search("pirate", 10));
+        found.sort();
+        assert_eq!(found, vec!["pk", "pp"]);
+        assert_eq!(ids(index.search("privte lessons", 10)), vec!["pl"]);
+    }
+
+    #[test]
+    fn test_search_matches_cjk_titles() {
+        let index = index();
+        assert_eq!(ids(index.search("進撃", 10)), vec!["aot"]);
+        assert_eq!(ids(index.search("ワンピース", 10)), vec!["op"]);
+        assert_eq!(ids(index.search("레벨업", 10)), vec!["solo"]);
+    }
+
+    #[test]
+    fn test_localized_titles_are_searchable_and_suggested() {
+        let index = SearchIndex::new();
+        let manga: Manga = serde_json::from_value(serde_json::json!({
+            "manga_id": "frieren",
+            "title": "Frieren: Beyond Journey's End",
+            "titles": [
+                { "language": "ja", "title": "葬送のフリーレン" },
+                { "language": "ja-ro", "title": "Sousou no Frieren" },
+                { "language": "ko", "title": "장송의 프리렌" }
+            ]
+        }))
+        .unwrap();
+        index.upsert(&manga);
+
+        assert_eq!(ids(index.search("sousou", 10)), vec!["frieren"]);
+        assert_eq!(ids(index.search("葬送", 10)), vec!["frieren"]);
+        let content = ContentFilter::default();
+        assert_eq!(index.autocomplete("frieren", 5, Some("ko"), &content), vec!["장송의 프리렌"]);
+        assert_eq!(index.autocomplete("frieren", 5, Some("fr"), &content), vec!["Frieren: Beyond Journey's End"]);
+    }
+
+    #[test]
+    fn test_autocomplete_and_updates() {
+        let index = index();
+        let content = ContentFilter::default();
+        assert_eq!(index.autocomplete("atta", 5, None, &content), vec!["Attack on Titan"]);
+
+        let mut mature = manga("atx", "Attack Extreme", &[], "Anon");
+        mature.content_rating = Some("erotica".to_string());
+        index.upsert(&mature);
+        assert_eq!(index.autocomplete("atta", 5, None, &content), vec!["Attack on Titan"]);
+
+        index.upsert(&manga("op", "One Punch Man", &[], "ONE"));
+        assert!(index.search("piece", 10).is_empty());
+        assert_eq!(ids(index.search("punch", 10)), vec!["op"]);
+        assert_eq!(index.document_count(), 4);
+    }
+}
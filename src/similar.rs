@@ -0,0 +1,265 @@
+use crate::cache::{cache_keys, CacheService};
+use crate::content_filter::ContentFilter;
+use crate::embedding::{cosine, EmbeddingProvider};
+use crate::manga_service::{Manga, MangaService};
+use crate::recommendations::tag_jaccard;
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Weights of the three signals in the combined score
+const EMBEDDING_WEIGHT: f32 = 0.6;
+const TAG_WEIGHT: f32 = 0.3;
+const CREATOR_WEIGHT: f32 = 0.1;
+
+/// Candidates gathered from each source
+const CANDIDATES: usize = 50;
+
+/// Ranked neighbours kept in the cache; requests page into this list
+const MAX_SIMILAR: usize = 50;
+
+/// Neighbour lists are also refreshed when an embedding changes
+const CACHE_TTL_SECS: u64 = 86400;
+
+/// How close another series is to a given one, and why
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SimilarScore {
+    pub manga_id: String,
+    pub score: f32,
+    pub embedding_similarity: Option<f32>,
+    pub tag_overlap: f32,
+    pub shared_creator: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarManga {
+    pub manga: Manga,
+    #[serde(flatten)]
+    pub similarity: SimilarScore,
+}
+
+fn same_creator(a: Option<&str>, b: Option<&str>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if !a.trim().is_empty() && a.trim().eq_ignore_ascii_case(b.trim()))
+}
+
+/// Score `other` against `base` from embedding similarity (when both were
+/// embedded by the same model), tag Jaccard overlap and a shared author or
+/// artist
+pub fn score_similarity(base: &Manga, other: &Manga) -> SimilarScore {
+    let embedding_similarity = match (&base.embedding, &other.embedding) {
+        (Some(a), Some(b)) if base.embedding_model == other.embedding_model => cosine(a, b),
+        _ => None,
+    };
+    let tag_overlap = tag_jaccard(
+        base.tags.as_deref().unwrap_or_default(),
+        other.tags.as_deref().unwrap_or_default(),
+    );
+    fn creators(manga: &Manga) -> [Option<&str>; 2] {
+        [manga.author.as_deref(), manga.artist.as_deref()]
+    }
+    let shared_creator = creators(base)
+        .into_iter()
+        .any(|a| creators(other).into_iter().any(|b| same_creator(a, b)));
+
+    let score = EMBEDDING_WEIGHT * embedding_similarity.unwrap_or(0.0).max(0.0)
+        + TAG_WEIGHT * tag_overlap
+        + if shared_creator { CREATOR_WEIGHT } else { 0.0 };
+    SimilarScore {
+        manga_id: other.manga_id.clone().unwrap_or_default(),
+        score,
+        embedding_similarity,
+        tag_overlap,
+        shared_creator,
+    }
+}
+
+/// "More like this" for a series page
+#[derive(Clone)]
+pub struct SimilarService {
+    manga_service: MangaService,
+    cache: Option<CacheService>,
+    embeddings: Option<Arc<dyn EmbeddingProvider>>,
+}
+
+impl SimilarService {
+    pub fn new(
+        manga_service: MangaService,
+        cache: Option<CacheService>,
+        embeddings: Option<Arc<dyn EmbeddingProvider>>,
+    ) -> Self {
+        Self {
+            manga_service,
+            cache,
+            embeddings,
+        }
+    }
+
+    /// Series most like `manga_id`, or `None` if it is not in the catalog
+    pub async fn similar(
+        &self,
+        manga_id: &str,
+        limit: usize,
+        content: &ContentFilter,
+    ) -> Result<Option<Vec<SimilarManga>>, Box<dyn std::error::Error>> {
+        let key = cache_keys::manga_similar(manga_id);
+        let cached = match &self.cache {
+            Some(cache) => cache.get::<Vec<SimilarScore>>(&key).await.ok().flatten(),
+            None => None,
+        };
+        let scores = match cached {
+            Some(scores) => scores,
+            None => {
+                let Some(scores) = self.rank(manga_id).await? else {
+                    return Ok(None);
+                };
+                if let Some(cache) = &self.cache {
+                    let _ = cache.set(&key, &scores, CACHE_TTL_SECS).await;
+                }
+                scores
+            }
+        };
+
+        // Ranking is shared by everyone; content filtering is per reader
+        let ids: Vec<&str> = scores.iter().map(|s| s.manga_id.as_str()).collect();
+        let mut filter = doc! { "manga_id": { "$in": &ids } };
+        content.apply(&mut filter);
+        let mut cursor = self
+            .manga_service
+            .manga_collection()
+            .find(filter)
+            .projection(doc! { "embedding": 0 })
+            .await?;
+        let mut found: HashMap<String, Manga> = HashMap::new();
+        while cursor.advance().await? {
+            let mut manga: Manga = cursor.deserialize_current()?;
+            manga.localize(content.preferred_language.as_deref());
+            if let Some(id) = manga.manga_id.clone() {
+                found.insert(id, manga);
+            }
+        }
+
+        Ok(Some(
+            scores
+                .into_iter()
+                .filter_map(|similarity| {
+                    found
+                        .remove(&similarity.manga_id)
+                        .map(|manga| SimilarManga { manga, similarity })
+                })
+                .take(limit)
+                .collect(),
+        ))
+    }
+
+    /// Gather candidates by embedding, shared creators and shared tags, then
+    /// score them all the same way
+    async fn rank(&self, manga_id: &str) -> Result<Option<Vec<SimilarScore>>, Box<dyn std::error::Error>> {
+        let collection = self.manga_service.manga_collection();
+        let Some(base) = collection.find_one(doc! { "manga_id": manga_id }).await? else {
+            return Ok(None);
+        };
+        let others = doc! { "manga_id": { "$ne": manga_id } };
+        let mut candidates: HashMap<String, Manga> = HashMap::new();
+
+        let model = self.embeddings.as_ref().map(|e| e.model_id());
+        if let (Some(embedding), Some(model)) = (&base.embedding, model)
+            && base.embedding_model.as_deref() == Some(model.as_str())
+        {
+            let neighbours = self
+                .manga_service
+                .semantic_search(embedding, &model, CANDIDATES, others.clone())
+                .await?;
+            for (manga, _) in neighbours {
+                if let Some(id) = manga.manga_id.clone() {
+                    candidates.insert(id, manga);
+                }
+            }
+        }
+
+        let creators: Vec<&String> = base.author.iter().chain(base.artist.iter()).collect();
+        if !creators.is_empty() {
+            let filter = doc! {
+                "$and": [
+                    others.clone(),
+                    { "$or": [{ "author": { "$in": &creators } }, { "artist": { "$in": &creators } }] }
+                ]
+            };
+            self.collect(filter, &mut candidates).await?;
+        }
+
+        if let Some(tags) = base.tags.as_ref().filter(|t| !t.is_empty()) {
+            let filter = doc! { "$and": [others, { "tags": { "$in": tags } }] };
+            self.collect(filter, &mut candidates).await?;
+        }
+
+        // A series is never its own neighbour, whatever the queries returned
+        candidates.remove(manga_id);
+        let mut scores: Vec<SimilarScore> = candidates
+            .values()
+            .map(|other| score_similarity(&base, other))
+            .filter(|s| s.score > 0.0)
+            .collect();
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.manga_id.cmp(&b.manga_id)));
+        scores.truncate(MAX_SIMILAR);
+        Ok(Some(scores))
+    }
+
+    async fn collect(
+        &self,
+        filter: Document,
+        candidates: &mut HashMap<String, Manga>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .manga_service
+            .manga_collection()
+            .find(filter)
+            .sort(doc! { "popularity": -1 })
+            .limit(CANDIDATES as i64)
+            .await?;
+        while cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            if let Some(id) = manga.manga_id.clone() {
+                candidates.entry(id).or_insert(manga);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manga(id: &str, author: &str, tags: &[&str], embedding: Option<Vec<f32>>) -> Manga {
+        let mut manga: Manga = serde_json::from_value(serde_json::json!({
+            "manga_id": id,
+            "author": author,
+            "tags": tags,
+            "embedding_model": "hashing:2"
+        }))
+        .unwrap();
+        manga.embedding = embedding;
+        manga
+    }
+
+    #[test]
+    fn test_score_mixes_embedding_tags_and_creators() {
+        let base = manga("a", "Kentaro Miura", &["action", "fantasy"], Some(vec![1.0, 0.0]));
+        let same_author = manga("b", "kentaro miura ", &["fantasy"], Some(vec![0.0, 1.0]));
+        let close = manga("c", "Someone", &["action", "fantasy"], Some(vec![1.0, 0.1]));
+        let unembedded = manga("d", "", &["romance"], None);
+
+        let b = score_similarity(&base, &same_author);
+        assert!(b.shared_creator);
+        assert_eq!(b.tag_overlap, 0.5);
+        assert_eq!(b.embedding_similarity, Some(0.0));
+
+        let c = score_similarity(&base, &close);
+        assert!(!c.shared_creator);
+        assert!(c.score > b.score);
+
+        let d = score_similarity(&base, &unembedded);
+        assert_eq!((d.embedding_similarity, d.score), (None, 0.0));
+    }
+}
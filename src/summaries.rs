@@ -0,0 +1,464 @@
+use crate::ai_service::AIService;
+use crate::cache::{cache_keys, CacheService};
+use crate::embedding_worker::text_hash;
+use crate::manga_service::{AiSummary, Manga, MangaService};
+use crate::search_index::normalize;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Bump when the summary prompt changes, so every summary is regenerated
+pub const SUMMARY_VERSION: i32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+#[error("AI summaries need an LLM (set LLM_API_URL or LLM_API_KEY)")]
+pub struct SummariesUnavailable;
+
+#[derive(Debug, thiserror::Error)]
+#[error("The summary could not be written recently, try again later")]
+pub struct SummaryRecentlyFailed;
+
+/// The text a summary is written from
+pub fn summary_source(manga: &Manga) -> String {
+    format!(
+        "{}\n{}\n{}",
+        manga.title.as_deref().unwrap_or_default(),
+        manga.description.as_deref().unwrap_or_default(),
+        manga.status.as_deref().unwrap_or_default()
+    )
+}
+
+/// Whether a manga's stored summary was written by `model` from its
+/// current text with the current prompt
+pub fn summary_is_current(manga: &Manga, model: &str) -> bool {
+    manga.ai_summary.as_ref().is_some_and(|summary| {
+        summary.model == model
+            && summary.version == SUMMARY_VERSION
+            && summary.source_hash == text_hash(&summary_source(manga))
+    })
+}
+
+/// Suggested genres that are not yet tags of the manga, spelled the way the
+/// catalog spells them when it already has the tag
+pub fn proposed_tags(genres: &[String], current: &[String], vocabulary: &[String]) -> Vec<String> {
+    let canonical: HashMap<String, &String> = vocabulary.iter().map(|tag| (normalize(tag), tag)).collect();
+    let current: Vec<String> = current.iter().map(|tag| normalize(tag)).collect();
+    let mut proposed: Vec<String> = Vec::new();
+    for genre in genres {
+        let genre = genre.trim();
+        let key = normalize(genre);
+        if key.is_empty() || current.contains(&key) || proposed.iter().any(|p| normalize(p) == key) {
+            continue;
+        }
+        proposed.push(canonical.get(&key).map_or_else(|| genre.to_string(), |tag| (*tag).clone()));
+    }
+    proposed
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// A tag the LLM suggested for a manga, merged into its tags only once an
+/// admin approves it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TagProposal {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub manga_id: String,
+    pub manga_title: Option<String>,
+    pub tag: String,
+    pub status: ProposalStatus,
+    /// Model that suggested the tag
+    pub model: String,
+    pub created_at: String,
+    pub reviewed_at: Option<String>,
+    pub reviewed_by: Option<String>,
+}
+
+/// Generates series summaries on demand and in batches, and manages the
+/// tag proposals that come with them
+#[derive(Clone)]
+pub struct SummaryService {
+    manga_service: MangaService,
+    ai: Option<AIService>,
+    cache: Option<CacheService>,
+    interval: Duration,
+    batch_size: usize,
+    retry_after: Duration,
+    wake: Arc<Notify>,
+}
+
+impl SummaryService {
+    pub fn new(manga_service: MangaService, ai: Option<AIService>, cache: Option<CacheService>) -> Self {
+        let interval_secs = env::var("SUMMARY_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+        let batch_size = env::var("SUMMARY_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(20);
+        let retry_after_secs = env::var("SUMMARY_RETRY_AFTER_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(604800);
+
+        Self {
+            manga_service,
+            ai,
+            cache,
+            interval: Duration::from_secs(interval_secs),
+            batch_size,
+            retry_after: Duration::from_secs(retry_after_secs),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    fn proposals_collection(&self) -> Collection<TagProposal> {
+        self.manga_service.db().collection("tag_proposals")
+    }
+
+    async fn create_indexes(&self) -> Result<(), Box<dyn std::error::Error>> {
+        use mongodb::{options::IndexOptions, IndexModel};
+
+        // One proposal per manga and tag, so a rejected tag stays rejected
+        self.proposals_collection()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "manga_id": 1, "tag": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+        self.proposals_collection()
+            .create_index(IndexModel::builder().keys(doc! { "status": 1, "created_at": -1 }).build())
+            .await?;
+        Ok(())
+    }
+
+    /// Start a batch now instead of waiting for the interval
+    pub fn trigger(&self) -> Result<(), SummariesUnavailable> {
+        self.ai.as_ref().ok_or(SummariesUnavailable)?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Summarize a batch of manga now and then on every interval or trigger
+    pub fn spawn_scheduler(self) {
+        tokio::spawn(async move {
+            if let Err(e) = self.create_indexes().await {
+                tracing::warn!("⚠️  Failed to create tag proposal indexes: {}", e);
+            }
+            if self.ai.is_none() {
+                return;
+            }
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = self.wake.notified() => {}
+                }
+                match self.run_batch().await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("📝 Summarized {} manga", count),
+                    Err(e) => tracing::error!("Summary batch failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Summarize up to one batch of manga whose summary is missing or
+    /// stale; returns how many were written. Manga that failed recently are
+    /// left out so they cannot hold up every batch.
+    pub async fn run_batch(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let ai = self.ai.as_ref().ok_or(SummariesUnavailable)?;
+        let model = ai.model_id();
+        let mut cursor = self
+            .manga_service
+            .manga_collection()
+            .find(doc! {
+                "$or": [
+                    { "ai_summary_failed_at": null },
+                    { "ai_summary_failed_at": { "$lt": self.retry_cutoff() } }
+                ]
+            })
+            .projection(doc! { "embedding": 0, "chapters": 0 })
+            .sort(doc! { "popularity": -1 })
+            .await?;
+
+        let mut pending = Vec::new();
+        while pending.len() < self.batch_size && cursor.advance().await? {
+            let manga: Manga = cursor.deserialize_current()?;
+            if !summary_is_current(&manga, &model) {
+                pending.push(manga);
+            }
+        }
+
+        let vocabulary = self.manga_service.tag_vocabulary().await?;
+        let mut written = 0;
+        for manga in &pending {
+            // Only the message is kept, so the batch future stays `Send`
+            let result = self.generate(ai, manga, &vocabulary).await.map_err(|e| e.to_string());
+            match result {
+                Ok(_) => written += 1,
+                Err(e) => {
+                    let manga_id = manga.manga_id.as_deref().unwrap_or_default();
+                    tracing::warn!("⚠️  Summary for {} failed: {}", manga_id, e);
+                    self.record_failure(manga_id).await?;
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    /// A manga's summary, written now when it is missing or stale. The flag
+    /// is true when a stale summary is served because no fresh one could be
+    /// written. `None` when the manga is not in the catalog.
+    pub async fn summary(&self, manga_id: &str) -> Result<Option<(AiSummary, bool)>, Box<dyn std::error::Error>> {
+        let Some(manga) = self
+            .manga_service
+            .manga_collection()
+            .find_one(doc! { "manga_id": manga_id })
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let Some(ai) = &self.ai else {
+            return match manga.ai_summary {
+                Some(summary) => Ok(Some((summary, true))),
+                None => Err(SummariesUnavailable.into()),
+            };
+        };
+        if summary_is_current(&manga, &ai.model_id())
+            && let Some(summary) = manga.ai_summary
+        {
+            return Ok(Some((summary, false)));
+        }
+        // Within the retry window a failing series costs no LLM calls
+        if manga
+            .ai_summary_failed_at
+            .as_deref()
+            .is_some_and(|failed_at| failed_at >= self.retry_cutoff().as_str())
+        {
+            return match manga.ai_summary {
+                Some(summary) => Ok(Some((summary, true))),
+                None => Err(SummaryRecentlyFailed.into()),
+            };
+        }
+
+        let vocabulary = self.manga_service.tag_vocabulary().await?;
+        // Only the message is kept, so the future stays `Send`
+        let result = self.generate(ai, &manga, &vocabulary).await.map_err(|e| e.to_string());
+        match result {
+            Ok(summary) => Ok(Some((summary, false))),
+            Err(e) => {
+                self.record_failure(manga_id).await?;
+                match manga.ai_summary {
+                    Some(summary) => {
+                        tracing::warn!("⚠️  Serving stale summary for {}: {}", manga_id, e);
+                        Ok(Some((summary, true)))
+                    }
+                    None => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    /// Failures at or after this time keep a series from being retried
+    fn retry_cutoff(&self) -> String {
+        (chrono::Utc::now() - self.retry_after).to_rfc3339()
+    }
+
+    async fn record_failure(&self, manga_id: &str) -> Result<(), mongodb::error::Error> {
+        self.manga_service
+            .manga_collection()
+            .update_one(
+                doc! { "manga_id": manga_id },
+                doc! { "$set": { "ai_summary_failed_at": chrono::Utc::now().to_rfc3339() } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Write and store a summary, filing its genres as tag proposals
+    async fn generate(
+        &self,
+        ai: &AIService,
+        manga: &Manga,
+        vocabulary: &[String],
+    ) -> Result<AiSummary, Box<dyn std::error::Error>> {
+        let manga_id = manga.manga_id.as_deref().ok_or("manga has no manga_id")?;
+        let generated = ai.generate_manga_summary(manga, vocabulary).await?;
+        let summary = AiSummary {
+            summary: generated.summary.trim().to_string(),
+            target_audience: generated.target_audience.trim().to_string(),
+            model: ai.model_id(),
+            version: SUMMARY_VERSION,
+            source_hash: text_hash(&summary_source(manga)),
+            generated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.manga_service
+            .manga_collection()
+            .update_one(
+                doc! { "manga_id": manga_id },
+                doc! {
+                    "$set": { "ai_summary": mongodb::bson::to_bson(&summary)? },
+                    "$unset": { "ai_summary_failed_at": "" }
+                },
+            )
+            .await?;
+
+        let current = manga.tags.as_deref().unwrap_or_default();
+        let now = chrono::Utc::now().to_rfc3339();
+        for tag in proposed_tags(&generated.genres, current, vocabulary) {
+            self.proposals_collection()
+                .update_one(
+                    doc! { "manga_id": manga_id, "tag": &tag },
+                    doc! {
+                        "$setOnInsert": {
+                            "manga_title": &manga.title,
+                            "status": "pending",
+                            "model": &summary.model,
+                            "created_at": &now,
+                            "reviewed_at": null,
+                            "reviewed_by": null
+                        }
+                    },
+                )
+                .upsert(true)
+                .await?;
+        }
+        Ok(summary)
+    }
+
+    pub async fn proposals(
+        &self,
+        status: ProposalStatus,
+        limit: u32,
+    ) -> Result<Vec<TagProposal>, Box<dyn std::error::Error>> {
+        let mut cursor = self
+            .proposals_collection()
+            .find(doc! { "status": mongodb::bson::to_bson(&status)? })
+            .sort(doc! { "created_at": -1 })
+            .limit(limit.min(500) as i64)
+            .await?;
+
+        let mut results = Vec::new();
+        while cursor.advance().await? {
+            results.push(cursor.deserialize_current()?);
+        }
+        Ok(results)
+    }
+
+    /// Approve or reject a pending proposal. Approved tags are merged into
+    /// the manga's tags. `None` when there is no such pending proposal.
+    pub async fn review(
+        &self,
+        proposal_id: &str,
+        approve: bool,
+        admin_user_id: &str,
+    ) -> Result<Option<TagProposal>, Box<dyn std::error::Error>> {
+        let id = ObjectId::parse_str(proposal_id)?;
+        let proposals = self.proposals_collection();
+        let Some(mut proposal) = proposals.find_one(doc! { "_id": id, "status": "pending" }).await? else {
+            return Ok(None);
+        };
+
+        if approve {
+            let collection = self.manga_service.manga_collection();
+            // Untagged series store `tags: null`, which `$addToSet` refuses
+            collection
+                .update_one(
+                    doc! { "manga_id": &proposal.manga_id, "tags": null },
+                    doc! { "$set": { "tags": [] } },
+                )
+                .await?;
+            collection
+                .update_one(
+                    doc! { "manga_id": &proposal.manga_id },
+                    doc! { "$addToSet": { "tags": &proposal.tag } },
+                )
+                .await?;
+            if let Some(manga) = collection.find_one(doc! { "manga_id": &proposal.manga_id }).await? {
+                self.manga_service.search_index().upsert(&manga);
+            }
+            // Similar series are partly ranked by tag overlap
+            if let Some(cache) = &self.cache {
+                let _ = cache.delete(&cache_keys::manga_similar(&proposal.manga_id)).await;
+            }
+        }
+
+        proposal.status = if approve { ProposalStatus::Approved } else { ProposalStatus::Rejected };
+        proposal.reviewed_at = Some(chrono::Utc::now().to_rfc3339());
+        proposal.reviewed_by = Some(admin_user_id.to_string());
+        proposals
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": {
+                        "status": mongodb::bson::to_bson(&proposal.status)?,
+                        "reviewed_at": &proposal.reviewed_at,
+                        "reviewed_by": &proposal.reviewed_by
+                    }
+                },
+            )
+            .await?;
+        Ok(Some(proposal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_proposed_tags_skip_existing_and_use_catalog_spelling() {
+        let proposed = proposed_tags(
+            &strings(&["romance", "Comedy", " sci-fi ", "Found Family", "comedy", ""]),
+            &strings(&["Romance"]),
+            &strings(&["Comedy", "Romance", "Sci-Fi"]),
+        );
+        assert_eq!(proposed, strings(&["Comedy", "Sci-Fi", "Found Family"]));
+    }
+
+    #[test]
+    fn test_summary_goes_stale_with_text_model_or_version() {
+        let mut manga: Manga = serde_json::from_value(serde_json::json!({
+            "manga_id": "a",
+            "title": "Berserk",
+            "description": "A dark fantasy",
+            "status": "ongoing"
+        }))
+        .unwrap();
+        assert!(!summary_is_current(&manga, "gpt-4o-mini"));
+
+        manga.ai_summary = Some(AiSummary {
+            summary: "Guts fights demons.".to_string(),
+            target_audience: "seinen".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            version: SUMMARY_VERSION,
+            source_hash: text_hash(&summary_source(&manga)),
+            generated_at: "2024-01-01T00:00:00Z".to_string(),
+        });
+        assert!(summary_is_current(&manga, "gpt-4o-mini"));
+        assert!(!summary_is_current(&manga, "llama3"));
+
+        manga.status = Some("completed".to_string());
+        assert!(!summary_is_current(&manga, "gpt-4o-mini"));
+    }
+}
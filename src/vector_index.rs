@@ -0,0 +1,753 @@
+use crate::embedding::{dot, normalized};
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Links per node on upper layers; layer 0 keeps twice as many
+const M: usize = 16;
+const M0: usize = M * 2;
+
+/// Candidate list size while inserting
+const EF_CONSTRUCTION: usize = 100;
+
+/// Candidate list size while searching (raised to `k` when smaller)
+const EF_SEARCH: usize = 64;
+
+/// Rebuild the graph once this share of nodes are stale replacements
+const MAX_DELETED_RATIO: f32 = 0.3;
+
+const FILE_MAGIC: &[u8; 8] = b"MVHNSW01";
+
+/// Sanity limits for a loaded file, checked before allocating
+const MAX_DIMENSIONS: usize = 16_384;
+const MAX_ID_LEN: usize = 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VectorIndexError {
+    #[error("Embedding has {got} dimensions, index uses {expected}")]
+    DimensionMismatch { expected: usize, got: usize },
+    #[error("Embedding is empty or all zeros")]
+    ZeroVector,
+    #[error("Vector index file is corrupt: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// One nearest neighbour; `score` is the cosine similarity
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorHit {
+    pub manga_id: String,
+    pub score: f32,
+}
+
+struct Node {
+    manga_id: String,
+    vector: Vec<f32>,
+    // Neighbours per layer, layer 0 first
+    links: Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+/// (distance, node) ordered by distance
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate(f32, usize);
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Cosine distance between normalized vectors
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - dot(a, b)
+}
+
+/// Hierarchical navigable small world graph over normalized embeddings.
+/// Replacing a manga's embedding leaves a tombstone that search skips; the
+/// graph is rebuilt once too many accumulate.
+pub struct Hnsw {
+    dimensions: usize,
+    nodes: Vec<Node>,
+    by_id: HashMap<String, usize>,
+    entry: Option<usize>,
+    deleted: usize,
+    rng: u64,
+}
+
+impl Default for Hnsw {
+    fn default() -> Self {
+        Self {
+            dimensions: 0,
+            nodes: Vec::new(),
+            by_id: HashMap::new(),
+            entry: None,
+            deleted: 0,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+}
+
+impl Hnsw {
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// Whether `manga_id` is indexed with (a vector parallel to) `vector`
+    pub fn contains(&self, manga_id: &str, vector: &[f32]) -> bool {
+        let Some(&node) = self.by_id.get(manga_id) else {
+            return false;
+        };
+        normalized(vector).is_some_and(|v| {
+            v.len() == self.dimensions && distance(&v, &self.nodes[node].vector) < 1e-6
+        })
+    }
+
+    pub fn insert(&mut self, manga_id: &str, vector: &[f32]) -> Result<(), VectorIndexError> {
+        let vector = normalized(vector).ok_or(VectorIndexError::ZeroVector)?;
+        if self.is_empty() {
+            // Nothing live to stay compatible with, e.g. after switching models
+            *self = Hnsw {
+                dimensions: vector.len(),
+                rng: self.rng,
+                ..Default::default()
+            };
+        }
+        if vector.len() != self.dimensions {
+            return Err(VectorIndexError::DimensionMismatch {
+                expected: self.dimensions,
+                got: vector.len(),
+            });
+        }
+
+        if let Some(&existing) = self.by_id.get(manga_id) {
+            if distance(&vector, &self.nodes[existing].vector) < 1e-6 {
+                return Ok(());
+            }
+            self.remove(manga_id);
+        }
+        self.link(manga_id.to_string(), vector);
+
+        if self.deleted as f32 > self.nodes.len() as f32 * MAX_DELETED_RATIO {
+            self.compact();
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, manga_id: &str) -> bool {
+        match self.by_id.remove(manga_id) {
+            Some(node) => {
+                self.nodes[node].deleted = true;
+                self.deleted += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The `k` nearest live nodes to `query`
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<VectorHit> {
+        let (Some(entry), Some(query)) = (self.entry, normalized(query)) else {
+            return Vec::new();
+        };
+        if query.len() != self.dimensions || k == 0 {
+            return Vec::new();
+        }
+
+        let mut current = entry;
+        for layer in (1..self.nodes[entry].links.len()).rev() {
+            current = self.greedy(&query, current, layer);
+        }
+        // Tombstones still take up candidate slots, so widen the search
+        let ef = EF_SEARCH.max(k) + self.deleted.min(EF_SEARCH);
+        self.search_layer(&query, &[current], ef, 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.1].deleted)
+            .take(k)
+            .map(|Candidate(dist, node)| VectorHit {
+                manga_id: self.nodes[node].manga_id.clone(),
+                score: 1.0 - dist,
+            })
+            .collect()
+    }
+
+    fn random_level(&mut self) -> usize {
+        // xorshift64*; levels only need to be geometric, not unpredictable
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        let uniform = ((bits >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() / (M as f64).ln()).floor() as usize
+    }
+
+    fn link(&mut self, manga_id: String, vector: Vec<f32>) {
+        let level = self.random_level();
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            manga_id: manga_id.clone(),
+            vector,
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.by_id.insert(manga_id, node);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return;
+        };
+
+        let query = self.nodes[node].vector.clone();
+        let top = self.nodes[entry].links.len() - 1;
+        let mut current = entry;
+        for layer in (level + 1..=top).rev() {
+            current = self.greedy(&query, current, layer);
+        }
+
+        let mut entry_points = vec![current];
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(&query, &entry_points, EF_CONSTRUCTION, layer);
+            let max_links = if layer == 0 { M0 } else { M };
+            let neighbours: Vec<u32> = candidates
+                .iter()
+                .filter(|c| c.1 != node)
+                .take(M)
+                .map(|c| c.1 as u32)
+                .collect();
+            self.nodes[node].links[layer] = neighbours.clone();
+            for neighbour in neighbours {
+                let neighbour = neighbour as usize;
+                self.nodes[neighbour].links[layer].push(node as u32);
+                if self.nodes[neighbour].links[layer].len() > max_links {
+                    self.prune(neighbour, layer, max_links);
+                }
+            }
+            entry_points = candidates.iter().map(|c| c.1).collect();
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Keep a node's closest `max_links` neighbours on a layer
+    fn prune(&mut self, node: usize, layer: usize, max_links: usize) {
+        let base = &self.nodes[node].vector;
+        let mut scored: Vec<Candidate> = self.nodes[node].links[layer]
+            .iter()
+            .map(|&n| Candidate(distance(base, &self.nodes[n as usize].vector), n as usize))
+            .collect();
+        scored.sort();
+        scored.truncate(max_links);
+        self.nodes[node].links[layer] = scored.into_iter().map(|c| c.1 as u32).collect();
+    }
+
+    /// Walk towards `query` on one layer until no neighbour is closer
+    fn greedy(&self, query: &[f32], start: usize, layer: usize) -> usize {
+        let mut current = start;
+        let mut best = distance(query, &self.nodes[current].vector);
+        loop {
+            let mut improved = false;
+            for &n in &self.nodes[current].links[layer] {
+                let d = distance(query, &self.nodes[n as usize].vector);
+                if d < best {
+                    best = d;
+                    current = n as usize;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    /// Best-first search on one layer; returns up to `ef` nodes, closest first
+    fn search_layer(&self, query: &[f32], entry_points: &[usize], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().copied().collect();
+        let mut frontier: BinaryHeap<Reverse<Candidate>> = BinaryHeap::new();
+        let mut found: BinaryHeap<Candidate> = BinaryHeap::new();
+        for &point in entry_points {
+            let candidate = Candidate(distance(query, &self.nodes[point].vector), point);
+            frontier.push(Reverse(candidate));
+            found.push(candidate);
+        }
+        while found.len() > ef {
+            found.pop();
+        }
+
+        while let Some(Reverse(closest)) = frontier.pop() {
+            let furthest = found.peek().map_or(f32::INFINITY, |c| c.0);
+            if closest.0 > furthest && found.len() >= ef {
+                break;
+            }
+            let Some(links) = self.nodes[closest.1].links.get(layer) else {
+                continue;
+            };
+            for &n in links {
+                let n = n as usize;
+                if !visited.insert(n) {
+                    continue;
+                }
+                let d = distance(query, &self.nodes[n].vector);
+                let furthest = found.peek().map_or(f32::INFINITY, |c| c.0);
+                if found.len() < ef || d < furthest {
+                    frontier.push(Reverse(Candidate(d, n)));
+                    found.push(Candidate(d, n));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Rebuild the graph from live nodes, dropping tombstones
+    fn compact(&mut self) {
+        let live: Vec<(String, Vec<f32>)> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .filter(|node| !node.deleted)
+            .map(|node| (node.manga_id, node.vector))
+            .collect();
+        self.by_id.clear();
+        self.entry = None;
+        self.deleted = 0;
+        for (manga_id, vector) in live {
+            self.link(manga_id, vector);
+        }
+    }
+
+    /// Serialize the graph, tombstones included
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(FILE_MAGIC)?;
+        write_u32(writer, self.dimensions as u32)?;
+        write_u32(writer, self.nodes.len() as u32)?;
+        write_u32(writer, self.entry.map_or(u32::MAX, |e| e as u32))?;
+        writer.write_all(&self.rng.to_le_bytes())?;
+        for node in &self.nodes {
+            let id = node.manga_id.as_bytes();
+            write_u32(writer, id.len() as u32)?;
+            writer.write_all(id)?;
+            writer.write_all(&[node.deleted as u8, node.links.len() as u8])?;
+            for value in &node.vector {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            for links in &node.links {
+                write_u32(writer, links.len() as u32)?;
+                for link in links {
+                    write_u32(writer, *link)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, VectorIndexError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(VectorIndexError::Corrupt("unknown file format".to_string()));
+        }
+        let dimensions = read_u32(reader)? as usize;
+        if dimensions > MAX_DIMENSIONS {
+            return Err(VectorIndexError::Corrupt(format!("{} dimensions", dimensions)));
+        }
+        let count = read_u32(reader)? as usize;
+        let entry = read_u32(reader)?;
+        let mut rng = [0u8; 8];
+        reader.read_exact(&mut rng)?;
+
+        let mut index = Hnsw {
+            dimensions,
+            rng: u64::from_le_bytes(rng),
+            ..Default::default()
+        };
+        for node in 0..count {
+            let id_len = read_u32(reader)? as usize;
+            if id_len > MAX_ID_LEN {
+                return Err(VectorIndexError::Corrupt("manga id too long".to_string()));
+            }
+            let mut id = vec![0u8; id_len];
+            reader.read_exact(&mut id)?;
+            let manga_id = String::from_utf8(id).map_err(|e| VectorIndexError::Corrupt(e.to_string()))?;
+            let mut flags = [0u8; 2];
+            reader.read_exact(&mut flags)?;
+            if flags[1] == 0 {
+                return Err(VectorIndexError::Corrupt("node without layers".to_string()));
+            }
+            let mut vector = Vec::with_capacity(dimensions);
+            for _ in 0..dimensions {
+                let mut value = [0u8; 4];
+                reader.read_exact(&mut value)?;
+                vector.push(f32::from_le_bytes(value));
+            }
+            let mut links = Vec::with_capacity(flags[1] as usize);
+            for _ in 0..flags[1] {
+                let len = read_u32(reader)? as usize;
+                if len > M0 {
+                    return Err(VectorIndexError::Corrupt("too many links".to_string()));
+                }
+                let mut layer = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let link = read_u32(reader)?;
+                    if link as usize >= count {
+                        return Err(VectorIndexError::Corrupt("link out of range".to_string()));
+                    }
+                    layer.push(link);
+                }
+                links.push(layer);
+            }
+            let deleted = flags[0] != 0;
+            if deleted {
+                index.deleted += 1;
+            } else {
+                index.by_id.insert(manga_id.clone(), node);
+            }
+            index.nodes.push(Node {
+                manga_id,
+                vector,
+                links,
+                deleted,
+            });
+        }
+        index.entry = match entry {
+            u32::MAX => None,
+            e if (e as usize) < count => Some(e as usize),
+            _ => return Err(VectorIndexError::Corrupt("entry point out of range".to_string())),
+        };
+
+        // Search walks down from the entry node and follows layer-l links
+        // only on layer l, so both have to exist
+        let top = index.entry.map_or(0, |e| index.nodes[e].links.len());
+        for node in &index.nodes {
+            if node.links.len() > top {
+                return Err(VectorIndexError::Corrupt("node above the entry point".to_string()));
+            }
+            for (layer, links) in node.links.iter().enumerate() {
+                if links.iter().any(|&n| index.nodes[n as usize].links.len() <= layer) {
+                    return Err(VectorIndexError::Corrupt("link to a node without that layer".to_string()));
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Shared handle to the embedding index, persisted to `path` so restarts only
+/// reconcile changes instead of rebuilding the graph
+#[derive(Clone)]
+pub struct VectorIndex {
+    inner: Arc<RwLock<Hnsw>>,
+    ready: Arc<AtomicBool>,
+    dirty: Arc<AtomicBool>,
+    path: PathBuf,
+    persist_interval: Duration,
+    // Embedding model the vectors came from, recorded in the file
+    model: Arc<RwLock<String>>,
+}
+
+impl VectorIndex {
+    pub fn new() -> Self {
+        let path = env::var("VECTOR_INDEX_PATH").unwrap_or_else(|_| "vector_index.bin".to_string());
+        let persist_secs = env::var("VECTOR_INDEX_PERSIST_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+
+        Self {
+            inner: Arc::new(RwLock::new(Hnsw::default())),
+            ready: Arc::new(AtomicBool::new(false)),
+            dirty: Arc::new(AtomicBool::new(false)),
+            path: PathBuf::from(path),
+            persist_interval: Duration::from_secs(persist_secs),
+            model: Arc::new(RwLock::new(String::new())),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.inner.read().unwrap().len()
+    }
+
+    pub fn contains(&self, manga_id: &str, vector: &[f32]) -> bool {
+        self.inner.read().unwrap().contains(manga_id, vector)
+    }
+
+    pub fn upsert(&self, manga_id: &str, vector: &[f32]) -> Result<(), VectorIndexError> {
+        self.inner.write().unwrap().insert(manga_id, vector)?;
+        self.dirty.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn remove(&self, manga_id: &str) -> bool {
+        let removed = self.inner.write().unwrap().remove(manga_id);
+        if removed {
+            self.dirty.store(true, Ordering::Release);
+        }
+        removed
+    }
+
+    /// Drop every manga `keep` rejects. Returns how many were removed.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) -> usize {
+        let mut inner = self.inner.write().unwrap();
+        let stale: Vec<String> = inner.by_id.keys().filter(|id| !keep(id)).cloned().collect();
+        for manga_id in &stale {
+            inner.remove(manga_id);
+        }
+        if !stale.is_empty() {
+            self.dirty.store(true, Ordering::Release);
+        }
+        stale.len()
+    }
+
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<VectorHit> {
+        self.inner.read().unwrap().search(query, k)
+    }
+
+    /// Load the persisted graph, if there is one for `model`. Returns how many
+    /// manga it holds.
+    pub fn load(&self, model: &str) -> Result<usize, VectorIndexError> {
+        *self.model.write().unwrap() = model.to_string();
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut reader = BufReader::new(file);
+        let model_len = read_u32(&mut reader)? as usize;
+        if model_len > 1024 {
+            return Err(VectorIndexError::Corrupt("model name too long".to_string()));
+        }
+        let mut file_model = vec![0u8; model_len];
+        reader.read_exact(&mut file_model)?;
+        if file_model != model.as_bytes() {
+            // Vectors from another model are useless; rebuild from scratch
+            return Ok(0);
+        }
+        let loaded = Hnsw::read_from(&mut reader)?;
+        let count = loaded.len();
+        *self.inner.write().unwrap() = loaded;
+        Ok(count)
+    }
+
+    /// Write the graph if it changed since the last save
+    pub fn persist(&self) -> Result<bool, VectorIndexError> {
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(false);
+        }
+        let result = self.write_file(&self.path);
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Release);
+        }
+        result.map(|_| true)
+    }
+
+    fn write_file(&self, path: &Path) -> Result<(), VectorIndexError> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        // Write next to the target and rename, so a crash never leaves a torn file
+        let tmp = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(fs::File::create(&tmp)?);
+            let model = self.model.read().unwrap().clone();
+            write_u32(&mut writer, model.len() as u32)?;
+            writer.write_all(model.as_bytes())?;
+            self.inner.read().unwrap().write_to(&mut writer)?;
+            writer.flush()?;
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Save changes every `VECTOR_INDEX_PERSIST_SECS`
+    pub fn spawn_persister(self) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.persist_interval);
+            loop {
+                ticker.tick().await;
+                let index = self.clone();
+                match tokio::task::spawn_blocking(move || index.persist()).await {
+                    Ok(Ok(true)) => tracing::debug!("💾 Saved vector index ({} manga)", self.document_count()),
+                    Ok(Ok(false)) => {}
+                    Ok(Err(e)) => tracing::warn!("⚠️  Failed to save vector index: {}", e),
+                    Err(e) => tracing::warn!("⚠️  Vector index save task failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random unit-ish vectors
+    fn vectors(count: usize, dimensions: usize) -> Vec<Vec<f32>> {
+        let mut state = 42u32;
+        (0..count)
+            .map(|_| {
+                (0..dimensions)
+                    .map(|_| {
+                        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                        (state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn brute_force(data: &[Vec<f32>], query: &[f32], k: usize) -> Vec<String> {
+        let query = normalized(query).unwrap();
+        let mut scored: Vec<(f32, usize)> = data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (distance(&query, &normalized(v).unwrap()), i))
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored.into_iter().take(k).map(|(_, i)| format!("m{}", i)).collect()
+    }
+
+    #[test]
+    fn test_search_finds_nearest_neighbours() {
+        let data = vectors(500, 16);
+        let mut index = Hnsw::default();
+        for (i, v) in data.iter().enumerate() {
+            index.insert(&format!("m{}", i), v).unwrap();
+        }
+
+        let mut recalled = 0;
+        for query in vectors(20, 16) {
+            let expected = brute_force(&data, &query, 10);
+            let found: Vec<String> = index.search(&query, 10).into_iter().map(|h| h.manga_id).collect();
+            recalled += found.iter().filter(|id| expected.contains(id)).count();
+        }
+        // HNSW is approximate; on this size it should find nearly everything
+        assert!(recalled >= 190, "recall too low: {}/200", recalled);
+
+        let exact = index.search(&data[7], 1);
+        assert_eq!(exact[0].manga_id, "m7");
+        assert!((exact[0].score - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_updates_replace_and_remove() {
+        let mut index = Hnsw::default();
+        index.insert("a", &[1.0, 0.0, 0.0]).unwrap();
+        index.insert("b", &[0.0, 1.0, 0.0]).unwrap();
+        assert!(matches!(
+            index.insert("c", &[1.0, 0.0]),
+            Err(VectorIndexError::DimensionMismatch { expected: 3, got: 2 })
+        ));
+        assert!(matches!(index.insert("c", &[0.0, 0.0, 0.0]), Err(VectorIndexError::ZeroVector)));
+
+        index.insert("a", &[0.0, 0.0, 1.0]).unwrap();
+        assert_eq!(index.len(), 2);
+        assert!(index.contains("a", &[0.0, 0.0, 2.0]));
+        assert_eq!(index.search(&[0.0, 0.0, 1.0], 1)[0].manga_id, "a");
+        assert_eq!(index.search(&[1.0, 0.0, 0.0], 3).len(), 2);
+
+        index.remove("b");
+        let ids: Vec<String> = index.search(&[0.0, 1.0, 0.0], 3).into_iter().map(|h| h.manga_id).collect();
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn test_round_trip_through_file_format() {
+        let data = vectors(50, 8);
+        let mut index = Hnsw::default();
+        for (i, v) in data.iter().enumerate() {
+            index.insert(&format!("m{}", i), v).unwrap();
+        }
+        index.remove("m3");
+
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        let loaded = Hnsw::read_from(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded.len(), 49);
+        assert_eq!(loaded.search(&data[10], 5), index.search(&data[10], 5));
+        assert!(Hnsw::read_from(&mut &bytes[..20]).is_err());
+        assert!(matches!(
+            Hnsw::read_from(&mut &b"not an index"[..]),
+            Err(VectorIndexError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_sizes_and_layers_are_rejected() {
+        fn header(dimensions: u32, count: u32) -> Vec<u8> {
+            let mut bytes = FILE_MAGIC.to_vec();
+            for value in [dimensions, count, 0] {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0u8; 8]);
+            bytes
+        }
+        fn node(bytes: &mut Vec<u8>, id: &str, layers: &[&[u32]]) {
+            bytes.extend((id.len() as u32).to_le_bytes());
+            bytes.extend(id.as_bytes());
+            bytes.extend([0, layers.len() as u8]);
+            bytes.extend(1.0f32.to_le_bytes());
+            for links in layers {
+                bytes.extend((links.len() as u32).to_le_bytes());
+                links.iter().for_each(|link| bytes.extend(link.to_le_bytes()));
+            }
+        }
+
+        let huge = header(u32::MAX, 1);
+        assert!(matches!(Hnsw::read_from(&mut huge.as_slice()), Err(VectorIndexError::Corrupt(_))));
+
+        let mut consistent = header(1, 2);
+        node(&mut consistent, "a", &[&[1], &[]]);
+        node(&mut consistent, "b", &[&[0]]);
+        assert_eq!(Hnsw::read_from(&mut consistent.as_slice()).unwrap().len(), 2);
+
+        let mut above_entry = header(1, 2);
+        node(&mut above_entry, "a", &[&[1]]);
+        node(&mut above_entry, "b", &[&[0], &[]]);
+        assert!(matches!(Hnsw::read_from(&mut above_entry.as_slice()), Err(VectorIndexError::Corrupt(_))));
+
+        let mut missing_layer = header(1, 2);
+        node(&mut missing_layer, "a", &[&[1], &[1]]);
+        node(&mut missing_layer, "b", &[&[0]]);
+        assert!(matches!(Hnsw::read_from(&mut missing_layer.as_slice()), Err(VectorIndexError::Corrupt(_))));
+    }
+}